@@ -1,14 +1,35 @@
 use crate::{Context, Error};
+use poise::serenity_prelude as serenity;
+use std::env;
 use tokio::time::{Duration, sleep};
 
+use super::filters::{parse_message_id, parse_regex, Filters};
+use crate::commands::modlog::modlog::{log_action, update_action, ModAction};
+use crate::commands::modlog::transcript::message_transcript;
+
+const DEFAULT_MAX_MESSAGES: u64 = 1000; // the most messages one /clear deletes, unless CLEAR_MAX_MESSAGES says otherwise
+const MAX_SCANNED: u64 = 5000; // how far back to look for messages that match the filters
+const MESSAGES_PER_REQUEST: u64 = 100; // the most discord returns, and bulk deletes, at once
+const BULK_DELETE_MAX_AGE: i64 = 14 * 24 * 60 * 60 * 1000 - 60 * 1000; // 14 days in ms, with a minute to spare
+const DISCORD_EPOCH: i64 = 1420070400000; // the first ms of 2015, where message ids start counting
+const PROGRESS_EVERY: usize = 10; // messages deleted one by one between progress updates
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+
 /// Delete messages in bulk.
 ///
-/// Maximum messages that can be deleted at once are 100.
+/// Maximum messages that can be deleted at once are 1000 (or CLEAR_MAX_MESSAGES).
+/// Messages older than 14 days can't be bulk deleted, so they're deleted one by one, which is slower.
+/// Filters are optional, and a message has to match all of them to be deleted.
+/// With filters, up to the last 5000 messages are searched for matches.
 /// **Usage:**
-/// `/clear <number>`
+/// `/clear <number> [user] [bots] [attachments] [embeds] [links] [contains] [regex] [after] [before]`
+/// `after` and `before` take message ids or links.
+/// As a prefix command, the flags go before the text to look for, e.g. `&clear 20 bots links "spam"`.
 ///
 /// **Example:**
 /// `/clear 50` will delete the last 50 messages.
+/// `/clear 30 @spammer` will delete the last 30 messages from @spammer.
+/// `/clear 10 links` will delete the last 10 messages with links.
 #[poise::command(
     prefix_command,
     slash_command,
@@ -16,42 +37,279 @@ use tokio::time::{Duration, sleep};
     defer_response,
     required_permissions = "MANAGE_MESSAGES"
 )]
+#[allow(clippy::too_many_arguments)]
 pub async fn clear(
     ctx: Context<'_>,
     #[description = "The number of messages to delete"] number: u64,
+    #[description = "Only delete messages from this user"] user: Option<serenity::User>,
+    #[description = "Only delete messages from bots"]
+    #[flag]
+    bots: bool,
+    // the flags come before the text options, or a prefix command would take a flag as the text
+    #[description = "Only delete messages with attachments"]
+    #[flag]
+    attachments: bool,
+    #[description = "Only delete messages with embeds"]
+    #[flag]
+    embeds: bool,
+    #[description = "Only delete messages with links"]
+    #[flag]
+    links: bool,
+    #[description = "Only delete messages containing this text"] contains: Option<String>,
+    #[description = "Only delete messages matching this regex"] regex: Option<String>,
+    #[description = "Only delete messages after this message (id or link)"] after: Option<String>,
+    #[description = "Only delete messages before this message (id or link)"] before: Option<String>,
 ) -> Result<(), Error> {
-    // get the last `number` messages
-    let messages = ctx
-        .channel_id()
-        .messages(&ctx.discord().http, |messages| {
-            messages.before(ctx.id());
-            messages.limit(number);
-
-            messages
-        })
-        .await?;
+    let max_messages = max_messages();
+    if number == 0 || number > max_messages {
+        poise::say_reply(ctx, format!("You can delete between 1 and {} messages at once.", max_messages)).await?;
+        return Ok(());
+    }
+
+    let regex = match regex.as_deref().map(parse_regex).transpose() {
+        Ok(regex) => regex,
+        Err(reason) => {
+            poise::say_reply(ctx, reason).await?;
+            return Ok(());
+        }
+    };
+    let after = match after.as_deref().map(|after| parse_message_id(after).ok_or(after)).transpose() {
+        Ok(after) => after,
+        Err(after) => {
+            poise::say_reply(ctx, format!("\"{}\" isn't a message id or link.", after)).await?;
+            return Ok(());
+        }
+    };
+    let before = match before.as_deref().map(|before| parse_message_id(before).ok_or(before)).transpose() {
+        Ok(before) => before,
+        Err(before) => {
+            poise::say_reply(ctx, format!("\"{}\" isn't a message id or link.", before)).await?;
+            return Ok(());
+        }
+    };
+
+    let filters = Filters {
+        user: user.map(|user| user.id),
+        bots,
+        contains: contains.map(|text| text.to_lowercase()).filter(|text| !text.is_empty()),
+        regex,
+        attachments,
+        embeds,
+        links,
+        after,
+        before,
+    };
+
+    // look through the channel, newest first, until there are enough matches
+    let (messages, scanned) = find_messages(ctx, &filters, number).await?;
+    if messages.is_empty() {
+        poise::say_reply(ctx, format!("None of the last {} messages matched.", scanned)).await?;
+        return Ok(());
+    }
+    let found = messages.len();
+
+    // the mod log gets a transcript before anything is gone
+    let mut action = ModAction {
+        name: String::from("Clear"),
+        channel_id: ctx.channel_id(),
+        fields: clear_fields(&filters, scanned, format!("Deleting {}...", found)),
+    };
+    let transcript = message_transcript(
+        &format!("Messages cleared in channel {} by {}", ctx.channel_id().0, ctx.author().tag()),
+        &messages,
+    );
+    let mut log_message = match log_action(ctx, &action, &transcript).await {
+        Ok(log_message) => log_message,
+        Err(reason) => {
+            // deleting without the transcript would lose the messages for good
+            poise::say_reply(ctx, format!("{}\nNothing was deleted.", reason)).await?;
+            return Ok(());
+        }
+    };
+
+    // the progress message is sent after the command, so it's never one of the deleted messages
+    let reply = poise::say_reply(ctx, format!("🗑️ Deleting {} messages...", found)).await?;
+    let mut progress = reply.message().await?;
 
     // delete the acquired messages
-    if let Err(err) = ctx
-        .channel_id()
-        .delete_messages(
-            &ctx.discord().http,
-            messages.iter().map(|message| message.id),
-        )
-        .await
-    {
-        poise::say_reply(ctx, format!("There was an error: {}", err)).await?;
+    let deleted = delete_messages(ctx, &messages, &mut progress).await;
+
+    let mut text = if filters.is_empty() {
+        format!("Successfully deleted the last {} messages.", deleted)
     } else {
-        let reply = poise::say_reply(
-            ctx,
-            format!("Successfully deleted the last {} messages.", number),
+        format!(
+            "Successfully deleted {} messages {} (searched {} messages).",
+            deleted,
+            filters.describe().join(", "),
+            scanned
         )
-        .await?;
-
-        // Wait 3 seconds and delete the notification message
-        sleep(Duration::from_secs(3)).await;
-        reply.message().await?.delete(&ctx.discord().http).await?;
+    };
+    let mut count = format!("{} deleted", deleted);
+    if deleted < found {
+        text.push_str(&format!(" {} couldn't be deleted.", found - deleted));
+        count.push_str(&format!(", {} couldn't be deleted", found - deleted));
     }
+    if let Some(log_message) = &mut log_message {
+        action.fields = clear_fields(&filters, scanned, count);
+        update_action(ctx, log_message, &action).await;
+    }
+    progress.edit(&ctx.discord().http, |edit| edit.content(text)).await?;
+
+    // Wait 3 seconds and delete the notification message, someone might have deleted it already
+    sleep(Duration::from_secs(3)).await;
+    let _ = progress.delete(&ctx.discord().http).await;
 
     Ok(())
 }
+
+/// The details of a clear in the mod log
+fn clear_fields(filters: &Filters, scanned: u64, count: String) -> Vec<(String, String)> {
+    let described = if filters.is_empty() {
+        String::from("None")
+    } else {
+        filters.describe().join("\n")
+    };
+
+    vec![
+        (String::from("Messages"), count),
+        (String::from("Filters"), described),
+        (String::from("Searched"), format!("{} messages", scanned)),
+    ]
+}
+
+/// Deletes the messages, showing the progress in `progress`. Returns how many were deleted.
+///
+/// Messages newer than 14 days are bulk deleted 100 at a time. Older ones (and a lone message)
+/// can only be deleted one by one. serenity waits out the rate limits, and failed deletes are tried once more.
+/// If a bulk delete fails, the messages in it are deleted one by one instead.
+async fn delete_messages(
+    ctx: Context<'_>,
+    messages: &[serenity::Message],
+    progress: &mut serenity::Message,
+) -> usize {
+    let http = &ctx.discord().http;
+    let total = messages.len();
+    let now = chrono::Utc::now().timestamp_millis();
+    let (recent, old): (Vec<serenity::MessageId>, Vec<serenity::MessageId>) = messages
+        .iter()
+        .map(|message| message.id)
+        .partition(|id| now - created_at(id.0) < BULK_DELETE_MAX_AGE);
+
+    let mut deleted = 0;
+    for batch in recent.chunks(MESSAGES_PER_REQUEST as usize) {
+        // bulk delete needs at least 2 messages
+        let bulk_deleted = batch.len() > 1 && ctx.channel_id().delete_messages(http, batch.iter().copied()).await.is_ok();
+        if bulk_deleted {
+            deleted += batch.len();
+        } else {
+            // some of them might be gone already, or just became too old
+            for id in batch {
+                if delete_message(ctx, *id).await {
+                    deleted += 1;
+                }
+            }
+        }
+
+        if deleted < total {
+            update_progress(ctx, progress, deleted, total).await;
+        }
+    }
+
+    for (index, id) in old.iter().enumerate() {
+        if delete_message(ctx, *id).await {
+            deleted += 1;
+        }
+        if (index + 1) % PROGRESS_EVERY == 0 && index + 1 < old.len() {
+            update_progress(ctx, progress, deleted, total).await;
+        }
+    }
+
+    deleted
+}
+
+/// Deletes one message, trying again once if it fails. Returns whether it was deleted.
+async fn delete_message(ctx: Context<'_>, id: serenity::MessageId) -> bool {
+    let http = &ctx.discord().http;
+    if ctx.channel_id().delete_message(http, id).await.is_ok() {
+        return true;
+    }
+
+    sleep(RETRY_DELAY).await;
+    ctx.channel_id().delete_message(http, id).await.is_ok()
+}
+
+async fn update_progress(ctx: Context<'_>, progress: &mut serenity::Message, deleted: usize, total: usize) {
+    // the progress is a nice-to-have, don't stop deleting over it
+    let _ = progress
+        .edit(&ctx.discord().http, |edit| {
+            edit.content(format!("🗑️ Deleting messages... {}/{}", deleted, total))
+        })
+        .await;
+}
+
+/// When a message was sent, in ms since the unix epoch. Message ids start with it.
+fn created_at(id: u64) -> i64 {
+    (id >> 22) as i64 + DISCORD_EPOCH
+}
+
+/// The most messages one /clear deletes, from CLEAR_MAX_MESSAGES
+fn max_messages() -> u64 {
+    env::var("CLEAR_MAX_MESSAGES")
+        .ok()
+        .and_then(|max| max.parse::<u64>().ok())
+        .filter(|max| *max > 0)
+        .unwrap_or(DEFAULT_MAX_MESSAGES)
+}
+
+/// Finds up to `number` messages that match the filters, newest first.
+/// Looks through at most `MAX_SCANNED` messages. Returns the matches and how many messages were looked through.
+async fn find_messages(
+    ctx: Context<'_>,
+    filters: &Filters,
+    number: u64,
+) -> Result<(Vec<serenity::Message>, u64), Error> {
+    let mut matches = Vec::new();
+    let mut scanned = 0;
+    // start right before the command, or the `before` message
+    let mut cursor = filters.before.unwrap_or_else(|| ctx.id());
+    // without filters, the last `number` messages are all that's needed
+    let max_scanned = if filters.is_empty() { number } else { MAX_SCANNED };
+
+    while (matches.len() as u64) < number && scanned < max_scanned {
+        let limit = MESSAGES_PER_REQUEST.min(max_scanned - scanned);
+        let batch = ctx
+            .channel_id()
+            .messages(&ctx.discord().http, |messages| {
+                messages.before(cursor);
+                messages.limit(limit);
+
+                messages
+            })
+            .await?;
+        let last_batch = (batch.len() as u64) < limit;
+        scanned += batch.len() as u64;
+        if let Some(oldest) = batch.last() {
+            cursor = oldest.id.0;
+        }
+
+        for message in batch {
+            // everything from here on is older than the range
+            if filters.after.map_or(false, |after| message.id.0 <= after) {
+                return Ok((matches, scanned));
+            }
+            if filters.matches(&message) {
+                matches.push(message);
+                if matches.len() as u64 == number {
+                    break;
+                }
+            }
+        }
+
+        // the start of the channel
+        if last_batch {
+            break;
+        }
+    }
+
+    Ok((matches, scanned))
+}
@@ -0,0 +1,295 @@
+use poise::serenity_prelude as serenity;
+use regex::{Regex, RegexBuilder};
+
+const MAX_REGEX_SIZE: usize = 1 << 20; // so a pattern can't eat all the memory
+
+/// Which messages `/clear` deletes. A message has to match every filter that's set.
+#[derive(Debug)]
+pub struct Filters {
+    pub user: Option<serenity::UserId>,
+    pub bots: bool,
+    pub contains: Option<String>, // lowercase, matched case-insensitively
+    pub regex: Option<Regex>,
+    pub attachments: bool,
+    pub embeds: bool,
+    pub links: bool,
+    pub after: Option<u64>,  // only messages newer than this id
+    pub before: Option<u64>, // only messages older than this id
+}
+
+impl Filters {
+    pub fn matches(&self, message: &serenity::Message) -> bool {
+        if let Some(user) = self.user {
+            if message.author.id != user {
+                return false;
+            }
+        }
+        if self.bots && !message.author.bot {
+            return false;
+        }
+        if let Some(text) = &self.contains {
+            if !message.content.to_lowercase().contains(text) {
+                return false;
+            }
+        }
+        if let Some(regex) = &self.regex {
+            if !regex.is_match(&message.content) {
+                return false;
+            }
+        }
+        if self.attachments && message.attachments.is_empty() {
+            return false;
+        }
+        if self.embeds && message.embeds.is_empty() {
+            return false;
+        }
+        if self.links && !has_link(&message.content) {
+            return false;
+        }
+        if self.after.map_or(false, |after| message.id.0 <= after) {
+            return false;
+        }
+        if self.before.map_or(false, |before| message.id.0 >= before) {
+            return false;
+        }
+        true
+    }
+
+    /// Whether any filter is set, or every message is deleted
+    pub fn is_empty(&self) -> bool {
+        self.describe().is_empty()
+    }
+
+    /// Describes every filter that's set, e.g. ["from @someone", "with attachments"]
+    pub fn describe(&self) -> Vec<String> {
+        let mut filters = Vec::new();
+        if let Some(user) = self.user {
+            filters.push(format!("from <@{}>", user.0));
+        }
+        if self.bots {
+            filters.push(String::from("from bots"));
+        }
+        if let Some(text) = &self.contains {
+            filters.push(format!("containing \"{}\"", text));
+        }
+        if let Some(regex) = &self.regex {
+            filters.push(format!("matching `{}`", regex.as_str()));
+        }
+        if self.attachments {
+            filters.push(String::from("with attachments"));
+        }
+        if self.embeds {
+            filters.push(String::from("with embeds"));
+        }
+        if self.links {
+            filters.push(String::from("with links"));
+        }
+        if let Some(after) = self.after {
+            filters.push(format!("after message {}", after));
+        }
+        if let Some(before) = self.before {
+            filters.push(format!("before message {}", before));
+        }
+        filters
+    }
+}
+
+/// Compiles a regex given by a user. Returns the reason to show them if it's invalid or too big.
+pub fn parse_regex(pattern: &str) -> Result<Regex, String> {
+    RegexBuilder::new(pattern)
+        .size_limit(MAX_REGEX_SIZE)
+        .build()
+        .map_err(|why| format!("That regex doesn't work: {}", why))
+}
+
+/// Gets the id out of a message id or a message link (`https://discord.com/channels/<guild>/<channel>/<message>`)
+pub fn parse_message_id(input: &str) -> Option<u64> {
+    input.trim().trim_end_matches('/').rsplit('/').next()?.parse().ok()
+}
+
+fn has_link(content: &str) -> bool {
+    let content = content.to_lowercase();
+    content.contains("http://") || content.contains("https://") || content.contains("discord.gg/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const AUTHOR: u64 = 111;
+
+    fn no_filters() -> Filters {
+        Filters {
+            user: None,
+            bots: false,
+            contains: None,
+            regex: None,
+            attachments: false,
+            embeds: false,
+            links: false,
+            after: None,
+            before: None,
+        }
+    }
+
+    /// A message as discord sends it, with only what the filters look at filled in
+    fn message(id: u64, bot: bool, content: &str, attachments: usize, embeds: usize) -> serenity::Message {
+        let attachments = (0..attachments)
+            .map(|index| {
+                json!({
+                    "id": (1000 + index).to_string(),
+                    "filename": "cat.png",
+                    "size": 1234,
+                    "url": "https://cdn.discordapp.com/attachments/1/2/cat.png",
+                    "proxy_url": "https://media.discordapp.net/attachments/1/2/cat.png",
+                    "height": null,
+                    "width": null
+                })
+            })
+            .collect::<Vec<_>>();
+        let embeds = (0..embeds).map(|_| json!({ "type": "rich", "fields": [] })).collect::<Vec<_>>();
+
+        serde_json::from_value(json!({
+            "id": id.to_string(),
+            "channel_id": "222",
+            "author": {
+                "id": AUTHOR.to_string(),
+                "username": "someone",
+                "discriminator": "1234",
+                "avatar": null,
+                "bot": bot
+            },
+            "content": content,
+            "timestamp": "2021-10-18T12:00:00+00:00",
+            "edited_timestamp": null,
+            "tts": false,
+            "mention_everyone": false,
+            "mentions": [],
+            "mention_roles": [],
+            "attachments": attachments,
+            "embeds": embeds,
+            "pinned": false,
+            "type": 0
+        }))
+        .expect("a valid message")
+    }
+
+    #[test]
+    fn no_filters_match_everything() {
+        let filters = no_filters();
+        assert!(filters.is_empty());
+        assert!(filters.matches(&message(5, false, "hello", 0, 0)));
+        assert!(filters.matches(&message(5, true, "", 1, 1)));
+    }
+
+    #[test]
+    fn matches_the_author_and_bots() {
+        let filters = Filters {
+            user: Some(serenity::UserId(AUTHOR)),
+            ..no_filters()
+        };
+        assert!(filters.matches(&message(5, false, "hi", 0, 0)));
+        let filters = Filters {
+            user: Some(serenity::UserId(AUTHOR + 1)),
+            ..no_filters()
+        };
+        assert!(!filters.matches(&message(5, false, "hi", 0, 0)));
+
+        let filters = Filters { bots: true, ..no_filters() };
+        assert!(filters.matches(&message(5, true, "beep", 0, 0)));
+        assert!(!filters.matches(&message(5, false, "beep", 0, 0)));
+    }
+
+    #[test]
+    fn matches_text_case_insensitively() {
+        let filters = Filters {
+            contains: Some(String::from("free nitro")),
+            ..no_filters()
+        };
+        assert!(filters.matches(&message(5, false, "Get FREE Nitro here", 0, 0)));
+        assert!(!filters.matches(&message(5, false, "nitro isn't free", 0, 0)));
+    }
+
+    #[test]
+    fn matches_a_regex() {
+        let filters = Filters {
+            regex: Some(parse_regex(r"^\d+$").unwrap()),
+            ..no_filters()
+        };
+        assert!(filters.matches(&message(5, false, "12345", 0, 0)));
+        assert!(!filters.matches(&message(5, false, "123 go", 0, 0)));
+    }
+
+    #[test]
+    fn matches_attachments_embeds_and_links() {
+        let attachments = Filters { attachments: true, ..no_filters() };
+        assert!(attachments.matches(&message(5, false, "", 1, 0)));
+        assert!(!attachments.matches(&message(5, false, "", 0, 1)));
+
+        let embeds = Filters { embeds: true, ..no_filters() };
+        assert!(embeds.matches(&message(5, false, "", 0, 1)));
+        assert!(!embeds.matches(&message(5, false, "", 1, 0)));
+
+        let links = Filters { links: true, ..no_filters() };
+        assert!(links.matches(&message(5, false, "look https://example.com", 0, 0)));
+        assert!(!links.matches(&message(5, false, "no links here", 0, 0)));
+    }
+
+    #[test]
+    fn matches_only_inside_the_range() {
+        let filters = Filters {
+            after: Some(10),
+            before: Some(20),
+            ..no_filters()
+        };
+        assert!(filters.matches(&message(15, false, "", 0, 0)));
+        // both ends are left out
+        assert!(!filters.matches(&message(10, false, "", 0, 0)));
+        assert!(!filters.matches(&message(20, false, "", 0, 0)));
+        assert!(!filters.matches(&message(25, false, "", 0, 0)));
+    }
+
+    #[test]
+    fn every_filter_has_to_match() {
+        let filters = Filters {
+            bots: true,
+            links: true,
+            ..no_filters()
+        };
+        assert!(filters.matches(&message(5, true, "https://example.com", 0, 0)));
+        assert!(!filters.matches(&message(5, true, "no link", 0, 0)));
+        assert!(!filters.matches(&message(5, false, "https://example.com", 0, 0)));
+    }
+
+    #[test]
+    fn parses_message_ids_and_links() {
+        assert_eq!(parse_message_id("900000000000000000"), Some(900000000000000000));
+        assert_eq!(parse_message_id("  900000000000000000 "), Some(900000000000000000));
+        assert_eq!(
+            parse_message_id("https://discord.com/channels/1/2/900000000000000000"),
+            Some(900000000000000000)
+        );
+        assert_eq!(
+            parse_message_id("https://discord.com/channels/1/2/900000000000000000/"),
+            Some(900000000000000000)
+        );
+    }
+
+    #[test]
+    fn rejects_what_isnt_a_message_id() {
+        assert_eq!(parse_message_id(""), None);
+        assert_eq!(parse_message_id("hello"), None);
+        assert_eq!(parse_message_id("https://discord.com/channels/1/2/abc"), None);
+    }
+
+    #[test]
+    fn finds_links() {
+        assert!(has_link("see http://example.com"));
+        assert!(has_link("HTTPS://EXAMPLE.COM"));
+        assert!(has_link("join discord.gg/abc"));
+        assert!(!has_link("example.com"));
+        assert!(!has_link("https:/broken"));
+        assert!(!has_link(""));
+    }
+}
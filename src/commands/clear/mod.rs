@@ -1 +1,2 @@
-pub mod clear;
\ No newline at end of file
+pub mod clear;
+pub mod filters; // which messages /clear deletes
@@ -0,0 +1,184 @@
+use crate::Error;
+
+use async_trait::async_trait;
+use serde::Deserialize;
+
+use super::provider::{ImageInfo, SearchPage, SearchProvider, SearchResult, SearchType};
+use crate::helpers::Api;
+
+const CUSTOM_SEARCH_URL: &str = "https://customsearch.googleapis.com/customsearch/v1";
+const GOOGLE_LOGO: &str = "https://www.freepnglogos.com/uploads/google-logo-png/google-logo-icon-png-transparent-background-osteopathy-16.png";
+const RESULTS_PER_PAGE: u32 = 5;
+const IMAGES_PER_PAGE: u32 = 10; // the most the custom search api returns at once
+const MAX_RESULTS: u32 = 100; // the custom search api never returns results past the 100th
+
+/// Searches with the Google Custom Search API
+pub struct GoogleProvider {
+    api_key: String,
+    search_engine_id: String,
+}
+
+impl GoogleProvider {
+    /// Returns `None` if GOOGLE_SEARCH_KEY or SEARCH_ENGINE_ID isn't set
+    pub fn from_env() -> Option<Self> {
+        Some(GoogleProvider {
+            api_key: dotenv::var("GOOGLE_SEARCH_KEY").ok()?,
+            search_engine_id: dotenv::var("SEARCH_ENGINE_ID").ok()?,
+        })
+    }
+}
+
+#[async_trait]
+impl SearchProvider for GoogleProvider {
+    fn id(&self) -> &'static str {
+        "google"
+    }
+
+    fn name(&self) -> &'static str {
+        "Google"
+    }
+
+    fn logo(&self) -> Option<&'static str> {
+        Some(GOOGLE_LOGO)
+    }
+
+    fn quota(&self) -> Option<Api> {
+        Some(Api::GoogleSearch)
+    }
+
+    fn supports(&self, _search_type: SearchType) -> bool {
+        true
+    }
+
+    async fn search(
+        &self,
+        query: &str,
+        page: u32,
+        search_type: SearchType,
+        safe_search: bool,
+    ) -> Result<SearchPage, Error> {
+        let per_page = match search_type {
+            SearchType::Web => RESULTS_PER_PAGE,
+            SearchType::Image => IMAGES_PER_PAGE,
+        };
+        let start = page * per_page + 1; // the index of the first result to get, starting at 1
+
+        let num = per_page.to_string();
+        let start_param = start.to_string();
+
+        let mut request = reqwest::Client::new()
+            .get(CUSTOM_SEARCH_URL)
+            .query(&[
+                ("key", self.api_key.as_str()),
+                ("cx", self.search_engine_id.as_str()),
+                ("num", num.as_str()),
+                ("start", start_param.as_str()),
+                ("q", query), // the query to search for
+                ("safe", if safe_search { "active" } else { "off" }), // omit nsfw results unless allowed
+            ]);
+        if search_type == SearchType::Image {
+            request = request.query(&[("searchType", "image")]);
+        }
+
+        let response = request
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<Response>()
+            .await?;
+
+        Ok(SearchPage {
+            has_more: !response.queries.next_page.is_empty() && has_next_page(start, per_page),
+            total_results: Some(response.search_information.total_results),
+            items: response
+                .items
+                .into_iter()
+                .map(|item| SearchResult {
+                    title: item.title,
+                    link: item.link,
+                    snippet: item.snippet,
+                    display_link: item.display_link,
+                    image: item.image.map(|image| ImageInfo {
+                        context_link: image.context_link,
+                        width: Some(image.width),
+                        height: Some(image.height),
+                        byte_size: Some(image.byte_size).filter(|size| *size > 0),
+                    }),
+                })
+                .collect(),
+            ..Default::default()
+        })
+    }
+}
+
+/// Whether the page after the one starting at `start` can be requested.
+/// The api refuses requests where start + num is over 100, instead of returning what's left.
+fn has_next_page(start: u32, per_page: u32) -> bool {
+    let next_start = start + per_page;
+    next_start + per_page <= MAX_RESULTS
+}
+
+#[derive(Debug, Deserialize)]
+struct Response {
+    #[serde(default)]
+    items: Vec<Item>, // missing when there are no results
+    #[serde(default)]
+    queries: Queries,
+    #[serde(rename = "searchInformation", default)]
+    search_information: SearchInformation,
+}
+
+#[derive(Debug, Deserialize)]
+struct Item {
+    title: String,
+    link: String,
+    #[serde(default)]
+    snippet: String,
+    #[serde(rename = "displayLink", default)]
+    display_link: String,
+    image: Option<Image>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Image {
+    #[serde(rename = "contextLink")]
+    context_link: String,
+    width: u32,
+    height: u32,
+    #[serde(rename = "byteSize", default)]
+    byte_size: u64,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Queries {
+    #[serde(rename = "nextPage", default)]
+    next_page: Vec<serde::de::IgnoredAny>, // only there if there are more results
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct SearchInformation {
+    #[serde(rename = "totalResults", default)]
+    total_results: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stops_before_a_page_that_goes_past_the_last_result() {
+        // web results: 86 is followed by 91, and 91 + 5 is fine, 96 + 5 isn't
+        assert!(has_next_page(86, RESULTS_PER_PAGE));
+        assert!(!has_next_page(91, RESULTS_PER_PAGE));
+
+        // images: 81 + 10 is fine, 91 + 10 isn't
+        assert!(has_next_page(71, IMAGES_PER_PAGE));
+        assert!(!has_next_page(81, IMAGES_PER_PAGE));
+    }
+
+    #[test]
+    fn has_next_page_on_the_first_page() {
+        assert!(has_next_page(1, RESULTS_PER_PAGE));
+        assert!(has_next_page(1, IMAGES_PER_PAGE));
+    }
+}
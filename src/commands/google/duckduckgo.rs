@@ -0,0 +1,172 @@
+use crate::Error;
+
+use async_trait::async_trait;
+use serde::Deserialize;
+
+use super::provider::{SearchPage, SearchProvider, SearchResult, SearchType};
+
+const INSTANT_ANSWER_URL: &str = "https://api.duckduckgo.com/";
+const DUCKDUCKGO_LOGO: &str = "https://duckduckgo.com/assets/logo_homepage.normal.v108.png";
+const MAX_RESULTS: usize = 5;
+const MAX_SNIPPET_LENGTH: usize = 300; // abstracts can be longer than an embed field allows
+
+/// Searches with the DuckDuckGo Instant Answer API.
+///
+/// It doesn't need a key, but it only gives instant answers (abstracts, definitions and related topics),
+/// not full web results. There's one page of them and no images.
+pub struct DuckDuckGoProvider;
+
+impl DuckDuckGoProvider {
+    pub fn new() -> Self {
+        DuckDuckGoProvider
+    }
+}
+
+#[async_trait]
+impl SearchProvider for DuckDuckGoProvider {
+    fn id(&self) -> &'static str {
+        "duckduckgo"
+    }
+
+    fn name(&self) -> &'static str {
+        "DuckDuckGo"
+    }
+
+    fn logo(&self) -> Option<&'static str> {
+        Some(DUCKDUCKGO_LOGO)
+    }
+
+    fn supports(&self, search_type: SearchType) -> bool {
+        search_type == SearchType::Web
+    }
+
+    async fn search(
+        &self,
+        query: &str,
+        page: u32,
+        _search_type: SearchType,
+        safe_search: bool,
+    ) -> Result<SearchPage, Error> {
+        // instant answers only have one page
+        if page > 0 {
+            return Ok(SearchPage::default());
+        }
+
+        let response = reqwest::Client::new()
+            .get(INSTANT_ANSWER_URL)
+            .query(&[
+                ("q", query),
+                ("format", "json"),
+                ("no_html", "1"),
+                ("skip_disambig", "1"),
+                ("kp", if safe_search { "1" } else { "-2" }), // strict or off
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<Response>()
+            .await?;
+
+        let mut items = Vec::new();
+        if !response.abstract_url.is_empty() {
+            items.push(to_search_result(
+                &response.heading,
+                &response.abstract_url,
+                &response.abstract_text,
+            ));
+        }
+        if !response.answer.is_empty() {
+            items.push(SearchResult {
+                title: String::from("Answer"),
+                link: reqwest::Url::parse_with_params("https://duckduckgo.com/", &[("q", query)])?.to_string(),
+                snippet: response.answer,
+                display_link: String::from("duckduckgo.com"),
+                image: None,
+            });
+        }
+
+        // related topics can be grouped, flatten the groups
+        let topics = response
+            .related_topics
+            .into_iter()
+            .flat_map(|topic| match topic {
+                Topic::Group { topics } => topics,
+                Topic::Single(topic) => vec![topic],
+            });
+        for topic in response.results.into_iter().chain(topics) {
+            if topic.first_url.is_empty() {
+                continue;
+            }
+            // the text starts with the title, e.g. "Shiba Inu - A breed of hunting dog..."
+            let title = topic.text.split(" - ").next().unwrap_or(&topic.text);
+            items.push(to_search_result(title, &topic.first_url, &topic.text));
+        }
+        items.truncate(MAX_RESULTS);
+
+        Ok(SearchPage {
+            items,
+            ..Default::default()
+        })
+    }
+}
+
+fn to_search_result(title: &str, link: &str, snippet: &str) -> SearchResult {
+    let display_link = reqwest::Url::parse(link)
+        .ok()
+        .and_then(|url| url.host_str().map(|host| host.to_string()))
+        .unwrap_or_default();
+
+    SearchResult {
+        title: if title.is_empty() { display_link.clone() } else { title.to_string() },
+        link: link.to_string(),
+        snippet: if snippet.chars().count() > MAX_SNIPPET_LENGTH {
+            format!("{}...", snippet.chars().take(MAX_SNIPPET_LENGTH).collect::<String>())
+        } else {
+            snippet.to_string()
+        },
+        display_link,
+        image: None,
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Response {
+    #[serde(rename = "Heading", default)]
+    heading: String,
+    #[serde(rename = "AbstractText", default)]
+    abstract_text: String,
+    #[serde(rename = "AbstractURL", default)]
+    abstract_url: String,
+    #[serde(rename = "Answer", default, deserialize_with = "string_or_other")]
+    answer: String,
+    #[serde(rename = "Results", default)]
+    results: Vec<TopicItem>,
+    #[serde(rename = "RelatedTopics", default)]
+    related_topics: Vec<Topic>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Topic {
+    Group {
+        #[serde(rename = "Topics")]
+        topics: Vec<TopicItem>,
+    },
+    Single(TopicItem),
+}
+
+#[derive(Debug, Deserialize)]
+struct TopicItem {
+    #[serde(rename = "FirstURL", default)]
+    first_url: String,
+    #[serde(rename = "Text", default)]
+    text: String,
+}
+
+/// "Answer" is usually a string, but some instant answers put an object there. Those are ignored.
+fn string_or_other<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    match serde_json::Value::deserialize(deserializer)? {
+        serde_json::Value::String(answer) => Ok(answer),
+        _ => Ok(String::new()),
+    }
+}
@@ -1,25 +1,47 @@
 use crate::{Context, Error};
+use std::time::Duration;
 
-use serde::Deserialize;
 use poise::serenity_prelude as serenity;
 use rand::seq::SliceRandom;
 
-const CUSTOM_SEARCH_URL: &str = "https://customsearch.googleapis.com/customsearch/v1";
-const GOOGLE_LOGO: &str = "https://www.freepnglogos.com/uploads/google-logo-png/google-logo-icon-png-transparent-background-osteopathy-16.png";
-const GOOGLE_COLORS: [serenity::Colour; 4] = [
+use super::{
+    images::image_search,
+    policy::SearchPolicy,
+    preview::reply_preview,
+    provider::{search, SearchPage, SearchType},
+};
+use crate::helpers::reply_ephemeral;
+
+pub(super) const GOOGLE_COLORS: [serenity::Colour; 4] = [
     serenity::Colour::from_rgb(66, 133, 244), // google blue
     serenity::Colour::from_rgb(219, 68, 55), // google red
     serenity::Colour::from_rgb(244, 180, 0), // google yellow
     serenity::Colour::from_rgb(15, 157, 88), // google green
 ];
+pub(super) const PAGINATION_TIMEOUT: Duration = Duration::from_secs(120);
+const PREVIOUS_BUTTON_ID: &str = "google_previous";
+const NEXT_BUTTON_ID: &str = "google_next";
+const EXPAND_BUTTON_PREFIX: &str = "google_expand_"; // followed by the index of the result on the page
 
 /// Search Google from Discord and get the top results displayed in an embed
 ///
-/// **Usage:**
-/// `/google <query>`
+/// Use the buttons below the results to see more of them,
+/// or expand a result to see a preview of the page.
+/// If Google isn't available, the search falls back to SearXNG or DuckDuckGo.
+/// Admins can choose which one is tried first with `/searchsettings provider`.
+///
+/// **Subcommands**
+///
+/// **web** `/google web <query>`
+/// **images** `/google images <query>` shows one image at a time
+///
+/// **Prefix usage:**
+/// `&google <query>`
+/// `&google image <query>` for an image search
 ///
 /// **Example:**
-/// `/google shiba inu`
+/// `/google web shiba inu`
+/// `&google image shiba inu`
 #[poise::command(
 prefix_command,
 slash_command,
@@ -30,91 +52,217 @@ aliases("search")
 )]
 pub async fn google(
     ctx: Context<'_>,
+    #[description = "Search for images instead of web pages"]
+    #[flag] image: bool,
     #[description = "Your Google search query"]
     #[rest] query: String,
 ) -> Result<(), Error> {
-    let api_key = dotenv::var("GOOGLE_SEARCH_KEY")
-        .expect("Expected environment variable GOOGLE_SEARCH_KEY");
-    let search_engine_id = dotenv::var("SEARCH_ENGINE_ID")
-        .expect("Expected environment variable SEARCH_ENGINE_ID");
-
-    let search_url = format!(
-        "{}?key={}&cx={}&num={}&q={}&safe={}",
-        CUSTOM_SEARCH_URL,
-        api_key,
-        search_engine_id,
-        5, // the number of results to get
-        query, // the query to search for
-        "active" // set safe search to active (omit nsfw results)
-    );
-
-    let search_result = reqwest::get(&search_url)
-        .await?
-        .json::<Response>()
-        .await?;
+    if image {
+        image_search(ctx, &query).await
+    } else {
+        web_search(ctx, &query).await
+    }
+}
 
-    poise::send_reply(ctx, |message| {
-        message.embed(|embed| {
-            embed.title("Google Search Results");
-
-            // randomly choose one of the 4 Google colors and assign that to the embed
-            let mut rng = rand::thread_rng();
-            let embed_color = *GOOGLE_COLORS.choose(&mut rng).unwrap();
-            embed.colour(embed_color);
-
-            embed.thumbnail(GOOGLE_LOGO);
-            embed.author(|author| {
-                if let Some(icon_url) = ctx.author().avatar_url() {
-                    author.icon_url(icon_url);
-                } else {
-                    author.icon_url(ctx.author().default_avatar_url());
-                }
-                author.name(&ctx.author().name);
-                author
-            });
-
-            for search_result in &search_result.items {
-                embed.field(
-                    &search_result.title,
-                    format!("**[Link]({})**\n{}", &search_result.link, &search_result.snippet),
-                    false,
-                );
-            }
+/// Search the web with Google
+#[poise::command(prefix_command, slash_command, broadcast_typing, defer_response, track_edits)]
+pub async fn web(
+    ctx: Context<'_>,
+    #[description = "Your Google search query"]
+    #[rest] query: String,
+) -> Result<(), Error> {
+    web_search(ctx, &query).await
+}
 
-            embed.footer(|footer| {
-                if let Some(icon_url) = &ctx.discord().cache.current_user().avatar_url() {
-                    footer.icon_url(icon_url);
-                } else {
-                    footer
-                        .icon_url(ctx.discord().cache.current_user().default_avatar_url());
-                }
-                footer.text(format!(
-                    "{} | Google",
-                    ctx.discord().cache.current_user().name
-                ));
-                footer
-            });
-
-            embed.timestamp(chrono::Utc::now());
-
-            embed
-        });
+/// Shows pages of web results for `query`, with buttons to go through them
+async fn web_search(ctx: Context<'_>, query: &str) -> Result<(), Error> {
+    let policy = SearchPolicy::for_context(ctx).await?;
+    let mut page = 0;
+    let mut search_page = match search(ctx, query, page, SearchType::Web, &policy).await {
+        Ok(search_page) => search_page,
+        Err(why) => {
+            poise::say_reply(ctx, why.to_string()).await?;
+            return Ok(());
+        }
+    };
+
+    // randomly choose one of the 4 Google colors and use that for every page
+    let embed_color = *GOOGLE_COLORS.choose(&mut rand::thread_rng()).unwrap();
 
+    let reply = poise::send_reply(ctx, |message| {
         message
+            .embed(|embed| results_embed(ctx, embed, &search_page, page, embed_color))
+            .components(|components| page_buttons(components, &search_page, page, false))
     })
         .await?;
+    let mut message = reply.message().await?;
+
+    while let Some(interaction) = serenity::CollectComponentInteraction::new(ctx.discord())
+        .message_id(message.id)
+        .timeout(PAGINATION_TIMEOUT)
+        .await
+    {
+        // anyone can expand a result, the preview is only shown to them
+        if let Some(index) = interaction.data.custom_id.strip_prefix(EXPAND_BUTTON_PREFIX) {
+            let result = index.parse::<usize>().ok().and_then(|index| search_page.items.get(index));
+            if let Some(result) = result {
+                reply_preview(ctx, &interaction, &result.link).await?;
+            }
+            continue;
+        }
+
+        // only the user who searched gets to page through the results
+        if interaction.user.id != ctx.author().id {
+            reply_ephemeral(ctx, &interaction, "Only the person who searched can change the page.").await?;
+            continue;
+        }
+
+        let new_page = match interaction.data.custom_id.as_str() {
+            PREVIOUS_BUTTON_ID if page > 0 => page - 1,
+            NEXT_BUTTON_ID if search_page.has_more => page + 1,
+            _ => continue,
+        };
+        search_page = match search(ctx, query, new_page, SearchType::Web, &policy).await {
+            Ok(search_page) => search_page,
+            Err(why) => {
+                reply_ephemeral(ctx, &interaction, why).await?;
+                continue;
+            }
+        };
+        page = new_page;
+
+        interaction
+            .create_interaction_response(ctx.discord(), |response| {
+                response
+                    .kind(serenity::InteractionResponseType::UpdateMessage)
+                    .interaction_response_data(|data| {
+                        data.create_embed(|embed| results_embed(ctx, embed, &search_page, page, embed_color))
+                            .components(|components| page_buttons(components, &search_page, page, false))
+                    })
+            })
+            .await?;
+    }
+
+    // the buttons stop working after the timeout, so disable them
+    message
+        .edit(ctx.discord(), |edit| {
+            edit.components(|components| page_buttons(components, &search_page, page, true))
+        })
+        .await?;
 
     Ok(())
 }
 
-#[derive(Debug, Deserialize)]
-struct Response {
-    items: Vec<SearchResult>,
+/// Fills an embed with one page of search results
+fn results_embed<'a>(
+    ctx: Context<'_>,
+    embed: &'a mut serenity::CreateEmbed,
+    search_page: &SearchPage,
+    page: u32,
+    embed_color: serenity::Colour,
+) -> &'a mut serenity::CreateEmbed {
+    embed.title(format!("{} Search Results", search_page.provider));
+    embed.colour(embed_color);
+
+    if let Some(logo) = search_page.logo {
+        embed.thumbnail(logo);
+    }
+    embed.author(|author| {
+        if let Some(icon_url) = ctx.author().avatar_url() {
+            author.icon_url(icon_url);
+        } else {
+            author.icon_url(ctx.author().default_avatar_url());
+        }
+        author.name(&ctx.author().name);
+        author
+    });
+
+    if search_page.items.is_empty() {
+        embed.description("No results found.");
+    }
+
+    // numbered so they match the expand buttons
+    for (index, search_result) in search_page.items.iter().enumerate() {
+        embed.field(
+            format!("{}. {}", index + 1, &search_result.title),
+            format!("**[Link]({})**\n{}", &search_result.link, &search_result.snippet),
+            false,
+        );
+    }
+
+    // e.g. "Page 2 (about 1230 results, 1 hidden)"
+    let mut details = Vec::new();
+    if let Some(total_results) = &search_page.total_results {
+        details.push(format!("about {} results", total_results));
+    }
+    if search_page.hidden > 0 {
+        details.push(format!("{} hidden", search_page.hidden));
+    }
+    let mut position = format!("Page {}", page + 1);
+    if !details.is_empty() {
+        position.push_str(&format!(" ({})", details.join(", ")));
+    }
+
+    embed.footer(|footer| {
+        if let Some(icon_url) = &ctx.discord().cache.current_user().avatar_url() {
+            footer.icon_url(icon_url);
+        } else {
+            footer
+                .icon_url(ctx.discord().cache.current_user().default_avatar_url());
+        }
+        footer.text(format!(
+            "{} | {} | {}",
+            ctx.discord().cache.current_user().name,
+            search_page.provider,
+            position
+        ));
+        footer
+    });
+
+    embed.timestamp(chrono::Utc::now());
+
+    embed
 }
 
-#[derive(Debug, Deserialize)]
-struct SearchResult {
-    title: String,
-    link: String,
-    snippet: String,
+/// Adds the previous and next page buttons, and a button to expand each result.
+/// The page buttons are disabled when there's no page to go to, and every button is disabled when `disabled` is true.
+fn page_buttons<'a>(
+    components: &'a mut serenity::CreateComponents,
+    search_page: &SearchPage,
+    page: u32,
+    disabled: bool,
+) -> &'a mut serenity::CreateComponents {
+    components.create_action_row(|action_row| {
+        action_row.create_button(|button| {
+            button
+                .style(serenity::ButtonStyle::Secondary)
+                .label("◀ Previous")
+                .custom_id(PREVIOUS_BUTTON_ID)
+                .disabled(disabled || page == 0)
+        });
+        action_row.create_button(|button| {
+            button
+                .style(serenity::ButtonStyle::Secondary)
+                .label("Next ▶")
+                .custom_id(NEXT_BUTTON_ID)
+                .disabled(disabled || !search_page.has_more)
+        })
+    });
+
+    if !search_page.items.is_empty() {
+        components.create_action_row(|action_row| {
+            for index in 0..search_page.items.len() {
+                action_row.create_button(|button| {
+                    button
+                        .style(serenity::ButtonStyle::Secondary)
+                        .label(format!("🔍 {}", index + 1))
+                        .custom_id(format!("{}{}", EXPAND_BUTTON_PREFIX, index))
+                        .disabled(disabled)
+                });
+            }
+            action_row
+        });
+    }
+
+    components
 }
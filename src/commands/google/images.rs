@@ -0,0 +1,236 @@
+use crate::{Context, Error};
+
+use poise::serenity_prelude as serenity;
+use rand::seq::SliceRandom;
+
+use super::google::{GOOGLE_COLORS, PAGINATION_TIMEOUT};
+use super::policy::SearchPolicy;
+use super::provider::{search, SearchPage, SearchResult, SearchType};
+use crate::helpers::reply_ephemeral;
+
+const PREVIOUS_BUTTON_ID: &str = "google_image_previous";
+const NEXT_BUTTON_ID: &str = "google_image_next";
+
+/// Search Google Images and flip through the results one at a time
+///
+/// **Usage:**
+/// `/google images <query>`
+///
+/// **Example:**
+/// `/google images shiba inu`
+#[poise::command(prefix_command, slash_command, broadcast_typing, defer_response, track_edits)]
+pub async fn images(
+    ctx: Context<'_>,
+    #[description = "Your Google image search query"]
+    #[rest] query: String,
+) -> Result<(), Error> {
+    image_search(ctx, &query).await
+}
+
+/// Shows the image results for `query` one at a time, with buttons to go through them.
+/// Fetches the next page of images whenever the user reaches the end of the ones already fetched.
+pub(super) async fn image_search(ctx: Context<'_>, query: &str) -> Result<(), Error> {
+    let policy = SearchPolicy::for_context(ctx).await?;
+    let mut page = 0;
+    let first_page = match search(ctx, query, page, SearchType::Image, &policy).await {
+        Ok(search_page) => search_page,
+        Err(why) => {
+            poise::say_reply(ctx, why.to_string()).await?;
+            return Ok(());
+        }
+    };
+    let mut has_more = first_page.has_more;
+    let mut results = first_page.items;
+    let source = SearchPage {
+        items: Vec::new(),
+        ..first_page
+    }; // the provider and total of the results, shown in the embed
+
+    if results.is_empty() {
+        poise::say_reply(ctx, format!("No images found for \"{}\".", query)).await?;
+        return Ok(());
+    }
+
+    let mut index = 0;
+
+    // randomly choose one of the 4 Google colors and use that for every image
+    let embed_color = *GOOGLE_COLORS.choose(&mut rand::thread_rng()).unwrap();
+
+    let reply = poise::send_reply(ctx, |message| {
+        message
+            .embed(|embed| image_embed(ctx, embed, &results[index], index, &source, embed_color))
+            .components(|components| {
+                carousel_buttons(components, &results[index], index, index + 1 < results.len() || has_more, false)
+            })
+    })
+    .await?;
+    let mut message = reply.message().await?;
+
+    while let Some(interaction) = serenity::CollectComponentInteraction::new(ctx.discord())
+        .message_id(message.id)
+        .timeout(PAGINATION_TIMEOUT)
+        .await
+    {
+        // only the user who searched gets to flip through the images
+        if interaction.user.id != ctx.author().id {
+            reply_ephemeral(ctx, &interaction, "Only the person who searched can change the image.").await?;
+            continue;
+        }
+
+        match interaction.data.custom_id.as_str() {
+            PREVIOUS_BUTTON_ID if index > 0 => index -= 1,
+            NEXT_BUTTON_ID if index + 1 < results.len() => index += 1,
+            NEXT_BUTTON_ID if has_more => {
+                // we've shown every image we have, so get the next page
+                let response = match search(ctx, query, page + 1, SearchType::Image, &policy).await {
+                    Ok(search_page) => search_page,
+                    Err(why) => {
+                        reply_ephemeral(ctx, &interaction, why).await?;
+                        continue;
+                    }
+                };
+
+                page += 1;
+                has_more = response.has_more;
+                if response.items.is_empty() {
+                    has_more = false;
+                } else {
+                    results.extend(response.items);
+                    index += 1;
+                }
+            }
+            _ => continue,
+        }
+
+        let has_next = index + 1 < results.len() || has_more;
+        interaction
+            .create_interaction_response(ctx.discord(), |response| {
+                response
+                    .kind(serenity::InteractionResponseType::UpdateMessage)
+                    .interaction_response_data(|data| {
+                        data.create_embed(|embed| {
+                            image_embed(ctx, embed, &results[index], index, &source, embed_color)
+                        })
+                        .components(|components| {
+                            carousel_buttons(components, &results[index], index, has_next, false)
+                        })
+                    })
+            })
+            .await?;
+    }
+
+    // the buttons stop working after the timeout, so disable them
+    message
+        .edit(ctx.discord(), |edit| {
+            edit.components(|components| carousel_buttons(components, &results[index], index, false, true))
+        })
+        .await?;
+
+    Ok(())
+}
+
+/// Fills an embed with a single image result
+fn image_embed<'a>(
+    ctx: Context<'_>,
+    embed: &'a mut serenity::CreateEmbed,
+    result: &SearchResult,
+    index: usize,
+    source: &SearchPage,
+    embed_color: serenity::Colour,
+) -> &'a mut serenity::CreateEmbed {
+    embed.title(&result.title);
+    embed.image(&result.link);
+    embed.colour(embed_color);
+    if let Some(logo) = source.logo {
+        embed.thumbnail(logo);
+    }
+    embed.author(|author| {
+        if let Some(icon_url) = ctx.author().avatar_url() {
+            author.icon_url(icon_url);
+        } else {
+            author.icon_url(ctx.author().default_avatar_url());
+        }
+        author.name(&ctx.author().name);
+        author
+    });
+
+    if let Some(image) = &result.image {
+        embed.url(&image.context_link);
+        embed.description(format!(
+            "**[Source page]({})** ({})",
+            &image.context_link, &result.display_link
+        ));
+        if let (Some(width), Some(height)) = (image.width, image.height) {
+            embed.field("Dimensions", format!("{} × {}", width, height), true);
+        }
+        if let Some(byte_size) = image.byte_size {
+            embed.field("Size", format!("{:.1} KB", byte_size as f64 / 1024.0), true);
+        }
+    }
+
+    embed.footer(|footer| {
+        if let Some(icon_url) = &ctx.discord().cache.current_user().avatar_url() {
+            footer.icon_url(icon_url);
+        } else {
+            footer.icon_url(ctx.discord().cache.current_user().default_avatar_url());
+        }
+        let mut position = format!("Image {}", index + 1);
+        if let Some(total_results) = &source.total_results {
+            position.push_str(&format!(" of about {}", total_results));
+        }
+        footer.text(format!(
+            "{} | {} Images | {}",
+            ctx.discord().cache.current_user().name,
+            source.provider,
+            position
+        ));
+        footer
+    });
+
+    embed.timestamp(chrono::Utc::now());
+
+    embed
+}
+
+/// Adds the previous/next buttons and the links to the image and its source page.
+/// The previous/next buttons are disabled when there's no image to go to, or when `disabled` is true.
+fn carousel_buttons<'a>(
+    components: &'a mut serenity::CreateComponents,
+    result: &SearchResult,
+    index: usize,
+    has_next: bool,
+    disabled: bool,
+) -> &'a mut serenity::CreateComponents {
+    components.create_action_row(|action_row| {
+        action_row.create_button(|button| {
+            button
+                .style(serenity::ButtonStyle::Secondary)
+                .label("◀ Previous")
+                .custom_id(PREVIOUS_BUTTON_ID)
+                .disabled(disabled || index == 0)
+        });
+        action_row.create_button(|button| {
+            button
+                .style(serenity::ButtonStyle::Secondary)
+                .label("Next ▶")
+                .custom_id(NEXT_BUTTON_ID)
+                .disabled(disabled || !has_next)
+        });
+        action_row.create_button(|button| {
+            button
+                .style(serenity::ButtonStyle::Link)
+                .label("Open image")
+                .url(&result.link)
+        });
+        if let Some(image) = &result.image {
+            action_row.create_button(|button| {
+                button
+                    .style(serenity::ButtonStyle::Link)
+                    .label("Source page")
+                    .url(&image.context_link)
+            });
+        }
+
+        action_row
+    })
+}
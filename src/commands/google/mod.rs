@@ -1 +1,9 @@
-pub mod google;
\ No newline at end of file
+pub mod google;
+pub mod images;
+pub mod policy;
+pub mod preview;
+pub mod provider;
+pub mod settings;
+mod custom_search;
+mod duckduckgo;
+mod searxng;
@@ -0,0 +1,105 @@
+use crate::{Context, Error};
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+/// The search settings of every server, saved in search_settings.json
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SearchSettings {
+    pub guilds: HashMap<u64, GuildSearchSettings>, // keyed by guild id
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct GuildSearchSettings {
+    /// Age-restricted channels where admins turned SafeSearch off
+    #[serde(default)]
+    pub safe_search_off: HashSet<u64>,
+    /// Results from these domains (and their subdomains) are never shown
+    #[serde(default)]
+    pub blocked_domains: Vec<String>,
+    /// The id of the search provider to try first, `None` to use the configured order
+    #[serde(default)]
+    pub provider: Option<String>,
+}
+
+/// What a single search is allowed to return, worked out from where it was made.
+///
+/// SafeSearch is always on, except in age-restricted channels where an admin turned it off.
+pub struct SearchPolicy {
+    pub safe_search: bool,
+    pub blocked_domains: Vec<String>,
+    pub provider: Option<String>, // the search provider chosen for the server
+}
+
+impl SearchPolicy {
+    /// Works out the policy for a search made in the channel of `ctx`
+    pub async fn for_context(ctx: Context<'_>) -> Result<SearchPolicy, Error> {
+        let guild_id = match ctx.guild_id() {
+            Some(guild_id) => guild_id,
+            // no admins to turn anything off in DMs
+            None => {
+                return Ok(SearchPolicy {
+                    safe_search: true,
+                    blocked_domains: Vec::new(),
+                    provider: None,
+                })
+            }
+        };
+
+        let settings = ctx
+            .data()
+            .search_settings
+            .read()
+            .await
+            .guilds
+            .get(&guild_id.0)
+            .cloned()
+            .unwrap_or_default();
+
+        // check the channel every time, it might not be age-restricted anymore
+        let mut safe_search = true;
+        if settings.safe_search_off.contains(&ctx.channel_id().0) {
+            safe_search = !ctx.channel_id().to_channel(ctx.discord()).await?.is_nsfw();
+        }
+
+        Ok(SearchPolicy {
+            safe_search,
+            blocked_domains: settings.blocked_domains,
+            provider: settings.provider,
+        })
+    }
+
+    /// Returns true if the url is on a blocked domain or one of its subdomains
+    pub fn is_blocked(&self, url: &str) -> bool {
+        let host = match reqwest::Url::parse(url) {
+            Ok(url) => match url.host_str() {
+                Some(host) => host.to_lowercase(),
+                None => return false,
+            },
+            // don't show links we can't even parse
+            Err(_) => return true,
+        };
+
+        self.blocked_domains
+            .iter()
+            .any(|domain| host == *domain || host.ends_with(&format!(".{}", domain)))
+    }
+}
+
+/// Turns user input like "https://www.Example.com/page" into a bare domain ("www.example.com")
+pub fn normalize_domain(domain: &str) -> Option<String> {
+    let domain = domain.trim().to_lowercase();
+    let domain = domain
+        .trim_start_matches("https://")
+        .trim_start_matches("http://")
+        .split('/')
+        .next()
+        .unwrap_or("")
+        .trim_end_matches('.');
+
+    if domain.is_empty() || !domain.contains('.') || domain.contains(char::is_whitespace) {
+        None
+    } else {
+        Some(domain.to_string())
+    }
+}
@@ -0,0 +1,369 @@
+use crate::{Context, Error};
+use std::{net::SocketAddr, time::Duration};
+
+use poise::serenity_prelude as serenity;
+use reqwest::{header, redirect, Url};
+
+use crate::helpers::{is_public, truncate};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5); // for the whole request, including the body
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+const MAX_BODY_SIZE: usize = 512 * 1024; // the meta tags are in the head, no need to read any further
+const MAX_REDIRECTS: usize = 3;
+const MAX_DESCRIPTION_LENGTH: usize = 500;
+const MAX_TITLE_LENGTH: usize = 256; // discord rejects embeds with longer titles and author names
+const USER_AGENT: &str = "Mozilla/5.0 (compatible; OxidizeBot/0.1; link preview)";
+
+/// What a page says about itself in its OpenGraph/Twitter card meta tags
+#[derive(Debug, Default)]
+pub struct LinkPreview {
+    pub url: String, // the page the preview is of, after redirects
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub image: Option<String>,
+    pub site_name: Option<String>,
+}
+
+/// Replies to a button click with a preview of `url` that only the user who clicked can see.
+///
+/// Fetching the page can take longer than the 3 seconds Discord waits for a response,
+/// so the response is deferred first and filled in once the page is fetched.
+pub(super) async fn reply_preview(
+    ctx: Context<'_>,
+    interaction: &serenity::MessageComponentInteraction,
+    url: &str,
+) -> Result<(), Error> {
+    interaction
+        .create_interaction_response(ctx.discord(), |response| {
+            response
+                .kind(serenity::InteractionResponseType::DeferredChannelMessageWithSource)
+                .interaction_response_data(|data| {
+                    data.flags(serenity::InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
+                })
+        })
+        .await?;
+
+    match fetch_preview(url).await {
+        Ok(preview) => {
+            interaction
+                .edit_original_interaction_response(ctx.discord(), |response| {
+                    response.create_embed(|embed| preview_embed(ctx, embed, &preview))
+                })
+                .await?;
+        }
+        Err(why) => {
+            interaction
+                .edit_original_interaction_response(ctx.discord(), |response| {
+                    response.content(format!("Couldn't get a preview of <{}>: {}", url, why))
+                })
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Fills an embed with the preview of a page
+fn preview_embed<'a>(
+    ctx: Context<'_>,
+    embed: &'a mut serenity::CreateEmbed,
+    preview: &LinkPreview,
+) -> &'a mut serenity::CreateEmbed {
+    let host = Url::parse(&preview.url)
+        .ok()
+        .and_then(|url| url.host_str().map(|host| host.to_string()))
+        .unwrap_or_default();
+
+    embed.title(truncate(preview.title.as_deref().unwrap_or(&host), MAX_TITLE_LENGTH));
+    embed.url(&preview.url);
+    embed.colour(serenity::Colour::from_rgb(88, 101, 242));
+    embed.author(|author| author.name(truncate(preview.site_name.as_deref().unwrap_or(&host), MAX_TITLE_LENGTH)));
+    embed.description(
+        preview
+            .description
+            .as_deref()
+            .unwrap_or("This page doesn't have a description."),
+    );
+    if let Some(image) = &preview.image {
+        embed.image(image);
+    }
+
+    embed.footer(|footer| {
+        if let Some(icon_url) = &ctx.discord().cache.current_user().avatar_url() {
+            footer.icon_url(icon_url);
+        } else {
+            footer.icon_url(ctx.discord().cache.current_user().default_avatar_url());
+        }
+        footer.text(format!("{} | Link preview", ctx.discord().cache.current_user().name));
+        footer
+    });
+
+    embed.timestamp(chrono::Utc::now());
+
+    embed
+}
+
+/// Fetches a page and reads its OpenGraph/Twitter card meta tags.
+///
+/// Only public http(s) addresses are fetched, redirects included, so a search result
+/// can't make the bot request something on its own network.
+pub async fn fetch_preview(url: &str) -> Result<LinkPreview, Error> {
+    let mut url = Url::parse(url)?;
+    let mut redirects = 0;
+    // redirects are followed by hand so every one of them goes through the guard
+    let mut response = loop {
+        let address = check_public(&url).await?;
+
+        let client = pinned_client(&url, address)?;
+        let response = client.get(url.clone()).header(header::ACCEPT, "text/html").send().await?;
+        if !response.status().is_redirection() {
+            break response.error_for_status()?;
+        }
+
+        redirects += 1;
+        if redirects > MAX_REDIRECTS {
+            return Err("the page redirects too many times".into());
+        }
+        let location = response
+            .headers()
+            .get(header::LOCATION)
+            .and_then(|location| location.to_str().ok())
+            .ok_or("the page redirects nowhere")?;
+        url = url.join(location)?;
+    };
+
+    let is_html = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .map_or(false, |content_type| content_type.contains("html"));
+    if !is_html {
+        return Err("it isn't a web page".into());
+    }
+
+    // read until the end of the head, or until the size cap
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        body.extend_from_slice(&chunk);
+        if body.len() >= MAX_BODY_SIZE || contains(&body, b"</head>") {
+            break;
+        }
+    }
+    body.truncate(MAX_BODY_SIZE);
+
+    Ok(parse_preview(&String::from_utf8_lossy(&body), &url))
+}
+
+/// A client that connects to `address` for the url's host, instead of looking the host up again.
+/// Otherwise the host could resolve to a public address for the check and a private one for the request.
+fn pinned_client(url: &Url, address: SocketAddr) -> Result<reqwest::Client, Error> {
+    let mut builder = reqwest::Client::builder()
+        .redirect(redirect::Policy::none())
+        .no_proxy() // a proxy would do its own lookup
+        .timeout(REQUEST_TIMEOUT)
+        .connect_timeout(CONNECT_TIMEOUT)
+        .user_agent(USER_AGENT);
+    // ip addresses aren't looked up at all
+    if let Some(domain) = url.domain() {
+        builder = builder.resolve(domain, address);
+    }
+
+    Ok(builder.build()?)
+}
+
+/// Returns an error if the url isn't http(s) or its host resolves to a private address.
+/// Otherwise returns the address to connect to.
+async fn check_public(url: &Url) -> Result<SocketAddr, Error> {
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return Err("only http and https links can be previewed".into());
+    }
+
+    let host = url.host_str().ok_or("the link has no host")?;
+    let port = url.port_or_known_default().unwrap_or(80);
+
+    // every address the host resolves to has to be public, otherwise the request could go to any of them
+    let lookup = tokio::net::lookup_host((host.trim_start_matches('[').trim_end_matches(']'), port));
+    let addresses = tokio::time::timeout(CONNECT_TIMEOUT, lookup)
+        .await
+        .map_err(|_| "looking up the link's host took too long")??
+        .collect::<Vec<_>>();
+    if addresses.is_empty() {
+        return Err("the link's host doesn't resolve".into());
+    }
+    if addresses.iter().any(|address| !is_public(address.ip())) {
+        return Err("the link points to a private address".into());
+    }
+
+    Ok(addresses[0])
+}
+
+/// Reads the preview out of the meta tags of a page, falling back to the `<title>` for the title
+fn parse_preview(html: &str, url: &Url) -> LinkPreview {
+    let mut preview = LinkPreview {
+        url: url.to_string(),
+        ..Default::default()
+    };
+    let mut title_tag = None;
+
+    let lowercase = html.to_ascii_lowercase(); // same byte offsets as the html
+    let mut position = 0;
+    while let Some(start) = lowercase[position..].find('<').map(|start| start + position) {
+        let end = match lowercase[start..].find('>') {
+            Some(end) => start + end,
+            None => break,
+        };
+        let tag = &html[start + 1..end];
+        position = end + 1;
+
+        if lowercase[start..].starts_with("<title") && title_tag.is_none() {
+            if let Some(close) = lowercase[position..].find("</title>") {
+                title_tag = Some(decode_entities(html[position..position + close].trim()));
+            }
+            continue;
+        }
+        if !lowercase[start..].starts_with("<meta") {
+            continue;
+        }
+
+        let attributes = parse_attributes(tag);
+        let key = attributes
+            .iter()
+            .find(|(name, _)| name == "property" || name == "name")
+            .map(|(_, value)| value.to_lowercase());
+        let content = attributes
+            .iter()
+            .find(|(name, _)| name == "content")
+            .map(|(_, value)| decode_entities(value.trim()))
+            .filter(|content| !content.is_empty());
+        let (key, content) = match (key, content) {
+            (Some(key), Some(content)) => (key, content),
+            _ => continue,
+        };
+
+        // og tags win over twitter tags, whichever comes first in the page
+        match key.as_str() {
+            "og:title" => preview.title = Some(content),
+            "twitter:title" => preview.title = preview.title.or(Some(content)),
+            "og:description" => preview.description = Some(content),
+            "twitter:description" | "description" => preview.description = preview.description.or(Some(content)),
+            "og:image" | "og:image:url" | "og:image:secure_url" => preview.image = Some(content),
+            "twitter:image" | "twitter:image:src" => preview.image = preview.image.or(Some(content)),
+            "og:site_name" => preview.site_name = Some(content),
+            "og:url" => {
+                // embed urls have to be http(s)
+                if let Ok(og_url) = url.join(&content) {
+                    if og_url.scheme() == "http" || og_url.scheme() == "https" {
+                        preview.url = og_url.to_string();
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    preview.title = preview.title.or(title_tag).filter(|title| !title.is_empty());
+    // images can be relative to the page, and discord only shows http(s) images
+    preview.image = preview
+        .image
+        .and_then(|image| url.join(&image).ok())
+        .filter(|image| image.scheme() == "http" || image.scheme() == "https")
+        .map(|image| image.to_string());
+    preview.description = preview.description.map(|description| {
+        if description.chars().count() > MAX_DESCRIPTION_LENGTH {
+            format!("{}...", description.chars().take(MAX_DESCRIPTION_LENGTH).collect::<String>())
+        } else {
+            description
+        }
+    });
+
+    preview
+}
+
+/// Splits the inside of a tag (`meta property="og:title" content="..."`) into lowercase names and values
+fn parse_attributes(tag: &str) -> Vec<(String, String)> {
+    let mut attributes = Vec::new();
+    let mut rest = tag.trim_end_matches('/');
+    // skip the tag name
+    rest = rest.trim_start().splitn(2, char::is_whitespace).nth(1).unwrap_or("");
+
+    loop {
+        rest = rest.trim_start();
+        let name_end = match rest.find(|c: char| c == '=' || c.is_whitespace()) {
+            Some(name_end) => name_end,
+            None => break, // the last attribute has no value
+        };
+        let name = rest[..name_end].to_lowercase();
+        rest = rest[name_end..].trim_start();
+        if !rest.starts_with('=') {
+            continue; // an attribute without a value
+        }
+        rest = rest[1..].trim_start();
+
+        let value;
+        if let Some(quote) = rest.chars().next().filter(|c| *c == '"' || *c == '\'') {
+            let close = rest[1..].find(quote).map_or(rest.len(), |close| close + 1);
+            value = rest[1..close].to_string();
+            rest = rest.get(close + 1..).unwrap_or("");
+        } else {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            value = rest[..end].to_string();
+            rest = &rest[end..];
+        }
+
+        attributes.push((name, value));
+    }
+
+    attributes
+}
+
+/// Decodes the html entities that show up in titles and descriptions
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let end = match rest.find(';').filter(|end| *end <= 10) {
+            Some(end) => end,
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+                continue;
+            }
+        };
+        let entity = &rest[1..end];
+        let character = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some(' '),
+            _ if entity.starts_with("#x") || entity.starts_with("#X") => {
+                u32::from_str_radix(&entity[2..], 16).ok().and_then(char::from_u32)
+            }
+            _ if entity.starts_with('#') => entity[1..].parse::<u32>().ok().and_then(char::from_u32),
+            _ => None,
+        };
+
+        match character {
+            Some(character) => {
+                decoded.push(character);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+
+    decoded
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|window| window.eq_ignore_ascii_case(needle))
+}
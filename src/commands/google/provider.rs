@@ -0,0 +1,186 @@
+use crate::helpers::{quota_exhausted_message, Api};
+use crate::{Context, Error};
+use std::env;
+
+use async_trait::async_trait;
+use log::warn;
+
+use super::{
+    custom_search::GoogleProvider, duckduckgo::DuckDuckGoProvider, policy::SearchPolicy,
+    searxng::SearxngProvider,
+};
+
+/// A search backend (Google, SearXNG, etc).
+///
+/// Every provider turns its own API response into a `SearchPage`,
+/// so the search commands don't need to know where the results came from.
+#[async_trait]
+pub trait SearchProvider: Send + Sync {
+    /// The id of the provider, used in the config and the server settings ("google", etc)
+    fn id(&self) -> &'static str;
+
+    /// The name of the provider, shown on the results
+    fn name(&self) -> &'static str;
+
+    /// The logo of the provider, shown as the thumbnail of the results
+    fn logo(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// The API whose quota every search with this provider uses, if any
+    fn quota(&self) -> Option<Api> {
+        None
+    }
+
+    fn supports(&self, search_type: SearchType) -> bool;
+
+    /// Gets one page of results. `page` starts at 0.
+    async fn search(
+        &self,
+        query: &str,
+        page: u32,
+        search_type: SearchType,
+        safe_search: bool,
+    ) -> Result<SearchPage, Error>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SearchType {
+    Web,
+    Image,
+}
+
+/// One page of search results
+#[derive(Debug, Default)]
+pub struct SearchPage {
+    pub items: Vec<SearchResult>,
+    pub has_more: bool,
+    pub total_results: Option<String>,
+    pub hidden: u32, // the number of results removed because of the domain blocklist
+    pub provider: &'static str, // the name of the provider the results came from
+    pub logo: Option<&'static str>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchResult {
+    pub title: String,
+    pub link: String, // the url of the image itself for image searches
+    pub snippet: String,
+    pub display_link: String, // the domain of the result, e.g. "en.wikipedia.org"
+    pub image: Option<ImageInfo>, // only there for image searches
+}
+
+#[derive(Debug, Clone)]
+pub struct ImageInfo {
+    pub context_link: String, // the page the image is on
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub byte_size: Option<u64>,
+}
+
+/// Every search provider that is configured, in the order they're tried in
+pub struct SearchProviders {
+    providers: Vec<Box<dyn SearchProvider>>,
+}
+
+impl SearchProviders {
+    /// Creates every provider that has the environment variables it needs.
+    ///
+    /// SEARCH_PROVIDERS sets the fallback order (by default "google,searxng,duckduckgo").
+    /// Google needs GOOGLE_SEARCH_KEY and SEARCH_ENGINE_ID, SearXNG needs SEARXNG_URL.
+    /// DuckDuckGo doesn't need anything.
+    pub fn from_env() -> Self {
+        let order = env::var("SEARCH_PROVIDERS").unwrap_or_else(|_| String::from("google,searxng,duckduckgo"));
+
+        let mut providers: Vec<Box<dyn SearchProvider>> = Vec::new();
+        for id in order.split(',').map(|id| id.trim().to_lowercase()) {
+            match id.as_str() {
+                "google" => match GoogleProvider::from_env() {
+                    Some(provider) => providers.push(Box::new(provider)),
+                    None => warn!("GOOGLE_SEARCH_KEY or SEARCH_ENGINE_ID isn't set. Google search is disabled."),
+                },
+                "searxng" => match SearxngProvider::from_env() {
+                    Some(provider) => providers.push(Box::new(provider)),
+                    None => warn!("SEARXNG_URL isn't set. SearXNG search is disabled."),
+                },
+                "duckduckgo" => providers.push(Box::new(DuckDuckGoProvider::new())),
+                other => warn!("Unknown search provider \"{}\" in SEARCH_PROVIDERS.", other),
+            }
+        }
+
+        SearchProviders { providers }
+    }
+
+    /// The ids of every configured provider
+    pub fn ids(&self) -> Vec<&'static str> {
+        self.providers.iter().map(|provider| provider.id()).collect()
+    }
+}
+
+/// Searches with the provider chosen for the server (see `SearchPolicy`),
+/// falling back to the other providers in order when one fails or its quota is used up.
+///
+/// SafeSearch is set, and results from blocked domains are removed, according to `policy`.
+/// The error explains to the user why no provider could search.
+pub async fn search(
+    ctx: Context<'_>,
+    query: &str,
+    page: u32,
+    search_type: SearchType,
+    policy: &SearchPolicy,
+) -> Result<SearchPage, Error> {
+    let providers = &ctx.data().search_providers.providers;
+    let guild_id = ctx.guild_id().map(|guild_id| guild_id.0);
+
+    // the server's provider first, then the rest in the configured order
+    let mut ordered = providers
+        .iter()
+        .filter(|provider| provider.supports(search_type))
+        .collect::<Vec<&Box<dyn SearchProvider>>>();
+    if let Some(preferred) = &policy.provider {
+        ordered.sort_by_key(|provider| provider.id() != preferred.as_str());
+    }
+
+    if ordered.is_empty() {
+        return Err("There's no search provider for this kind of search.".into());
+    }
+
+    let mut failures = Vec::new();
+    for provider in ordered {
+        if let Some(api) = provider.quota() {
+            if !ctx.data().usage.try_use(api, guild_id, 1).await? {
+                failures.push(quota_exhausted_message(api));
+                continue;
+            }
+        }
+
+        match provider.search(query, page, search_type, policy.safe_search).await {
+            Ok(mut search_page) => {
+                // remove results from blocked domains, for images that includes the page the image is on
+                let count = search_page.items.len();
+                search_page.items.retain(|item| {
+                    !policy.is_blocked(&item.link)
+                        && !item
+                            .image
+                            .as_ref()
+                            .map_or(false, |image| policy.is_blocked(&image.context_link))
+                });
+                search_page.hidden = (count - search_page.items.len()) as u32;
+                search_page.provider = provider.name();
+                search_page.logo = provider.logo();
+
+                return Ok(search_page);
+            }
+            Err(why) => {
+                warn!("Search with {} failed, trying the next provider: {}", provider.name(), why);
+                failures.push(format!("{} isn't working right now.", provider.name()));
+            }
+        }
+    }
+
+    if failures.len() == 1 {
+        Err(failures.remove(0).into())
+    } else {
+        Err(format!("Every search provider failed:\n{}", failures.join("\n")).into())
+    }
+}
@@ -0,0 +1,195 @@
+use crate::Error;
+
+use async_trait::async_trait;
+use serde::Deserialize;
+
+use super::provider::{ImageInfo, SearchPage, SearchProvider, SearchResult, SearchType};
+
+const RESULTS_PER_PAGE: usize = 5;
+const IMAGES_PER_PAGE: usize = 10;
+
+/// Searches with a SearXNG instance through its JSON API.
+///
+/// The instance needs the json format enabled in its settings.yml (`search.formats`).
+pub struct SearxngProvider {
+    url: String, // the base url of the instance, e.g. "https://searx.example.com"
+}
+
+impl SearxngProvider {
+    /// Returns `None` if SEARXNG_URL isn't set
+    pub fn from_env() -> Option<Self> {
+        let url = dotenv::var("SEARXNG_URL").ok()?;
+
+        Some(SearxngProvider {
+            url: url.trim_end_matches('/').to_string(),
+        })
+    }
+}
+
+#[async_trait]
+impl SearchProvider for SearxngProvider {
+    fn id(&self) -> &'static str {
+        "searxng"
+    }
+
+    fn name(&self) -> &'static str {
+        "SearXNG"
+    }
+
+    fn supports(&self, _search_type: SearchType) -> bool {
+        true
+    }
+
+    async fn search(
+        &self,
+        query: &str,
+        page: u32,
+        search_type: SearchType,
+        safe_search: bool,
+    ) -> Result<SearchPage, Error> {
+        let per_page = match search_type {
+            SearchType::Web => RESULTS_PER_PAGE,
+            SearchType::Image => IMAGES_PER_PAGE,
+        };
+
+        // searxng pages are as big as its engines make them, so the first one says how big they are,
+        // and the results of this page start `page * per_page` results in
+        let first = self.fetch_page(query, 1, search_type, safe_search).await?;
+        let page_size = first.items.len();
+        if page_size == 0 {
+            return Ok(SearchPage::default());
+        }
+        let offset = page as usize * per_page;
+        let page_number = offset / page_size + 1; // searxng pages start at 1
+        let mut current = if page_number == 1 {
+            first
+        } else {
+            self.fetch_page(query, page_number, search_type, safe_search).await?
+        };
+
+        let mut items = current.items.split_off((offset % page_size).min(current.items.len()));
+        // the rest of this page is on the next searxng page
+        if items.len() < per_page && !current.items.is_empty() {
+            let next = self
+                .fetch_page(query, page_number + 1, search_type, safe_search)
+                .await?;
+            items.extend(next.items);
+            current.total = current.total.or(next.total);
+        }
+        let has_more = items.len() > per_page;
+        items.truncate(per_page);
+
+        Ok(SearchPage {
+            items,
+            has_more,
+            total_results: current.total.map(|total| total.to_string()),
+            ..Default::default()
+        })
+    }
+}
+
+/// One page of searxng results
+struct Page {
+    items: Vec<SearchResult>,
+    total: Option<u64>,
+}
+
+impl SearxngProvider {
+    async fn fetch_page(
+        &self,
+        query: &str,
+        page_number: usize,
+        search_type: SearchType,
+        safe_search: bool,
+    ) -> Result<Page, Error> {
+        let category = match search_type {
+            SearchType::Web => "general",
+            SearchType::Image => "images",
+        };
+        let page_number = page_number.to_string();
+
+        let response = reqwest::Client::new()
+            .get(format!("{}/search", self.url))
+            .query(&[
+                ("q", query),
+                ("format", "json"),
+                ("pageno", page_number.as_str()),
+                ("categories", category),
+                ("safesearch", if safe_search { "2" } else { "0" }), // 2 is strict
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<Response>()
+            .await?;
+
+        Ok(Page {
+            // searxng says 0 when none of its engines gave a count
+            total: Some(response.number_of_results as u64).filter(|total| *total > 0),
+            items: response
+                .results
+                .into_iter()
+                .filter_map(|result| to_search_result(result, search_type))
+                .collect(),
+        })
+    }
+}
+
+fn to_search_result(result: Item, search_type: SearchType) -> Option<SearchResult> {
+    let display_link = reqwest::Url::parse(&result.url)
+        .ok()
+        .and_then(|url| url.host_str().map(|host| host.to_string()))
+        .unwrap_or_default();
+    let title = if result.title.is_empty() { display_link.clone() } else { result.title };
+
+    match search_type {
+        SearchType::Web => Some(SearchResult {
+            title,
+            link: result.url,
+            snippet: result.content,
+            display_link,
+            image: None,
+        }),
+        SearchType::Image => {
+            // e.g. "1920 x 1080"
+            let mut resolution = result
+                .resolution
+                .as_deref()
+                .unwrap_or("")
+                .split(|c| c == 'x' || c == '×')
+                .map(|number| number.trim().parse::<u32>().ok());
+
+            Some(SearchResult {
+                title,
+                link: result.img_src?,
+                snippet: result.content,
+                display_link,
+                image: Some(ImageInfo {
+                    context_link: result.url,
+                    width: resolution.next().flatten(),
+                    height: resolution.next().flatten(),
+                    byte_size: None,
+                }),
+            })
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Response {
+    #[serde(default)]
+    results: Vec<Item>,
+    #[serde(default)]
+    number_of_results: f64, // a float for some reason
+}
+
+#[derive(Debug, Deserialize)]
+struct Item {
+    url: String,
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    content: String,
+    img_src: Option<String>, // only there for image results
+    resolution: Option<String>,
+}
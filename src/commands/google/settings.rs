@@ -0,0 +1,248 @@
+use crate::{Context, Error};
+
+use super::policy::normalize_domain;
+
+/// Change how the search commands behave in this server (admins only)
+///
+/// **Subcommands**
+///
+/// **safesearch** `/searchsettings safesearch <on|off>`
+/// Turns SafeSearch on or off in the current channel. It can only be turned off in age-restricted channels.
+///
+/// **block** `/searchsettings block <domain>`
+/// **unblock** `/searchsettings unblock <domain>`
+/// **blocklist** `/searchsettings blocklist`
+/// Results from blocked domains (and their subdomains) are never shown.
+///
+/// **provider** `/searchsettings provider <name|default>`
+/// Chooses the search provider that is tried first. The others are still used if it fails.
+///
+/// *examples*
+/// `/searchsettings safesearch off`
+/// `/searchsettings block example.com`
+/// `/searchsettings provider duckduckgo`
+#[poise::command(prefix_command, slash_command, required_permissions = "MANAGE_GUILD")]
+pub async fn searchsettings(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+#[derive(Debug, poise::SlashChoiceParameter)]
+pub enum SafeSearchMode {
+    #[name = "on"]
+    On,
+    #[name = "off"]
+    Off,
+}
+
+/// Turn SafeSearch on or off in this channel
+#[poise::command(prefix_command, slash_command, required_permissions = "MANAGE_GUILD")]
+pub async fn safesearch(
+    ctx: Context<'_>,
+    #[description = "Whether SafeSearch filters results in this channel"] mode: SafeSearchMode,
+) -> Result<(), Error> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id.0,
+        None => {
+            poise::say_reply(ctx, "Search settings only work in a server.").await?;
+            return Ok(());
+        }
+    };
+    let channel_id = ctx.channel_id().0;
+
+    match mode {
+        SafeSearchMode::Off => {
+            if !ctx.channel_id().to_channel(ctx.discord()).await?.is_nsfw() {
+                poise::say_reply(ctx, "SafeSearch can only be turned off in age-restricted channels.").await?;
+                return Ok(());
+            }
+
+            ctx.data()
+                .search_settings
+                .update(|settings| {
+                    settings
+                        .guilds
+                        .entry(guild_id)
+                        .or_default()
+                        .safe_search_off
+                        .insert(channel_id)
+                })
+                .await?;
+            poise::say_reply(ctx, "SafeSearch is now **off** in this channel.").await?;
+        }
+        SafeSearchMode::On => {
+            ctx.data()
+                .search_settings
+                .update(|settings| {
+                    settings
+                        .guilds
+                        .entry(guild_id)
+                        .or_default()
+                        .safe_search_off
+                        .remove(&channel_id)
+                })
+                .await?;
+            poise::say_reply(ctx, "SafeSearch is now **on** in this channel.").await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Never show search results from a domain
+#[poise::command(prefix_command, slash_command, required_permissions = "MANAGE_GUILD")]
+pub async fn block(
+    ctx: Context<'_>,
+    #[description = "The domain to block, e.g. example.com"] domain: String,
+) -> Result<(), Error> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id.0,
+        None => {
+            poise::say_reply(ctx, "Search settings only work in a server.").await?;
+            return Ok(());
+        }
+    };
+
+    let domain = match normalize_domain(&domain) {
+        Some(domain) => domain,
+        None => {
+            poise::say_reply(ctx, format!("\"{}\" isn't a valid domain.", domain)).await?;
+            return Ok(());
+        }
+    };
+
+    let added = ctx
+        .data()
+        .search_settings
+        .update(|settings| {
+            let blocked_domains = &mut settings.guilds.entry(guild_id).or_default().blocked_domains;
+            if blocked_domains.contains(&domain) {
+                false
+            } else {
+                blocked_domains.push(domain.clone());
+                true
+            }
+        })
+        .await?;
+
+    if added {
+        poise::say_reply(ctx, format!("Results from `{}` won't be shown anymore.", domain)).await?;
+    } else {
+        poise::say_reply(ctx, format!("`{}` is already blocked.", domain)).await?;
+    }
+
+    Ok(())
+}
+
+/// Allow search results from a blocked domain again
+#[poise::command(prefix_command, slash_command, required_permissions = "MANAGE_GUILD")]
+pub async fn unblock(
+    ctx: Context<'_>,
+    #[description = "The domain to unblock, e.g. example.com"] domain: String,
+) -> Result<(), Error> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id.0,
+        None => {
+            poise::say_reply(ctx, "Search settings only work in a server.").await?;
+            return Ok(());
+        }
+    };
+
+    let domain = normalize_domain(&domain).unwrap_or(domain);
+    let removed = ctx
+        .data()
+        .search_settings
+        .update(|settings| {
+            let blocked_domains = &mut settings.guilds.entry(guild_id).or_default().blocked_domains;
+            let count = blocked_domains.len();
+            blocked_domains.retain(|blocked| *blocked != domain);
+            blocked_domains.len() < count
+        })
+        .await?;
+
+    if removed {
+        poise::say_reply(ctx, format!("Results from `{}` will be shown again.", domain)).await?;
+    } else {
+        poise::say_reply(ctx, format!("`{}` isn't blocked.", domain)).await?;
+    }
+
+    Ok(())
+}
+
+/// Show the domains that are blocked from search results
+#[poise::command(prefix_command, slash_command, required_permissions = "MANAGE_GUILD")]
+pub async fn blocklist(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id.0,
+        None => {
+            poise::say_reply(ctx, "Search settings only work in a server.").await?;
+            return Ok(());
+        }
+    };
+
+    let blocked_domains = ctx
+        .data()
+        .search_settings
+        .read()
+        .await
+        .guilds
+        .get(&guild_id)
+        .map(|settings| settings.blocked_domains.clone())
+        .unwrap_or_default();
+
+    if blocked_domains.is_empty() {
+        poise::say_reply(ctx, "No domains are blocked in this server.").await?;
+    } else {
+        let list = blocked_domains
+            .iter()
+            .map(|domain| format!("`{}`", domain))
+            .collect::<Vec<String>>()
+            .join(", ");
+        poise::say_reply(ctx, format!("Blocked domains: {}", list)).await?;
+    }
+
+    Ok(())
+}
+
+/// Choose which search provider is tried first in this server
+#[poise::command(prefix_command, slash_command, required_permissions = "MANAGE_GUILD")]
+pub async fn provider(
+    ctx: Context<'_>,
+    #[description = "The provider to try first (google, searxng, duckduckgo), or \"default\""] name: String,
+) -> Result<(), Error> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id.0,
+        None => {
+            poise::say_reply(ctx, "Search settings only work in a server.").await?;
+            return Ok(());
+        }
+    };
+
+    let ids = ctx.data().search_providers.ids();
+    let name = name.trim().to_lowercase();
+    let provider = if name == "default" {
+        None
+    } else if ids.contains(&name.as_str()) {
+        Some(name)
+    } else {
+        poise::say_reply(
+            ctx,
+            format!("\"{}\" isn't an available search provider. Choose one of: {}", name, ids.join(", ")),
+        )
+        .await?;
+        return Ok(());
+    };
+
+    ctx.data()
+        .search_settings
+        .update(|settings| settings.guilds.entry(guild_id).or_default().provider = provider.clone())
+        .await?;
+
+    match provider {
+        Some(provider) => {
+            poise::say_reply(ctx, format!("Searches in this server will try `{}` first.", provider)).await?
+        }
+        None => poise::say_reply(ctx, format!("Searches in this server will try: {}", ids.join(", "))).await?,
+    };
+
+    Ok(())
+}
@@ -4,12 +4,31 @@ mod movie;
 mod ping;
 mod steam;
 mod clear;
+mod modlog;
 mod google;
+mod watchlist;
+mod movienight;
+mod usage;
+mod server;
 
 // re-export the main command functions
 pub use code::code::code;
 pub use movie::movie::movie;
+pub use movie::provider::{provider_from_env, MovieProvider};
 pub use ping::ping::ping;
-pub use steam::{steam::steam, user::user}; // steam main command and the user subcommand
+pub use steam::{steam::steam, user::user, game::game, games::games, together::together, apps::AppList};
+pub use steam::links::{link, unlink, linked, SteamLinks};
+pub use steam::{id::id, achievements::achievements, workshop::workshop}; // steam main command and its subcommands
+pub use steam::watch::{watch, watches, unwatch, alerts, start_price_checks, SteamWatches};
+pub use steam::news::{follow, unfollow, following, start_news_checks, SteamNews};
+pub use steam::presence::{presence, sessions, start_presence_checks, SteamPresence};
 pub use clear::clear::clear;
-pub use google::google::google;
+pub use modlog::modlog::{modlog, ModLogs};
+pub use google::{google::{google, web}, images::images}; // google main command and the web and images subcommands
+pub use google::{policy::SearchSettings, settings::{searchsettings, safesearch, block, unblock, blocklist, provider}};
+pub use google::provider::SearchProviders;
+pub use movienight::movienight::movienight;
+pub use watchlist::data::Watchlists;
+pub use watchlist::watchlist::{watchlist, add, remove, list, random, watched}; // watchlist main command and its subcommands
+pub use usage::usage::usage;
+pub use server::server::{server, servers, save, forget, saved, status, start_status_updates, ServerSettings}; // server command, and the servers command and its subcommands
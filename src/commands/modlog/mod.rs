@@ -0,0 +1,2 @@
+pub mod modlog; // export the modlog command, and posting moderation actions
+pub mod transcript; // transcripts of messages for the mod log
@@ -0,0 +1,184 @@
+use crate::{Context, Error};
+use std::collections::HashMap;
+
+use log::warn;
+use poise::serenity_prelude as serenity;
+use serde::{Deserialize, Serialize};
+
+use crate::helpers::truncate;
+
+const MAX_FIELD_LENGTH: usize = 1024; // discord rejects embeds with longer field values
+
+/// The mod log channel of every server, saved in mod_logs.json
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ModLogs {
+    pub channels: HashMap<u64, u64>, // mod log channel ids, keyed by guild id
+}
+
+/// A moderation action, as it's shown in the mod log
+pub struct ModAction {
+    pub name: String, // e.g. "Clear"
+    pub channel_id: serenity::ChannelId,
+    pub fields: Vec<(String, String)>, // the details of the action, e.g. ("Deleted", "30 messages")
+}
+
+/// A file to upload with a moderation action
+pub struct Transcript {
+    pub file_name: String,
+    pub contents: Vec<u8>,
+}
+
+/// Choose where moderation actions are logged (admins only)
+///
+/// Every moderation action, like `/clear`, posts who did what and where in the mod log.
+/// Cleared messages are uploaded there as a transcript before they're deleted. If that fails, nothing is deleted.
+///
+/// **Usage:**
+/// `/modlog [channel]`
+/// Leave the channel empty to stop logging.
+#[poise::command(prefix_command, slash_command, required_permissions = "MANAGE_GUILD")]
+pub async fn modlog(
+    ctx: Context<'_>,
+    #[description = "The channel to log moderation actions in. Leave empty to stop logging"] channel: Option<
+        serenity::GuildChannel,
+    >,
+) -> Result<(), Error> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id.0,
+        None => {
+            poise::say_reply(ctx, "This only works in a server.").await?;
+            return Ok(());
+        }
+    };
+
+    match channel {
+        Some(channel) => {
+            if channel.guild_id.0 != guild_id {
+                poise::say_reply(ctx, "That channel isn't in this server.").await?;
+                return Ok(());
+            }
+            ctx.data()
+                .mod_logs
+                .update(|mod_logs| mod_logs.channels.insert(guild_id, channel.id.0))
+                .await?;
+            poise::say_reply(ctx, format!("Moderation actions will be logged in <#{}>.", channel.id.0)).await?;
+        }
+        None => {
+            ctx.data()
+                .mod_logs
+                .update(|mod_logs| mod_logs.channels.remove(&guild_id))
+                .await?;
+            poise::say_reply(ctx, "Stopped logging moderation actions.").await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Posts a moderation action in the server's mod log, with the transcript if there is one.
+/// The first part of the transcript is uploaded with the action, any other parts right after it.
+///
+/// Returns the posted message so it can be updated once the action is done,
+/// or `None` if the server has no mod log.
+/// Returns the reason to show the moderator if the action or its transcript couldn't be posted.
+pub async fn log_action(
+    ctx: Context<'_>,
+    action: &ModAction,
+    transcript: &[Transcript],
+) -> Result<Option<serenity::Message>, String> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id,
+        None => return Ok(None),
+    };
+    let channel_id = match ctx.data().mod_logs.read().await.channels.get(&guild_id.0) {
+        Some(channel_id) => *channel_id,
+        None => return Ok(None),
+    };
+
+    let posted = serenity::ChannelId(channel_id)
+        .send_message(ctx.discord(), |message| {
+            message.embed(|embed| action_embed(ctx, embed, action));
+            if let Some(transcript) = transcript.first() {
+                message.add_file((transcript.contents.as_slice(), transcript.file_name.as_str()));
+            }
+            message
+        })
+        .await;
+
+    let posted = match posted {
+        Ok(message) => message,
+        Err(why) => {
+            warn!("Couldn't post in the mod log of {}: {}", guild_id.0, why);
+            return Err(format!("Couldn't post in the mod log (<#{}>): {}", channel_id, why));
+        }
+    };
+
+    for part in transcript.iter().skip(1) {
+        let uploaded = serenity::ChannelId(channel_id)
+            .send_message(ctx.discord(), |message| {
+                message.reference_message(&posted);
+                message.add_file((part.contents.as_slice(), part.file_name.as_str()))
+            })
+            .await;
+        if let Err(why) = uploaded {
+            warn!("Couldn't upload {} to the mod log of {}: {}", part.file_name, guild_id.0, why);
+            return Err(format!("Couldn't upload {} to the mod log (<#{}>): {}", part.file_name, channel_id, why));
+        }
+    }
+
+    Ok(Some(posted))
+}
+
+/// Replaces the details of an action that was already posted in the mod log
+pub async fn update_action(ctx: Context<'_>, message: &mut serenity::Message, action: &ModAction) {
+    let edited = message
+        .edit(ctx.discord(), |edit| edit.embed(|embed| action_embed(ctx, embed, action)))
+        .await;
+
+    if let Err(why) = edited {
+        warn!("Couldn't update the mod log message {}: {}", message.id.0, why);
+    }
+}
+
+fn action_embed<'a>(
+    ctx: Context<'_>,
+    embed: &'a mut serenity::CreateEmbed,
+    action: &ModAction,
+) -> &'a mut serenity::CreateEmbed {
+    embed.title(format!("🛡️ {}", action.name));
+    embed.colour(serenity::Colour::from_rgb(250, 166, 26));
+    embed.author(|author| {
+        if let Some(icon_url) = ctx.author().avatar_url() {
+            author.icon_url(icon_url);
+        } else {
+            author.icon_url(ctx.author().default_avatar_url());
+        }
+        author.name(ctx.author().tag());
+        author
+    });
+
+    embed.field("Moderator", format!("<@{}>", ctx.author().id.0), true);
+    embed.field("Channel", format!("<#{}>", action.channel_id.0), true);
+    // the values can come from users, e.g. the text /clear looked for
+    for (name, value) in &action.fields {
+        embed.field(name, truncate(value, MAX_FIELD_LENGTH), false);
+    }
+
+    embed.footer(|footer| {
+        if let Some(icon_url) = &ctx.discord().cache.current_user().avatar_url() {
+            footer.icon_url(icon_url);
+        } else {
+            footer.icon_url(ctx.discord().cache.current_user().default_avatar_url());
+        }
+        footer.text(format!(
+            "{} | Mod log | Moderator id {}",
+            ctx.discord().cache.current_user().name,
+            ctx.author().id.0
+        ));
+        footer
+    });
+
+    embed.timestamp(chrono::Utc::now());
+
+    embed
+}
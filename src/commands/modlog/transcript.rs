@@ -0,0 +1,55 @@
+use poise::serenity_prelude as serenity;
+
+use super::modlog::Transcript;
+
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S UTC";
+const MAX_PART_SIZE: usize = 7 * 1024 * 1024; // discord rejects uploads over 8 MB, the rest of the message included
+
+/// Writes a plain text transcript of messages, oldest first, for the mod log.
+///
+/// Every message gets its time, author, content and attachment urls, e.g.
+/// `[2021-10-18 12:00:00 UTC] someone#1234 (123456789): hello`
+/// A transcript too big to upload in one file is split into parts, each small enough to upload on its own.
+pub fn message_transcript(title: &str, messages: &[serenity::Message]) -> Vec<Transcript> {
+    let mut messages = messages.iter().collect::<Vec<&serenity::Message>>();
+    messages.sort_by_key(|message| message.id.0);
+
+    let mut parts = vec![format!("{}\n{} messages\n\n", title, messages.len())];
+    for message in messages {
+        let mut entry = format!(
+            "[{}] {} ({}): {}\n",
+            message.timestamp.format(TIME_FORMAT),
+            message.author.tag(),
+            message.author.id.0,
+            message.content
+        );
+        for attachment in &message.attachments {
+            entry.push_str(&format!("    attachment: {}\n", attachment.url));
+        }
+        if !message.embeds.is_empty() {
+            entry.push_str(&format!("    embeds: {}\n", message.embeds.len()));
+        }
+
+        // a message is never split between parts
+        let part = parts.last_mut().expect("there's always a first part");
+        if part.len() + entry.len() > MAX_PART_SIZE {
+            parts.push(format!("{} (part {})\n\n{}", title, parts.len() + 1, entry));
+        } else {
+            part.push_str(&entry);
+        }
+    }
+
+    let count = parts.len();
+    parts
+        .into_iter()
+        .enumerate()
+        .map(|(index, text)| Transcript {
+            file_name: if count == 1 {
+                String::from("transcript.txt")
+            } else {
+                format!("transcript-{}-of-{}.txt", index + 1, count)
+            },
+            contents: text.into_bytes(),
+        })
+        .collect()
+}
@@ -1 +1,4 @@
-pub mod movie;
\ No newline at end of file
+pub mod movie;
+pub mod provider;
+mod omdb;
+mod tmdb;
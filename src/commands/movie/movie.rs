@@ -2,8 +2,12 @@ use crate::{Context, Error};
 
 use log::error;
 use poise::serenity_prelude as serenity;
-use serde::Deserialize;
-use std::env;
+
+use super::provider::{CastMember, MovieDetails};
+use crate::helpers::{check_quota, truncate};
+use crate::commands::watchlist::watchlist::{handle_add_button, ADD_BUTTON_ID};
+
+const MAX_FIELD_LENGTH: usize = 1024;
 
 /// Lookup movie details (Plot, IMDb Rating, etc)
 ///
@@ -17,127 +21,263 @@ pub async fn movie(
     #[rest] // To collect all of the following arguments in prefix command into movie
     movie: String,
 ) -> Result<(), Error> {
-    // Get the API key for the OMDb API from the environment vars
-    let api_key = env::var("OMDB_API_KEY").expect("Expected environment variable OMDB_API_KEY");
-    let url = format!(
-        "http://www.omdbapi.com/?apikey={}&t={}&type=movie",
-        api_key, movie
-    );
-
-    // Fetch the data for the requested movie from the API
-    let api_response = reqwest::get(url)
-        .await?
-        .json::<Movie>()
-        .await
-        .unwrap_or(Movie {
-            response: "False".to_string(),
-            ..Default::default()
-        });
+    let provider = &ctx.data().movie_provider;
 
-    if api_response.response == "False" {
-        poise::say_reply(ctx, format!("No movie with title: \"{}\" found.", movie)).await?;
+    let (api, calls) = provider.quota();
+    if !check_quota(ctx, api, calls).await? {
         return Ok(());
     }
 
-    if let Err(why) = poise::send_reply(ctx, |message| {
-        message
-            .embed(|embed| {
-                // Construct an embed
-                embed.title(format!("{} ({})", &api_response.title, &api_response.year));
-                embed.description(&api_response.plot);
-                if &api_response.poster.to_lowercase() != "n/a" {
-                    embed.thumbnail(&api_response.poster);
-                    embed.image(&api_response.poster);
-                }
-                embed.author(|author| {
-                    if let Some(icon_url) = ctx.author().avatar_url() {
-                        author.icon_url(icon_url);
-                    } else {
-                        author.icon_url(ctx.author().default_avatar_url());
-                    }
-                    author.name(&ctx.author().name);
-                    author
-                });
-
-                let imdb_rating = format!("⭐ {}", &api_response.imdb_rating);
-
-                let fields = vec![
-                    // (field_name, field_content, inline)
-                    ("IMDB Rating: ", &imdb_rating, false),
-                    ("Country", &api_response.country, true),
-                    ("Rated", &api_response.rated, true),
-                    ("Runtime", &api_response.runtime, true),
-                    ("Genre", &api_response.genre, false),
-                    ("Actors", &api_response.actors, false),
-                    ("Released", &api_response.released, false),
-                    ("Production", &api_response.production, true),
-                    ("Director", &api_response.director, true),
-                    ("Writer", &api_response.writer, true),
-                    ("Awards", &api_response.awards, false),
-                    ("Box Office", &api_response.box_office, true),
-                ];
-
-                embed.fields(fields);
-
-                embed.footer(|footer| {
-                    if let Some(icon_url) = &ctx.discord().cache.current_user().avatar_url() {
-                        footer.icon_url(icon_url);
-                    } else {
-                        footer.icon_url(ctx.discord().cache.current_user().default_avatar_url());
-                    }
-                    footer.text(format!(
-                        "{} | Movie",
-                        ctx.discord().cache.current_user().name
-                    ));
-                    footer
-                });
-
-                embed.timestamp(chrono::Utc::now());
-                embed.colour(serenity::Colour::from_rgb(245, 197, 24));
-
-                embed
-            })
-            .components(|components| {
-                // Add a button at the bottom of the embed
-                components.create_action_row(|action_row| {
+    // Fetch the data for the requested movie from the configured provider
+    let details = match provider.find(&movie).await {
+        Ok(Some(details)) => details,
+        Ok(None) => {
+            poise::say_reply(ctx, format!("No movie with title: \"{}\" found.", movie)).await?;
+            return Ok(());
+        }
+        Err(why) => {
+            error!("Couldn't fetch movie \"{}\" from {}: {}", movie, provider.name(), why);
+            poise::say_reply(ctx, format!("Couldn't reach {}. Please try again later.", provider.name())).await?;
+            return Ok(());
+        }
+    };
+
+    let reply = poise::send_reply(ctx, |message| {
+        message.embed(|embed| movie_embed(ctx, embed, &details, provider.name()));
+
+        // Show a small card with a photo for each of the top billed cast members
+        for member in &details.cast {
+            message.embed(|embed| cast_embed(embed, member));
+        }
+
+        message.components(|components| {
+            // Add the buttons at the bottom of the embed
+            components.create_action_row(|action_row| {
+                movie_buttons(action_row, &details);
+
+                // only movies with an IMDb ID can go on a watchlist
+                if details.imdb_id.is_some() {
                     action_row.create_button(|button| {
                         button
-                            .style(serenity::ButtonStyle::Link)
-                            .label("Open IMDb page")
-                            .url(format!("https://imdb.com/title/{}", &api_response.imdb_id))
-                    })
-                })
+                            .style(serenity::ButtonStyle::Primary)
+                            .label("Add to watchlist")
+                            .custom_id(ADD_BUTTON_ID)
+                    });
+                }
+
+                action_row
             })
+        })
     })
-    .await
-    {
-        error!("Couldn't respond to the movie command: {}", why);
+    .await;
+
+    let reply = match reply {
+        Ok(reply) => reply,
+        Err(why) => {
+            error!("Couldn't respond to the movie command: {}", why);
+            return Ok(());
+        }
     };
 
+    if details.imdb_id.is_some() {
+        handle_add_button(ctx, reply.message().await?, &details).await?;
+    }
+
     Ok(())
 }
 
-#[derive(Deserialize, Debug, Default)]
-#[serde(rename_all = "PascalCase")]
-struct Movie {
-    title: String,
-    plot: String,
-    year: String,
-    rated: String,
-    released: String,
-    runtime: String,
-    genre: String,
-    director: String,
-    writer: String,
-    actors: String,
-    country: String,
-    awards: String,
-    #[serde(rename = "imdbRating")]
-    imdb_rating: String,
-    #[serde(rename = "imdbID")]
-    imdb_id: String,
-    box_office: String,
-    production: String,
-    poster: String,
-    response: String,
+/// Fills an embed with the details of a movie.
+///
+/// `source` is the name of the provider the details came from, shown in the footer.
+pub fn movie_embed<'a>(
+    ctx: Context<'_>,
+    embed: &'a mut serenity::CreateEmbed,
+    details: &MovieDetails,
+    source: &str,
+) -> &'a mut serenity::CreateEmbed {
+    match &details.year {
+        Some(year) => embed.title(format!("{} ({})", &details.title, year)),
+        None => embed.title(&details.title),
+    };
+    if let Some(page_url) = &details.page_url {
+        embed.url(page_url);
+    }
+    if let Some(plot) = &details.plot {
+        embed.description(plot);
+    }
+    if let Some(poster) = &details.poster {
+        embed.thumbnail(poster);
+        embed.image(poster);
+    }
+    embed.author(|author| {
+        if let Some(icon_url) = ctx.author().avatar_url() {
+            author.icon_url(icon_url);
+        } else {
+            author.icon_url(ctx.author().default_avatar_url());
+        }
+        author.name(&ctx.author().name);
+        author
+    });
+
+    let ratings = format_ratings(details);
+    // both lists can be long, and embed fields can only be 1024 characters long
+    let watch_providers = truncate(&format_watch_providers(details), MAX_FIELD_LENGTH);
+    let recommendations = truncate(&details.recommendations.join("\n"), MAX_FIELD_LENGTH);
+    let watch_providers_title = match &details.watch_providers {
+        Some(providers) => format!("Where to watch ({})", providers.region),
+        None => String::new(),
+    };
+
+    let fields = vec![
+        // (field_name, field_content, inline)
+        ("Ratings", Some(&ratings), false),
+        ("Country", details.country.as_ref(), true),
+        ("Language", details.language.as_ref(), true),
+        ("Rated", details.rated.as_ref(), true),
+        ("Runtime", details.runtime.as_ref(), true),
+        ("Genre", details.genre.as_ref(), false),
+        ("Actors", details.actors.as_ref(), false),
+        ("Released", details.released.as_ref(), true),
+        ("DVD", details.dvd.as_ref(), true),
+        ("Production", details.production.as_ref(), true),
+        ("Director", details.director.as_ref(), true),
+        ("Writer", details.writer.as_ref(), true),
+        ("Awards", details.awards.as_ref(), false),
+        ("Box Office", details.box_office.as_ref(), true),
+        (watch_providers_title.as_str(), Some(&watch_providers), false),
+        ("Similar titles", Some(&recommendations), false),
+    ];
+
+    // drop every field that the provider has no value for
+    let fields = fields
+        .into_iter()
+        .filter_map(|(name, content, inline)| match content {
+            Some(content) if !content.is_empty() => Some((name, content, inline)),
+            _ => None,
+        });
+
+    embed.fields(fields);
+
+    embed.footer(|footer| {
+        if let Some(icon_url) = &ctx.discord().cache.current_user().avatar_url() {
+            footer.icon_url(icon_url);
+        } else {
+            footer.icon_url(ctx.discord().cache.current_user().default_avatar_url());
+        }
+        footer.text(format!(
+            "{} | Movie | {}",
+            ctx.discord().cache.current_user().name,
+            source
+        ));
+        footer
+    });
+
+    embed.timestamp(chrono::Utc::now());
+    embed.colour(serenity::Colour::from_rgb(245, 197, 24));
+
+    embed
+}
+
+/// Adds the link buttons (IMDb page, trailer, etc) for a movie to an action row
+pub fn movie_buttons<'a>(
+    action_row: &'a mut serenity::CreateActionRow,
+    details: &MovieDetails,
+) -> &'a mut serenity::CreateActionRow {
+    if let Some(imdb_id) = &details.imdb_id {
+        action_row.create_button(|button| {
+            button
+                .style(serenity::ButtonStyle::Link)
+                .label("Open IMDb page")
+                .url(format!("https://imdb.com/title/{}", imdb_id))
+        });
+    } else if let Some(page_url) = &details.page_url {
+        action_row.create_button(|button| {
+            button
+                .style(serenity::ButtonStyle::Link)
+                .label("Open movie page")
+                .url(page_url)
+        });
+    }
+
+    if let Some(trailer) = &details.trailer {
+        action_row.create_button(|button| {
+            button
+                .style(serenity::ButtonStyle::Link)
+                .label("Watch trailer")
+                .url(trailer)
+        });
+    }
+
+    if let Some(link) = details.watch_providers.as_ref().and_then(|providers| providers.link.as_ref()) {
+        action_row.create_button(|button| {
+            button
+                .style(serenity::ButtonStyle::Link)
+                .label("Where to watch")
+                .url(link)
+        });
+    }
+
+    action_row
+}
+
+/// Fills a small embed with the name, character and photo of a cast member
+fn cast_embed<'a>(embed: &'a mut serenity::CreateEmbed, member: &CastMember) -> &'a mut serenity::CreateEmbed {
+    embed.title(&member.name);
+    if let Some(character) = &member.character {
+        embed.description(format!("as *{}*", character));
+    }
+    if let Some(photo) = &member.photo {
+        embed.thumbnail(photo);
+    }
+    embed.colour(serenity::Colour::from_rgb(245, 197, 24));
+
+    embed
+}
+
+/// Lists every rating source of a movie on its own line, with its icon and vote count.
+fn format_ratings(details: &MovieDetails) -> String {
+    details
+        .ratings
+        .iter()
+        .map(|rating| {
+            let mut line = format!("{} **{}**: {}", rating_icon(&rating.source), rating.source, rating.value);
+            if let Some(votes) = &rating.votes {
+                line.push_str(&format!(" ({} votes)", votes));
+            }
+            line
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Lists the streaming, rent and buy options of a movie, one kind per line
+fn format_watch_providers(details: &MovieDetails) -> String {
+    let providers = match &details.watch_providers {
+        Some(providers) => providers,
+        None => return String::new(),
+    };
+
+    let kinds = vec![
+        ("📺 Stream", &providers.stream),
+        ("💵 Rent", &providers.rent),
+        ("🛒 Buy", &providers.buy),
+    ];
+
+    kinds
+        .into_iter()
+        .filter(|(_, names)| !names.is_empty())
+        .map(|(kind, names)| format!("**{}**: {}", kind, names.join(", ")))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Takes the name of a rating source and returns an icon for it
+fn rating_icon(source: &str) -> &'static str {
+    match source {
+        "Internet Movie Database" => "⭐",
+        "Rotten Tomatoes" => "🍅",
+        "Metacritic" => "Ⓜ️",
+        "TMDb" => "🎞️",
+        _ => "🎬",
+    }
 }
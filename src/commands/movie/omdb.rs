@@ -0,0 +1,290 @@
+use crate::helpers::Api;
+use crate::Error;
+
+use async_trait::async_trait;
+use serde::Deserialize;
+
+use super::provider::{MovieDetails, MovieProvider, Rating};
+
+const OMDB_API: &str = "http://www.omdbapi.com/";
+
+/// Movie data from the OMDb API
+pub struct OmdbProvider {
+    api_key: String,
+    client: reqwest::Client,
+}
+
+impl OmdbProvider {
+    pub fn new(api_key: String) -> Self {
+        OmdbProvider {
+            api_key,
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl MovieProvider for OmdbProvider {
+    fn name(&self) -> &'static str {
+        "OMDb"
+    }
+
+    fn quota(&self) -> (Api, u32) {
+        (Api::Omdb, 1)
+    }
+
+    async fn find(&self, title: &str) -> Result<Option<MovieDetails>, Error> {
+        self.get(&[("t", title), ("type", "movie")]).await
+    }
+
+    async fn find_by_imdb_id(&self, imdb_id: &str) -> Result<Option<MovieDetails>, Error> {
+        self.get(&[("i", imdb_id)]).await
+    }
+}
+
+impl OmdbProvider {
+    /// Makes a request to the OMDb API with the given query parameters
+    async fn get(&self, query: &[(&str, &str)]) -> Result<Option<MovieDetails>, Error> {
+        let movie = self
+            .client
+            .get(OMDB_API)
+            .query(&[("apikey", self.api_key.as_str())])
+            .query(query)
+            .send()
+            .await?
+            .json::<OmdbMovie>()
+            .await?;
+
+        to_details(movie)
+    }
+}
+
+/// Turns an OMDb response into the details of a movie.
+/// Returns `None` if OMDb didn't find the movie, and an error for anything else it says went wrong,
+/// like an invalid api key or the daily limit being reached.
+fn to_details(movie: OmdbMovie) -> Result<Option<MovieDetails>, Error> {
+    if movie.response == "True" {
+        return Ok(Some(movie.into()));
+    }
+
+    // e.g. "Movie not found!"
+    if movie.error.to_lowercase().contains("not found") {
+        Ok(None)
+    } else if movie.error.is_empty() {
+        Err("OMDb didn't say what went wrong".into())
+    } else {
+        Err(movie.error.into())
+    }
+}
+
+/// Returns `None` if OMDb has no value for a field.
+/// OMDb sends "N/A" (or nothing at all) in place of missing values.
+fn available(value: String) -> Option<String> {
+    if value.trim().is_empty() || value.trim().to_lowercase() == "n/a" {
+        None
+    } else {
+        Some(value)
+    }
+}
+
+impl From<OmdbMovie> for MovieDetails {
+    fn from(movie: OmdbMovie) -> Self {
+        let imdb_votes = available(movie.imdb_votes);
+
+        let mut ratings = movie
+            .ratings
+            .into_iter()
+            .filter_map(|rating| {
+                let votes = if rating.source == IMDB_SOURCE {
+                    imdb_votes.clone()
+                } else {
+                    None
+                };
+
+                available(rating.value).map(|value| Rating {
+                    source: rating.source,
+                    value,
+                    votes,
+                })
+            })
+            .collect::<Vec<Rating>>();
+
+        // OMDb doesn't always list IMDb and Metacritic in the ratings array, so fall back to the plain fields
+        if !ratings.iter().any(|rating| rating.source == IMDB_SOURCE) {
+            if let Some(imdb_rating) = available(movie.imdb_rating) {
+                ratings.insert(
+                    0,
+                    Rating {
+                        source: String::from(IMDB_SOURCE),
+                        value: format!("{}/10", imdb_rating),
+                        votes: imdb_votes,
+                    },
+                );
+            }
+        }
+        if !ratings.iter().any(|rating| rating.source == METACRITIC_SOURCE) {
+            if let Some(metascore) = available(movie.metascore) {
+                ratings.push(Rating {
+                    source: String::from(METACRITIC_SOURCE),
+                    value: format!("{}/100", metascore),
+                    votes: None,
+                });
+            }
+        }
+
+        let imdb_id = available(movie.imdb_id);
+
+        MovieDetails {
+            title: movie.title,
+            year: available(movie.year),
+            plot: available(movie.plot),
+            poster: available(movie.poster),
+            page_url: imdb_id.as_ref().map(|id| format!("https://imdb.com/title/{}", id)),
+            imdb_id,
+            ratings,
+            rated: available(movie.rated),
+            released: available(movie.released),
+            dvd: available(movie.dvd),
+            runtime: available(movie.runtime),
+            genre: available(movie.genre),
+            director: available(movie.director),
+            writer: available(movie.writer),
+            actors: available(movie.actors),
+            country: available(movie.country),
+            language: available(movie.language),
+            awards: available(movie.awards),
+            box_office: available(movie.box_office),
+            production: available(movie.production),
+            ..Default::default()
+        }
+    }
+}
+
+// the names OMDb uses for the rating sources that also have their own fields
+const IMDB_SOURCE: &str = "Internet Movie Database";
+const METACRITIC_SOURCE: &str = "Metacritic";
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "PascalCase", default)]
+struct OmdbMovie {
+    title: String,
+    plot: String,
+    year: String,
+    rated: String,
+    released: String,
+    runtime: String,
+    genre: String,
+    director: String,
+    writer: String,
+    actors: String,
+    country: String,
+    awards: String,
+    language: String,
+    metascore: String,
+    ratings: Vec<OmdbRating>,
+    #[serde(rename = "imdbRating")]
+    imdb_rating: String,
+    #[serde(rename = "imdbVotes")]
+    imdb_votes: String,
+    #[serde(rename = "imdbID")]
+    imdb_id: String,
+    #[serde(rename = "DVD")]
+    dvd: String,
+    box_office: String,
+    production: String,
+    poster: String,
+    response: String,
+    error: String, // only there when the response is "False"
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "PascalCase")]
+struct OmdbRating {
+    source: String, // "Internet Movie Database", "Rotten Tomatoes" or "Metacritic"
+    value: String,  // e.g. "8.8/10", "87%" or "74/100"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> OmdbMovie {
+        let path = format!("{}/tests/fixtures/movie/{}", env!("CARGO_MANIFEST_DIR"), name);
+        let json = std::fs::read_to_string(&path).expect("missing fixture");
+        serde_json::from_str(&json).expect("fixture doesn't match OmdbMovie")
+    }
+
+    fn rating<'a>(details: &'a MovieDetails, source: &str) -> Option<&'a Rating> {
+        details.ratings.iter().find(|rating| rating.source == source)
+    }
+
+    #[test]
+    fn converts_a_movie() {
+        let details = MovieDetails::from(fixture("omdb_movie.json"));
+
+        assert_eq!(details.title, "The Shawshank Redemption");
+        assert_eq!(details.year.as_deref(), Some("1994"));
+        assert_eq!(details.imdb_id.as_deref(), Some("tt0111161"));
+        assert_eq!(details.page_url.as_deref(), Some("https://imdb.com/title/tt0111161"));
+        assert_eq!(details.director.as_deref(), Some("Frank Darabont"));
+        assert_eq!(details.writer.as_deref(), Some("Stephen King, Frank Darabont"));
+        assert_eq!(details.box_office.as_deref(), Some("$28,767,189"));
+    }
+
+    #[test]
+    fn drops_missing_values() {
+        let details = MovieDetails::from(fixture("omdb_movie.json"));
+        assert_eq!(details.dvd, None);
+        assert_eq!(details.production, None);
+
+        // "N/A", "n/a" and blank values are all missing
+        let details = MovieDetails::from(fixture("omdb_movie_missing_ratings.json"));
+        assert_eq!(details.poster, None);
+        assert_eq!(details.awards, None);
+        assert_eq!(details.box_office, None);
+        assert_eq!(details.production, None);
+        assert_eq!(details.dvd, None);
+        assert!(rating(&details, "Rotten Tomatoes").is_none());
+    }
+
+    #[test]
+    fn keeps_listed_ratings() {
+        let details = MovieDetails::from(fixture("omdb_movie.json"));
+
+        let sources = details.ratings.iter().map(|rating| rating.source.as_str()).collect::<Vec<&str>>();
+        assert_eq!(sources, [IMDB_SOURCE, "Rotten Tomatoes", METACRITIC_SOURCE]);
+
+        let imdb = rating(&details, IMDB_SOURCE).unwrap();
+        assert_eq!(imdb.value, "9.3/10");
+        assert_eq!(imdb.votes.as_deref(), Some("2,711,075"));
+        assert_eq!(rating(&details, "Rotten Tomatoes").unwrap().votes, None);
+    }
+
+    #[test]
+    fn falls_back_to_the_imdb_and_metacritic_fields() {
+        let details = MovieDetails::from(fixture("omdb_movie_missing_ratings.json"));
+
+        // imdb goes first and metacritic last, like in the ratings array
+        assert_eq!(details.ratings.len(), 2);
+        let imdb = &details.ratings[0];
+        assert_eq!(imdb.source, IMDB_SOURCE);
+        assert_eq!(imdb.value, "6.8/10");
+        assert_eq!(imdb.votes.as_deref(), Some("112,430"));
+        let metacritic = &details.ratings[1];
+        assert_eq!(metacritic.source, METACRITIC_SOURCE);
+        assert_eq!(metacritic.value, "68/100");
+        assert_eq!(metacritic.votes, None);
+    }
+
+    #[test]
+    fn recognizes_a_movie_that_was_not_found() {
+        let details = to_details(fixture("omdb_not_found.json")).expect("not found isn't an error");
+        assert!(details.is_none());
+    }
+
+    #[test]
+    fn reports_other_errors() {
+        let why = to_details(fixture("omdb_invalid_key.json")).expect_err("an invalid key is an error");
+        assert_eq!(why.to_string(), "Invalid API key!");
+    }
+}
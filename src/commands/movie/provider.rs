@@ -0,0 +1,105 @@
+use crate::helpers::Api;
+use crate::Error;
+use std::env;
+
+use async_trait::async_trait;
+
+use super::{omdb::OmdbProvider, tmdb::TmdbProvider};
+
+/// A source of movie data (OMDb, TMDb, etc).
+///
+/// Every provider turns its own API response into a `MovieDetails`,
+/// so the movie embed doesn't need to know where the data came from.
+#[async_trait]
+pub trait MovieProvider: Send + Sync {
+    /// The name of the provider, shown in the footer of the movie embed
+    fn name(&self) -> &'static str;
+
+    /// The API whose quota this provider uses, and how many calls one lookup takes
+    fn quota(&self) -> (Api, u32);
+
+    /// Looks up a movie by its title.
+    /// Returns `None` if the provider doesn't know any movie with that title.
+    async fn find(&self, title: &str) -> Result<Option<MovieDetails>, Error>;
+
+    /// Looks up a movie by its IMDb ID (tt0111161, etc).
+    /// Returns `None` if the provider doesn't know any movie with that ID.
+    async fn find_by_imdb_id(&self, imdb_id: &str) -> Result<Option<MovieDetails>, Error>;
+}
+
+/// Creates the movie provider chosen with the MOVIE_PROVIDER environment variable.
+///
+/// Supported values are "omdb" (the default) and "tmdb".
+pub fn provider_from_env() -> Box<dyn MovieProvider> {
+    let provider = env::var("MOVIE_PROVIDER").unwrap_or_else(|_| String::from("omdb"));
+
+    match provider.to_lowercase().as_str() {
+        "tmdb" => {
+            let api_key = env::var("TMDB_API_KEY").expect("Expected environment variable TMDB_API_KEY");
+            // the region that streaming availability is shown for
+            let region = env::var("TMDB_REGION").unwrap_or_else(|_| String::from("US"));
+
+            Box::new(TmdbProvider::new(api_key, region))
+        }
+        "omdb" => {
+            let api_key = env::var("OMDB_API_KEY").expect("Expected environment variable OMDB_API_KEY");
+
+            Box::new(OmdbProvider::new(api_key))
+        }
+        other => panic!("Unknown MOVIE_PROVIDER \"{}\". Expected \"omdb\" or \"tmdb\".", other),
+    }
+}
+
+/// Everything the movie embed can show about a movie.
+/// Fields that the provider has no value for are `None` (or empty) and don't get rendered.
+#[derive(Debug, Default, Clone)]
+pub struct MovieDetails {
+    pub title: String,
+    pub year: Option<String>,
+    pub plot: Option<String>,
+    pub poster: Option<String>,
+    pub imdb_id: Option<String>,
+    pub page_url: Option<String>, // link to the movie on the provider's website
+    pub ratings: Vec<Rating>,
+    pub rated: Option<String>,
+    pub released: Option<String>,
+    pub dvd: Option<String>,
+    pub runtime: Option<String>,
+    pub genre: Option<String>,
+    pub director: Option<String>,
+    pub writer: Option<String>,
+    pub actors: Option<String>,
+    pub country: Option<String>,
+    pub language: Option<String>,
+    pub awards: Option<String>,
+    pub box_office: Option<String>,
+    pub production: Option<String>,
+    pub trailer: Option<String>,             // link to the official trailer
+    pub watch_providers: Option<WatchProviders>,
+    pub cast: Vec<CastMember>,               // only filled by providers that have cast photos
+    pub recommendations: Vec<String>,        // titles of similar movies
+}
+
+#[derive(Debug, Clone)]
+pub struct Rating {
+    pub source: String, // "Internet Movie Database", "Rotten Tomatoes", "Metacritic", "TMDb"
+    pub value: String,  // e.g. "8.8/10", "87%" or "74/100"
+    pub votes: Option<String>,
+}
+
+/// Where a movie can be watched in one region
+#[derive(Debug, Clone)]
+pub struct WatchProviders {
+    pub region: String,
+    pub link: Option<String>,
+    pub stream: Vec<String>,
+    pub rent: Vec<String>,
+    pub buy: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CastMember {
+    pub name: String,
+    pub character: Option<String>,
+    pub photo: Option<String>,
+}
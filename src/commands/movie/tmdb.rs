@@ -0,0 +1,451 @@
+use crate::helpers::Api;
+use crate::Error;
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use serde::Deserialize;
+
+use super::provider::{CastMember, MovieDetails, MovieProvider, Rating, WatchProviders};
+
+const TMDB_API: &str = "https://api.themoviedb.org/3";
+const TMDB_IMAGES: &str = "https://image.tmdb.org/t/p";
+
+/// Movie data from The Movie Database (TMDb) API.
+///
+/// On top of the usual details, TMDb has trailers, streaming availability,
+/// cast photos and recommendations for similar movies.
+pub struct TmdbProvider {
+    api_key: String,
+    region: String, // the region (ISO 3166-1 code) to show streaming availability for
+    client: reqwest::Client,
+}
+
+impl TmdbProvider {
+    pub fn new(api_key: String, region: String) -> Self {
+        TmdbProvider {
+            api_key,
+            region: region.to_uppercase(),
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl MovieProvider for TmdbProvider {
+    fn name(&self) -> &'static str {
+        "TMDb"
+    }
+
+    fn quota(&self) -> (Api, u32) {
+        // a search (or find) and then the details
+        (Api::Tmdb, 2)
+    }
+
+    async fn find(&self, title: &str) -> Result<Option<MovieDetails>, Error> {
+        // TMDb can only give details by its own ID, so search for the title first
+        let search = self
+            .client
+            .get(format!("{}/search/movie", TMDB_API))
+            .query(&[("api_key", self.api_key.as_str()), ("query", title)])
+            .send()
+            .await?
+            .json::<SearchResponse>()
+            .await?;
+
+        match search.results.first() {
+            Some(result) => Ok(Some(self.details(result.id).await?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn find_by_imdb_id(&self, imdb_id: &str) -> Result<Option<MovieDetails>, Error> {
+        let found = self
+            .client
+            .get(format!("{}/find/{}", TMDB_API, imdb_id))
+            .query(&[("api_key", self.api_key.as_str()), ("external_source", "imdb_id")])
+            .send()
+            .await?
+            .json::<FindResponse>()
+            .await?;
+
+        match found.movie_results.first() {
+            Some(result) => Ok(Some(self.details(result.id).await?)),
+            None => Ok(None),
+        }
+    }
+}
+
+impl TmdbProvider {
+    /// Gets the details of a movie by its TMDb ID, along with everything else we show,
+    /// in a single request
+    async fn details(&self, movie_id: u64) -> Result<MovieDetails, Error> {
+        let movie = self
+            .client
+            .get(format!("{}/movie/{}", TMDB_API, movie_id))
+            .query(&[
+                ("api_key", self.api_key.as_str()),
+                ("append_to_response", "videos,credits,recommendations,watch/providers"),
+            ])
+            .send()
+            .await?
+            .json::<TmdbMovie>()
+            .await?;
+
+        Ok(movie.into_details(&self.region))
+    }
+}
+
+/// Returns the full url of an image on the TMDb CDN
+fn image_url(size: &str, path: &str) -> String {
+    format!("{}/{}{}", TMDB_IMAGES, size, path)
+}
+
+/// Joins the names of a list of TMDb objects with commas,
+/// returning `None` for an empty list.
+fn join_names<'a>(names: impl Iterator<Item = &'a str>) -> Option<String> {
+    let names = names.collect::<Vec<&str>>();
+
+    if names.is_empty() {
+        None
+    } else {
+        Some(names.join(", "))
+    }
+}
+
+impl TmdbMovie {
+    fn into_details(self, region: &str) -> MovieDetails {
+        let release_date = self.release_date.filter(|date| !date.is_empty());
+
+        let mut ratings = Vec::new();
+        if self.vote_count > 0 {
+            ratings.push(Rating {
+                source: String::from("TMDb"),
+                value: format!("{:.1}/10", self.vote_average),
+                votes: Some(self.vote_count.to_string()),
+            });
+        }
+
+        // prefer an official YouTube trailer, but take any trailer if there's none
+        let trailers = self
+            .videos
+            .results
+            .iter()
+            .filter(|video| video.site == "YouTube" && video.kind == "Trailer")
+            .collect::<Vec<&Video>>();
+        let trailer = trailers
+            .iter()
+            .find(|video| video.official)
+            .or_else(|| trailers.first())
+            .map(|video| format!("https://www.youtube.com/watch?v={}", video.key));
+
+        let watch_providers = self
+            .watch_providers
+            .results
+            .get(region)
+            .map(|providers| WatchProviders {
+                region: region.to_string(),
+                link: providers.link.clone(),
+                stream: providers.flatrate.iter().map(|p| p.provider_name.clone()).collect(),
+                rent: providers.rent.iter().map(|p| p.provider_name.clone()).collect(),
+                buy: providers.buy.iter().map(|p| p.provider_name.clone()).collect(),
+            });
+
+        let director = join_names(
+            self.credits
+                .crew
+                .iter()
+                .filter(|member| member.job == "Director")
+                .map(|member| member.name.as_str()),
+        );
+        let writer = join_names(
+            self.credits
+                .crew
+                .iter()
+                .filter(|member| member.job == "Screenplay" || member.job == "Writer")
+                .map(|member| member.name.as_str()),
+        );
+        let actors = join_names(self.credits.cast.iter().take(5).map(|member| member.name.as_str()));
+
+        let cast = self
+            .credits
+            .cast
+            .iter()
+            .take(3)
+            .map(|member| CastMember {
+                name: member.name.clone(),
+                character: member.character.clone().filter(|character| !character.is_empty()),
+                photo: member.profile_path.as_ref().map(|path| image_url("w185", path)),
+            })
+            .collect();
+
+        let recommendations = self
+            .recommendations
+            .results
+            .iter()
+            .take(5)
+            .map(|movie| match movie.release_date.as_deref() {
+                Some(date) if date.len() >= 4 => format!("{} ({})", movie.title, &date[..4]),
+                _ => movie.title.clone(),
+            })
+            .collect();
+
+        MovieDetails {
+            year: release_date.as_ref().filter(|date| date.len() >= 4).map(|date| date[..4].to_string()),
+            plot: self.overview.filter(|overview| !overview.is_empty()),
+            poster: self.poster_path.map(|path| image_url("w500", &path)),
+            imdb_id: self.imdb_id.filter(|id| !id.is_empty()),
+            page_url: Some(format!("https://www.themoviedb.org/movie/{}", self.id)),
+            ratings,
+            released: release_date,
+            runtime: self.runtime.filter(|runtime| *runtime > 0).map(|runtime| format!("{} min", runtime)),
+            genre: join_names(self.genres.iter().map(|genre| genre.name.as_str())),
+            director,
+            writer,
+            actors,
+            country: join_names(self.production_countries.iter().map(|country| country.name.as_str())),
+            language: join_names(self.spoken_languages.iter().map(|language| language.english_name.as_str())),
+            box_office: Some(self.revenue)
+                .filter(|revenue| *revenue > 0)
+                .map(|revenue| format!("${}", revenue)),
+            production: join_names(self.production_companies.iter().map(|company| company.name.as_str())),
+            trailer,
+            watch_providers,
+            cast,
+            recommendations,
+            title: self.title,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchResponse {
+    results: Vec<SearchResult>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FindResponse {
+    movie_results: Vec<SearchResult>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchResult {
+    id: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct TmdbMovie {
+    id: u64,
+    title: String,
+    overview: Option<String>,
+    release_date: Option<String>, // YYYY-MM-DD
+    runtime: Option<u32>,         // in minutes
+    poster_path: Option<String>,
+    imdb_id: Option<String>,
+    vote_average: f64,
+    vote_count: u64,
+    #[serde(default)]
+    revenue: u64,
+    #[serde(default)]
+    genres: Vec<Named>,
+    #[serde(default)]
+    production_companies: Vec<Named>,
+    #[serde(default)]
+    production_countries: Vec<Named>,
+    #[serde(default)]
+    spoken_languages: Vec<Language>,
+    #[serde(default)]
+    videos: Videos,
+    #[serde(default)]
+    credits: Credits,
+    #[serde(default)]
+    recommendations: Recommendations,
+    #[serde(rename = "watch/providers", default)]
+    watch_providers: WatchProvidersResponse,
+}
+
+#[derive(Debug, Deserialize)]
+struct Named {
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Language {
+    english_name: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Videos {
+    results: Vec<Video>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Video {
+    key: String,  // the youtube video id, for videos on youtube
+    site: String, // "YouTube", "Vimeo", etc
+    #[serde(rename = "type")]
+    kind: String, // "Trailer", "Teaser", "Clip", etc
+    #[serde(default)]
+    official: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Credits {
+    cast: Vec<Cast>,
+    crew: Vec<Crew>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Cast {
+    name: String,
+    character: Option<String>,
+    profile_path: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Crew {
+    name: String,
+    job: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Recommendations {
+    results: Vec<Recommendation>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Recommendation {
+    title: String,
+    release_date: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct WatchProvidersResponse {
+    results: HashMap<String, RegionProviders>, // keyed by region code
+}
+
+#[derive(Debug, Deserialize)]
+struct RegionProviders {
+    link: Option<String>,
+    #[serde(default)]
+    flatrate: Vec<Provider>, // subscription streaming services
+    #[serde(default)]
+    rent: Vec<Provider>,
+    #[serde(default)]
+    buy: Vec<Provider>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Provider {
+    provider_name: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> TmdbMovie {
+        let path = format!("{}/tests/fixtures/movie/{}", env!("CARGO_MANIFEST_DIR"), name);
+        let json = std::fs::read_to_string(&path).expect("missing fixture");
+        serde_json::from_str(&json).expect("fixture doesn't match TmdbMovie")
+    }
+
+    #[test]
+    fn converts_a_movie() {
+        let details = fixture("tmdb_movie.json").into_details("US");
+
+        assert_eq!(details.title, "The Matrix");
+        assert_eq!(details.year.as_deref(), Some("1999"));
+        assert_eq!(details.released.as_deref(), Some("1999-03-30"));
+        assert_eq!(details.runtime.as_deref(), Some("136 min"));
+        assert_eq!(details.imdb_id.as_deref(), Some("tt0133093"));
+        assert_eq!(details.page_url.as_deref(), Some("https://www.themoviedb.org/movie/603"));
+        assert_eq!(
+            details.poster.as_deref(),
+            Some("https://image.tmdb.org/t/p/w500/f89U3ADr1oiB1s9GkdPOEpXUk5H.jpg")
+        );
+        assert_eq!(details.genre.as_deref(), Some("Action, Science Fiction"));
+        assert_eq!(details.box_office.as_deref(), Some("$463517383"));
+        assert_eq!(details.ratings.len(), 1);
+        assert_eq!(details.ratings[0].value, "8.2/10");
+        assert_eq!(details.ratings[0].votes.as_deref(), Some("24683"));
+        assert_eq!(details.recommendations, ["The Matrix Reloaded (2003)", "Untitled Sequel"]);
+    }
+
+    #[test]
+    fn joins_directors_and_writers() {
+        let details = fixture("tmdb_movie.json").into_details("US");
+
+        assert_eq!(details.director.as_deref(), Some("Lilly Wachowski, Lana Wachowski"));
+        // screenplay and writer credits both count, producers don't
+        assert_eq!(details.writer.as_deref(), Some("Lilly Wachowski, Lana Wachowski"));
+        assert_eq!(
+            details.actors.as_deref(),
+            Some("Keanu Reeves, Laurence Fishburne, Carrie-Anne Moss")
+        );
+    }
+
+    #[test]
+    fn converts_the_cast() {
+        let details = fixture("tmdb_movie.json").into_details("US");
+
+        assert_eq!(details.cast.len(), 3);
+        assert_eq!(details.cast[0].character.as_deref(), Some("Thomas A. Anderson / Neo"));
+        assert_eq!(
+            details.cast[0].photo.as_deref(),
+            Some("https://image.tmdb.org/t/p/w185/4D0PpNI0kmP58hgrwGC3wCjxhnm.jpg")
+        );
+        assert_eq!(details.cast[1].photo, None);
+        assert_eq!(details.cast[2].character, None);
+    }
+
+    #[test]
+    fn prefers_an_official_youtube_trailer() {
+        let details = fixture("tmdb_movie.json").into_details("US");
+        assert_eq!(details.trailer.as_deref(), Some("https://www.youtube.com/watch?v=vKQi3bBA1y8"));
+    }
+
+    #[test]
+    fn falls_back_to_the_first_youtube_trailer() {
+        let details = fixture("tmdb_movie_unofficial_trailer.json").into_details("US");
+        assert_eq!(details.trailer.as_deref(), Some("https://www.youtube.com/watch?v=fanTrailer1"));
+    }
+
+    #[test]
+    fn picks_the_watch_providers_of_the_region() {
+        let us = fixture("tmdb_movie.json").into_details("US").watch_providers.unwrap();
+        assert_eq!(us.region, "US");
+        assert_eq!(
+            us.link.as_deref(),
+            Some("https://www.themoviedb.org/movie/603-the-matrix/watch?locale=US")
+        );
+        assert_eq!(us.stream, ["Max"]);
+        assert_eq!(us.rent, ["Apple TV", "Google Play Movies"]);
+        assert_eq!(us.buy, ["Apple TV"]);
+
+        let gb = fixture("tmdb_movie.json").into_details("GB").watch_providers.unwrap();
+        assert_eq!(gb.region, "GB");
+        assert!(gb.stream.is_empty());
+        assert_eq!(gb.rent, ["Amazon Video"]);
+        assert!(gb.buy.is_empty());
+
+        assert!(fixture("tmdb_movie.json").into_details("DE").watch_providers.is_none());
+    }
+
+    #[test]
+    fn drops_empty_values() {
+        let details = fixture("tmdb_movie_unofficial_trailer.json").into_details("US");
+
+        assert_eq!(details.year, None);
+        assert_eq!(details.released, None);
+        assert_eq!(details.plot, None);
+        assert_eq!(details.runtime, None);
+        assert_eq!(details.imdb_id, None);
+        assert_eq!(details.poster, None);
+        assert_eq!(details.box_office, None);
+        assert_eq!(details.director, None);
+        assert_eq!(details.writer, None);
+        assert!(details.ratings.is_empty());
+        assert!(details.watch_providers.is_none());
+    }
+}
@@ -0,0 +1,332 @@
+use crate::{Context, Error};
+use std::collections::HashMap;
+
+use chrono::{Duration, NaiveDateTime, TimeZone, Utc};
+use log::error;
+use poise::serenity_prelude as serenity;
+use rand::seq::SliceRandom;
+
+use crate::helpers::{check_quota, truncate};
+use crate::commands::movie::{
+    movie::{movie_buttons, movie_embed},
+    provider::MovieDetails,
+};
+
+const MAX_CANDIDATES: usize = 5; // discord allows 5 action rows per message, one per candidate
+const DEFAULT_VOTING_MINUTES: i64 = 60;
+const MAX_CARD_TITLE_LENGTH: usize = 150; // 5 cards with the longest title and plot stay under 6000 characters
+const MAX_CARD_PLOT_LENGTH: usize = 700;
+const MAX_EVENT_NAME_LENGTH: usize = 100; // the limits of discord's scheduled events
+const MAX_EVENT_DESCRIPTION_LENGTH: usize = 1000;
+
+/// Start a movie night poll
+///
+/// Posts a card for every movie with a vote button. Everyone gets one vote,
+/// voting again changes your vote. When voting closes, the winner is announced.
+/// If a showtime is given, a server event is created for the winning movie.
+///
+/// **Usage:**
+/// `/movienight <movies> [minutes] [showtime]`
+/// Separate the movies with `|`. The showtime is in UTC, and has to be after voting closes.
+///
+/// **Example:**
+/// `&movienight "The Matrix | Alien | Heat" 120 "2021-11-05 20:00"`
+#[poise::command(prefix_command, slash_command, defer_response)]
+pub async fn movienight(
+    ctx: Context<'_>,
+    #[description = "The movies to vote on, separated by |"] movies: String,
+    #[description = "How many minutes voting stays open (60 by default)"] minutes: Option<u32>,
+    #[description = "When the showing starts (YYYY-MM-DD HH:MM in UTC). Creates a server event"]
+    showtime: Option<String>,
+) -> Result<(), Error> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id,
+        None => {
+            poise::say_reply(ctx, "Movie nights only work in a server.").await?;
+            return Ok(());
+        }
+    };
+
+    // the same movie twice would only split its votes
+    let mut titles: Vec<&str> = Vec::new();
+    for title in movies.split('|').map(str::trim).filter(|title| !title.is_empty()) {
+        if !titles.iter().any(|existing| existing.eq_ignore_ascii_case(title)) {
+            titles.push(title);
+        }
+    }
+
+    if titles.len() < 2 || titles.len() > MAX_CANDIDATES {
+        poise::say_reply(
+            ctx,
+            format!(
+                "Give between 2 and {} movies, separated by `|`. Example: `The Matrix | Alien | Heat`",
+                MAX_CANDIDATES
+            ),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let minutes = minutes.map(i64::from).unwrap_or(DEFAULT_VOTING_MINUTES).max(1);
+    let closes_at = Utc::now() + Duration::minutes(minutes);
+
+    // the winner is only known once voting closes
+    let showtime = match showtime.as_deref().map(str::trim) {
+        Some(showtime) if !showtime.is_empty() => match NaiveDateTime::parse_from_str(showtime, "%Y-%m-%d %H:%M") {
+            Ok(showtime) if Utc.from_utc_datetime(&showtime) >= closes_at => {
+                Some(Utc.from_utc_datetime(&showtime))
+            }
+            Ok(_) => {
+                poise::say_reply(
+                    ctx,
+                    format!("The showtime has to be after voting closes, in {} minutes.", minutes),
+                )
+                .await?;
+                return Ok(());
+            }
+            Err(_) => {
+                poise::say_reply(ctx, "The showtime has to look like `2021-11-05 20:00` (in UTC).").await?;
+                return Ok(());
+            }
+        },
+        _ => None,
+    };
+
+    // resolve every title with the movie provider, so the cards show the real movie
+    let provider = &ctx.data().movie_provider;
+    let (api, calls) = provider.quota();
+    if !check_quota(ctx, api, calls * titles.len() as u32).await? {
+        return Ok(());
+    }
+
+    let mut candidates: Vec<MovieDetails> = Vec::new();
+    let mut not_found: Vec<&str> = Vec::new();
+    for &title in &titles {
+        match provider.find(title).await {
+            Ok(Some(details)) => candidates.push(details),
+            Ok(None) => not_found.push(title),
+            Err(why) => {
+                error!("Couldn't fetch movie \"{}\" from {}: {}", title, provider.name(), why);
+                not_found.push(title);
+            }
+        }
+    }
+
+    if !not_found.is_empty() {
+        poise::say_reply(ctx, format!("Couldn't find these movies: {}", not_found.join(", "))).await?;
+        return Ok(());
+    }
+
+    let reply = poise::send_reply(ctx, |message| {
+        message.content(format!(
+            "🍿 **Movie night poll!** Vote for what we watch. Voting closes <t:{}:R>.",
+            closes_at.timestamp()
+        ));
+
+        for (index, candidate) in candidates.iter().enumerate() {
+            message.embed(|embed| candidate_embed(embed, index, candidate));
+        }
+
+        message.components(|components| {
+            for (index, candidate) in candidates.iter().enumerate() {
+                components.create_action_row(|action_row| {
+                    action_row.create_button(|button| {
+                        button
+                            .style(serenity::ButtonStyle::Primary)
+                            .label(format!("Vote #{}", index + 1))
+                            .custom_id(vote_button_id(index))
+                    });
+                    movie_buttons(action_row, candidate)
+                });
+            }
+            components
+        })
+    })
+    .await?;
+    let mut message = reply.message().await?;
+
+    // one vote per user, keyed by user id
+    let mut votes: HashMap<serenity::UserId, usize> = HashMap::new();
+
+    loop {
+        let remaining = match (closes_at - Utc::now()).to_std() {
+            Ok(remaining) => remaining,
+            Err(_) => break, // voting has closed
+        };
+
+        let interaction = match serenity::CollectComponentInteraction::new(ctx.discord())
+            .message_id(message.id)
+            .timeout(remaining)
+            .await
+        {
+            Some(interaction) => interaction,
+            None => break,
+        };
+
+        let index = match (0..candidates.len()).find(|index| interaction.data.custom_id == vote_button_id(*index)) {
+            Some(index) => index,
+            None => continue,
+        };
+
+        let reply = match votes.insert(interaction.user.id, index) {
+            Some(previous) if previous == index => format!("You already voted for **{}**.", candidates[index].title),
+            Some(_) => format!("Changed your vote to **{}**.", candidates[index].title),
+            None => format!("You voted for **{}**.", candidates[index].title),
+        };
+
+        interaction
+            .create_interaction_response(ctx.discord(), |response| {
+                response
+                    .kind(serenity::InteractionResponseType::ChannelMessageWithSource)
+                    .interaction_response_data(|data| {
+                        data.content(reply)
+                            .flags(serenity::InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
+                    })
+            })
+            .await?;
+    }
+
+    // count the votes for every candidate
+    let mut tally = vec![0; candidates.len()];
+    for index in votes.values() {
+        tally[*index] += 1;
+    }
+    let results = candidates
+        .iter()
+        .zip(&tally)
+        .enumerate()
+        .map(|(index, (candidate, count))| format!("**#{} {}**: {} vote(s)", index + 1, candidate.title, count))
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    // voting is over, so remove the buttons and show the results on the poll
+    message
+        .edit(ctx.discord(), |edit| {
+            edit.content(format!("🍿 **Movie night poll** (closed)\n{}", results))
+                .components(|components| components)
+        })
+        .await?;
+
+    // the interaction can't be replied to anymore after 15 minutes, so post in the channel instead
+    if votes.is_empty() {
+        ctx.channel_id()
+            .say(ctx.discord(), "🍿 The movie night poll closed without any votes.")
+            .await?;
+        return Ok(());
+    }
+
+    // pick a random movie out of the ones that are tied for the most votes
+    let most_votes = *tally.iter().max().unwrap_or(&0);
+    let tied = (0..candidates.len())
+        .filter(|index| tally[*index] == most_votes)
+        .collect::<Vec<usize>>();
+    let winner = &candidates[*tied.choose(&mut rand::thread_rng()).unwrap_or(&0)];
+
+    let mut announcement = format!(
+        "🏆 **{}** won the movie night poll with {} vote(s)!",
+        winner.title, most_votes
+    );
+    if tied.len() > 1 {
+        announcement.push_str(" (picked at random out of a tie)");
+    }
+
+    if let Some(showtime) = showtime {
+        match create_scheduled_event(ctx, guild_id, winner, showtime).await {
+            Ok(event_url) => announcement.push_str(&format!("\nShowing starts <t:{}:F>: {}", showtime.timestamp(), event_url)),
+            Err(why) => {
+                error!("Couldn't create the scheduled event for the movie night: {}", why);
+                announcement.push_str(&format!(
+                    "\nShowing starts <t:{}:F>. (Couldn't create a server event for it.)",
+                    showtime.timestamp()
+                ));
+            }
+        }
+    }
+
+    ctx.channel_id()
+        .send_message(ctx.discord(), |message| {
+            message
+                .content(announcement)
+                .embed(|embed| movie_embed(ctx, embed, winner, provider.name()))
+                .components(|components| {
+                    components.create_action_row(|action_row| movie_buttons(action_row, winner))
+                })
+        })
+        .await?;
+
+    Ok(())
+}
+
+/// The custom id of the vote button of the candidate at `index`
+fn vote_button_id(index: usize) -> String {
+    format!("movienight_vote_{}", index)
+}
+
+/// Fills a small card for one of the candidates of a movie night poll
+fn candidate_embed<'a>(
+    embed: &'a mut serenity::CreateEmbed,
+    index: usize,
+    candidate: &MovieDetails,
+) -> &'a mut serenity::CreateEmbed {
+    // all the cards share one message, and discord only allows 6000 characters in its embeds
+    let title = truncate(&candidate.title, MAX_CARD_TITLE_LENGTH);
+    match &candidate.year {
+        Some(year) => embed.title(format!("#{} {} ({})", index + 1, title, year)),
+        None => embed.title(format!("#{} {}", index + 1, title)),
+    };
+    if let Some(page_url) = &candidate.page_url {
+        embed.url(page_url);
+    }
+    if let Some(plot) = &candidate.plot {
+        embed.description(truncate(plot, MAX_CARD_PLOT_LENGTH));
+    }
+    if let Some(poster) = &candidate.poster {
+        embed.thumbnail(poster);
+    }
+    if let Some(rating) = candidate.ratings.first() {
+        embed.field(&rating.source, &rating.value, true);
+    }
+    if let Some(runtime) = &candidate.runtime {
+        embed.field("Runtime", runtime, true);
+    }
+    if let Some(genre) = &candidate.genre {
+        embed.field("Genre", genre, true);
+    }
+    embed.colour(serenity::Colour::from_rgb(245, 197, 24));
+
+    embed
+}
+
+/// Creates a discord scheduled event for the showing of the winning movie.
+/// Returns the link to the event.
+async fn create_scheduled_event(
+    ctx: Context<'_>,
+    guild_id: serenity::GuildId,
+    movie: &MovieDetails,
+    showtime: chrono::DateTime<Utc>,
+) -> Result<String, Error> {
+    // external events need an end time, so use the runtime of the movie if we know it
+    let runtime_minutes = movie
+        .runtime
+        .as_deref()
+        .and_then(|runtime| runtime.split_whitespace().next())
+        .and_then(|minutes| minutes.parse::<i64>().ok())
+        .unwrap_or(180);
+
+    let event = guild_id
+        .create_scheduled_event(ctx.discord(), |event| {
+            event
+                .name(truncate(&format!("Movie night: {}", movie.title), MAX_EVENT_NAME_LENGTH))
+                .kind(serenity::ScheduledEventType::External) // so it doesn't need a voice channel
+                .location("Movie night")
+                .start_time(showtime)
+                .end_time(showtime + Duration::minutes(runtime_minutes));
+            if let Some(plot) = &movie.plot {
+                event.description(truncate(plot, MAX_EVENT_DESCRIPTION_LENGTH));
+            }
+            event
+        })
+        .await?;
+
+    Ok(format!("https://discord.com/events/{}/{}", guild_id.0, event.id.0))
+}
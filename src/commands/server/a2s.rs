@@ -0,0 +1,597 @@
+use crate::Error;
+use std::{net::SocketAddr, time::Duration};
+
+use tokio::net::UdpSocket;
+
+const SINGLE_PACKET: i32 = -1; // 0xFFFFFFFF, the whole response is in this packet
+const MULTI_PACKET: i32 = -2; // 0xFFFFFFFE, the response is split over several packets
+const MAX_PACKET_SIZE: usize = 4096; // servers send at most 1400 bytes, but some are generous
+const MAX_PACKETS: u8 = 32; // more than any real server splits a response into
+const MAX_CHALLENGES: usize = 3; // some servers hand out a new challenge every time, don't ask forever
+
+const A2S_INFO: u8 = 0x54;
+const A2S_PLAYER: u8 = 0x55;
+const A2S_RULES: u8 = 0x56;
+const S2C_CHALLENGE: u8 = 0x41;
+const INFO_RESPONSE: u8 = 0x49;
+const PLAYER_RESPONSE: u8 = 0x44;
+const RULES_RESPONSE: u8 = 0x45;
+
+const THE_SHIP_APP_ID: u16 = 2400; // its info response has a few extra fields
+
+/// Queries Source engine game servers with the A2S protocol over UDP.
+///
+/// See https://developer.valvesoftware.com/wiki/Server_queries.
+/// It talks to whatever address it's given, checking that the address is
+/// allowed is up to the caller.
+pub struct A2sClient {
+    timeout: Duration, // for every packet
+}
+
+impl A2sClient {
+    pub fn new(timeout: Duration) -> Self {
+        A2sClient { timeout }
+    }
+
+    /// Gets the name, map, player count and other details of a server
+    pub async fn info(&self, address: SocketAddr) -> Result<ServerInfo, Error> {
+        let response = self
+            .query(address, A2S_INFO, b"Source Engine Query\0", None, INFO_RESPONSE)
+            .await?;
+        parse_info(&response)
+    }
+
+    /// Gets the players on a server, with their scores and how long they've been connected
+    pub async fn players(&self, address: SocketAddr) -> Result<Vec<Player>, Error> {
+        let response = self
+            .query(address, A2S_PLAYER, &[], Some([0xFF; 4]), PLAYER_RESPONSE)
+            .await?;
+        parse_players(&response)
+    }
+
+    /// Gets the server's rules (its console variables), as names and values
+    pub async fn rules(&self, address: SocketAddr) -> Result<Vec<(String, String)>, Error> {
+        let response = self
+            .query(address, A2S_RULES, &[], Some([0xFF; 4]), RULES_RESPONSE)
+            .await?;
+        parse_rules(&response)
+    }
+
+    /// Sends a request and returns the body of the response (everything after its type).
+    ///
+    /// If the server answers with a challenge instead, the request is sent again with the challenge,
+    /// which is how servers make sure the address isn't spoofed.
+    async fn query(
+        &self,
+        address: SocketAddr,
+        kind: u8,
+        payload: &[u8],
+        mut challenge: Option<[u8; 4]>,
+        expected: u8,
+    ) -> Result<Vec<u8>, Error> {
+        let local_address = if address.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+        let socket = UdpSocket::bind(local_address).await?;
+        socket.connect(address).await?;
+
+        for _ in 0..=MAX_CHALLENGES {
+            let mut request = SINGLE_PACKET.to_le_bytes().to_vec();
+            request.push(kind);
+            request.extend_from_slice(payload);
+            if let Some(challenge) = challenge {
+                request.extend_from_slice(&challenge);
+            }
+            socket.send(&request).await?;
+
+            let response = self.receive(&socket).await?;
+            match response.first() {
+                Some(&S2C_CHALLENGE) if response.len() >= 5 => {
+                    challenge = Some([response[1], response[2], response[3], response[4]]);
+                }
+                Some(kind) if *kind == expected => return Ok(response[1..].to_vec()),
+                Some(kind) => return Err(format!("the server sent an unexpected response (0x{:02X})", kind).into()),
+                None => return Err("the server sent an empty response".into()),
+            }
+        }
+
+        Err("the server kept asking for a new challenge".into())
+    }
+
+    /// Receives one response, putting it back together if it was split over several packets.
+    /// Returns it without the packet header, starting at the response type.
+    async fn receive(&self, socket: &UdpSocket) -> Result<Vec<u8>, Error> {
+        let mut split_id = None;
+        let mut parts = Vec::<Option<Vec<u8>>>::new();
+
+        loop {
+            let mut buffer = vec![0; MAX_PACKET_SIZE];
+            let length = tokio::time::timeout(self.timeout, socket.recv(&mut buffer))
+                .await
+                .map_err(|_| "the server didn't answer in time")??;
+            let mut reader = Reader::new(&buffer[..length]);
+
+            match reader.i32()? {
+                SINGLE_PACKET => return Ok(reader.rest().to_vec()),
+                MULTI_PACKET => {
+                    let id = reader.i32()?;
+                    if (id as u32) & 0x8000_0000 != 0 {
+                        return Err("the server sent a compressed response, which isn't supported".into());
+                    }
+                    let total = reader.u8()?;
+                    let number = reader.u8()?;
+                    let _size = reader.u16()?; // the most the server puts in one packet
+
+                    if total == 0 || total > MAX_PACKETS || number >= total {
+                        return Err("the server sent a broken split response".into());
+                    }
+                    match split_id {
+                        None => {
+                            split_id = Some(id);
+                            parts = vec![None; total as usize];
+                        }
+                        // a packet of some older response, ignore it
+                        Some(split_id) if split_id != id || parts.len() != total as usize => continue,
+                        Some(_) => (),
+                    }
+                    parts[number as usize] = Some(reader.rest().to_vec());
+
+                    if parts.iter().all(Option::is_some) {
+                        let joined = std::mem::take(&mut parts).into_iter().flatten().flatten().collect::<Vec<u8>>();
+                        // the joined parts start with a single packet header of their own
+                        let mut reader = Reader::new(&joined);
+                        if reader.i32()? != SINGLE_PACKET {
+                            return Err("the server sent a broken split response".into());
+                        }
+                        return Ok(reader.rest().to_vec());
+                    }
+                }
+                _ => return Err("the server didn't answer with an A2S response".into()),
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ServerInfo {
+    pub name: String,
+    pub map: String,
+    pub game: String, // the game's full name, e.g. "Counter-Strike: Global Offensive"
+    pub app_id: u16,
+    pub players: u8,
+    pub max_players: u8,
+    pub bots: u8,
+    pub server_type: char, // 'd' for dedicated, 'l' for listen (non-dedicated), 'p' for SourceTV
+    pub environment: char, // 'l' for Linux, 'w' for Windows, 'm' or 'o' for macOS
+    pub password: bool,
+    pub vac: bool,
+    pub version: String,
+    pub port: Option<u16>,        // the port to connect to, when it isn't the query port
+    pub keywords: Option<String>, // tags, comma separated
+}
+
+#[derive(Debug, Clone)]
+pub struct Player {
+    pub name: String, // empty while they're connecting
+    pub score: i32,
+    pub duration: f32, // seconds since they connected
+}
+
+fn parse_info(body: &[u8]) -> Result<ServerInfo, Error> {
+    let mut reader = Reader::new(body);
+
+    reader.u8()?; // the protocol version
+    let name = reader.string()?;
+    let map = reader.string()?;
+    reader.string()?; // the game's folder, e.g. "cstrike"
+    let game = reader.string()?;
+    let app_id = reader.u16()?;
+    let players = reader.u8()?;
+    let max_players = reader.u8()?;
+    let bots = reader.u8()?;
+    let server_type = (reader.u8()? as char).to_ascii_lowercase();
+    let environment = (reader.u8()? as char).to_ascii_lowercase();
+    let password = reader.u8()? == 1;
+    let vac = reader.u8()? == 1;
+    if app_id == THE_SHIP_APP_ID {
+        reader.bytes(3)?; // the game mode, witness count and witness time
+    }
+    let version = reader.string()?;
+
+    let mut info = ServerInfo {
+        name,
+        map,
+        game,
+        app_id,
+        players,
+        max_players,
+        bots,
+        server_type,
+        environment,
+        password,
+        vac,
+        version,
+        port: None,
+        keywords: None,
+    };
+
+    // the extra data flag says which optional fields follow. old servers don't send it
+    if let Ok(flags) = reader.u8() {
+        if flags & 0x80 != 0 {
+            info.port = Some(reader.u16()?);
+        }
+        if flags & 0x10 != 0 {
+            reader.u64()?; // the server's steam id
+        }
+        if flags & 0x40 != 0 {
+            reader.u16()?; // the SourceTV port
+            reader.string()?; // the SourceTV name
+        }
+        if flags & 0x20 != 0 {
+            info.keywords = Some(reader.string()?);
+        }
+        // 0x01 is the full game id, which isn't needed
+    }
+
+    Ok(info)
+}
+
+fn parse_players(body: &[u8]) -> Result<Vec<Player>, Error> {
+    let mut reader = Reader::new(body);
+    let count = reader.u8()?;
+
+    let mut players = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        reader.u8()?; // the index, always 0
+        let name = reader.string()?;
+        let score = reader.i32()?;
+        let duration = reader.f32()?;
+        players.push(Player { name, score, duration });
+    }
+
+    Ok(players)
+}
+
+fn parse_rules(body: &[u8]) -> Result<Vec<(String, String)>, Error> {
+    let mut reader = Reader::new(body);
+    let count = reader.u16()?;
+
+    let mut rules = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        // some servers cut the list short instead of splitting it, keep what's there
+        match (reader.string(), reader.string()) {
+            (Ok(name), Ok(value)) => rules.push((name, value)),
+            _ => break,
+        }
+    }
+
+    Ok(rules)
+}
+
+/// Reads little-endian numbers and null-terminated strings out of a packet
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Reader { data, position: 0 }
+    }
+
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8], Error> {
+        if self.position + count > self.data.len() {
+            return Err("the server's response ended too early".into());
+        }
+        let bytes = &self.data[self.position..self.position + count];
+        self.position += count;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, Error> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn i32(&mut self) -> Result<i32, Error> {
+        let bytes = self.bytes(4)?;
+        Ok(i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn f32(&mut self) -> Result<f32, Error> {
+        let bytes = self.bytes(4)?;
+        Ok(f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn u64(&mut self) -> Result<u64, Error> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.bytes(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    /// A null-terminated string. Names can be any bytes, so it's read lossily
+    fn string(&mut self) -> Result<String, Error> {
+        let rest = &self.data[self.position..];
+        let length = rest
+            .iter()
+            .position(|byte| *byte == 0)
+            .ok_or("the server's response has an unterminated string")?;
+        self.position += length + 1;
+        Ok(String::from_utf8_lossy(&rest[..length]).into_owned())
+    }
+
+    fn rest(&mut self) -> &'a [u8] {
+        let rest = &self.data[self.position..];
+        self.position = self.data.len();
+        rest
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::task::JoinHandle;
+
+    const CHALLENGE: [u8; 4] = [0x0A, 0x0B, 0x0C, 0x0D];
+    const TIMEOUT: Duration = Duration::from_millis(500);
+
+    /// A fake game server on localhost. For every step of the script it waits for a request,
+    /// then sends the step's packets. Returns the requests it got once the script is done.
+    async fn fake_server(script: Vec<Vec<Vec<u8>>>) -> (SocketAddr, JoinHandle<Vec<Vec<u8>>>) {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let address = socket.local_addr().unwrap();
+
+        let server = tokio::spawn(async move {
+            let mut requests = Vec::new();
+            for packets in script {
+                let mut buffer = vec![0; MAX_PACKET_SIZE];
+                let (length, client) = socket.recv_from(&mut buffer).await.unwrap();
+                requests.push(buffer[..length].to_vec());
+                for packet in packets {
+                    socket.send_to(&packet, client).await.unwrap();
+                }
+            }
+            requests
+        });
+
+        (address, server)
+    }
+
+    /// A whole response in one packet
+    fn single(kind: u8, body: &[u8]) -> Vec<u8> {
+        let mut packet = SINGLE_PACKET.to_le_bytes().to_vec();
+        packet.push(kind);
+        packet.extend_from_slice(body);
+        packet
+    }
+
+    fn challenge() -> Vec<u8> {
+        single(S2C_CHALLENGE, &CHALLENGE)
+    }
+
+    /// Splits a response into `parts` packets, the way source servers do
+    fn split(response: &[u8], id: i32, parts: usize) -> Vec<Vec<u8>> {
+        let size = (response.len() + parts - 1) / parts;
+        response
+            .chunks(size)
+            .enumerate()
+            .map(|(number, chunk)| {
+                let mut packet = MULTI_PACKET.to_le_bytes().to_vec();
+                packet.extend_from_slice(&id.to_le_bytes());
+                packet.push(parts as u8);
+                packet.push(number as u8);
+                packet.extend_from_slice(&(size as u16).to_le_bytes());
+                packet.extend_from_slice(chunk);
+                packet
+            })
+            .collect()
+    }
+
+    fn push_string(body: &mut Vec<u8>, string: &str) {
+        body.extend_from_slice(string.as_bytes());
+        body.push(0);
+    }
+
+    fn info_body(extra_data: Option<&[u8]>) -> Vec<u8> {
+        let mut body = vec![17]; // the protocol version
+        push_string(&mut body, "Test Server");
+        push_string(&mut body, "de_dust2");
+        push_string(&mut body, "csgo");
+        push_string(&mut body, "Counter-Strike: Global Offensive");
+        body.extend_from_slice(&730u16.to_le_bytes());
+        body.extend_from_slice(&[12, 24, 2, b'd', b'l', 0, 1]); // players, max players, bots, type, os, password, vac
+        push_string(&mut body, "1.38.2.2");
+        if let Some(extra_data) = extra_data {
+            body.extend_from_slice(extra_data);
+        }
+        body
+    }
+
+    fn players_body() -> Vec<u8> {
+        let mut body = vec![2];
+        for (name, score, duration) in &[("alice", 15i32, 754.5f32), ("", -1, 3.0)] {
+            body.push(0);
+            push_string(&mut body, name);
+            body.extend_from_slice(&score.to_le_bytes());
+            body.extend_from_slice(&duration.to_le_bytes());
+        }
+        body
+    }
+
+    fn rules_body(count: u16, rules: &[(&str, &str)]) -> Vec<u8> {
+        let mut body = count.to_le_bytes().to_vec();
+        for (name, value) in rules {
+            push_string(&mut body, name);
+            push_string(&mut body, value);
+        }
+        body
+    }
+
+    #[tokio::test]
+    async fn answers_the_challenge() {
+        let (address, server) = fake_server(vec![vec![challenge()], vec![single(INFO_RESPONSE, &info_body(None))]]).await;
+
+        let info = A2sClient::new(TIMEOUT).info(address).await.unwrap();
+        assert_eq!(info.name, "Test Server");
+
+        let requests = server.await.unwrap();
+        let first = single(A2S_INFO, b"Source Engine Query\0");
+        assert_eq!(requests[0], first);
+        // the second request is the same, with the challenge at the end
+        let mut second = first;
+        second.extend_from_slice(&CHALLENGE);
+        assert_eq!(requests[1], second);
+    }
+
+    #[tokio::test]
+    async fn gets_players_after_the_challenge() {
+        let (address, server) =
+            fake_server(vec![vec![challenge()], vec![single(PLAYER_RESPONSE, &players_body())]]).await;
+
+        let players = A2sClient::new(TIMEOUT).players(address).await.unwrap();
+        assert_eq!(players.len(), 2);
+        assert_eq!(players[0].name, "alice");
+        assert_eq!(players[0].score, 15);
+        assert!((players[0].duration - 754.5).abs() < f32::EPSILON);
+        assert_eq!(players[1].name, ""); // still connecting
+        assert_eq!(players[1].score, -1);
+
+        let requests = server.await.unwrap();
+        // players are asked for with a -1 challenge first
+        assert_eq!(requests[0], single(A2S_PLAYER, &[0xFF; 4]));
+        assert_eq!(requests[1], single(A2S_PLAYER, &CHALLENGE));
+    }
+
+    #[tokio::test]
+    async fn gives_up_on_endless_challenges() {
+        let script = vec![vec![challenge()]; MAX_CHALLENGES + 1];
+        let (address, _server) = fake_server(script).await;
+
+        let error = A2sClient::new(TIMEOUT).info(address).await.unwrap_err();
+        assert!(error.to_string().contains("new challenge"));
+    }
+
+    #[tokio::test]
+    async fn joins_a_split_response() {
+        let rules = rules_body(3, &[("mp_timelimit", "30"), ("sv_cheats", "0"), ("sv_tags", "casual,dust")]);
+        let mut packets = split(&single(RULES_RESPONSE, &rules), 0x1234, 3);
+        packets.swap(0, 2); // udp doesn't keep the order
+        let (address, _server) = fake_server(vec![packets]).await;
+
+        let rules = A2sClient::new(TIMEOUT).rules(address).await.unwrap();
+        assert_eq!(
+            rules,
+            vec![
+                (String::from("mp_timelimit"), String::from("30")),
+                (String::from("sv_cheats"), String::from("0")),
+                (String::from("sv_tags"), String::from("casual,dust")),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn ignores_parts_of_other_split_responses() {
+        let rules = single(RULES_RESPONSE, &rules_body(1, &[("sv_cheats", "0")]));
+        let mut packets = split(&rules, 7, 2);
+        // a leftover part of an older response shows up in the middle
+        packets.insert(1, split(&rules, 6, 2).remove(1));
+        let (address, _server) = fake_server(vec![packets]).await;
+
+        let rules = A2sClient::new(TIMEOUT).rules(address).await.unwrap();
+        assert_eq!(rules, vec![(String::from("sv_cheats"), String::from("0"))]);
+    }
+
+    #[tokio::test]
+    async fn rejects_compressed_responses() {
+        let mut packets = split(&single(RULES_RESPONSE, &rules_body(0, &[])), 0x1234, 1);
+        packets[0][7] |= 0x80; // the top bit of the id
+        let (address, _server) = fake_server(vec![packets]).await;
+
+        let error = A2sClient::new(TIMEOUT).rules(address).await.unwrap_err();
+        assert!(error.to_string().contains("compressed"));
+    }
+
+    #[tokio::test]
+    async fn times_out_when_the_server_is_silent() {
+        // bound, so nothing bounces back, but never answers
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let address = socket.local_addr().unwrap();
+
+        let started = std::time::Instant::now();
+        let error = A2sClient::new(Duration::from_millis(100)).info(address).await.unwrap_err();
+        assert!(error.to_string().contains("didn't answer in time"));
+        assert!(started.elapsed() < TIMEOUT);
+    }
+
+    #[test]
+    fn parses_info_without_extra_data() {
+        let info = parse_info(&info_body(None)).unwrap();
+
+        assert_eq!(info.name, "Test Server");
+        assert_eq!(info.map, "de_dust2");
+        assert_eq!(info.game, "Counter-Strike: Global Offensive");
+        assert_eq!(info.app_id, 730);
+        assert_eq!((info.players, info.max_players, info.bots), (12, 24, 2));
+        assert_eq!(info.server_type, 'd');
+        assert_eq!(info.environment, 'l');
+        assert!(!info.password);
+        assert!(info.vac);
+        assert_eq!(info.version, "1.38.2.2");
+        assert_eq!(info.port, None);
+        assert_eq!(info.keywords, None);
+    }
+
+    #[test]
+    fn parses_the_extra_data_flags() {
+        let mut extra_data = vec![0x80 | 0x10 | 0x40 | 0x20 | 0x01];
+        extra_data.extend_from_slice(&27016u16.to_le_bytes()); // the game port
+        extra_data.extend_from_slice(&90071992547409920u64.to_le_bytes()); // the steam id
+        extra_data.extend_from_slice(&27020u16.to_le_bytes()); // the SourceTV port
+        push_string(&mut extra_data, "SourceTV");
+        push_string(&mut extra_data, "casual,dust2");
+        extra_data.extend_from_slice(&730u64.to_le_bytes()); // the game id
+        let info = parse_info(&info_body(Some(&extra_data))).unwrap();
+
+        assert_eq!(info.port, Some(27016));
+        assert_eq!(info.keywords.as_deref(), Some("casual,dust2"));
+
+        // only the keywords
+        let mut extra_data = vec![0x20];
+        push_string(&mut extra_data, "hardcore");
+        let info = parse_info(&info_body(Some(&extra_data))).unwrap();
+
+        assert_eq!(info.port, None);
+        assert_eq!(info.keywords.as_deref(), Some("hardcore"));
+    }
+
+    #[test]
+    fn rejects_cut_off_info() {
+        let body = info_body(None);
+        assert!(parse_info(&body[..body.len() - 4]).is_err());
+    }
+
+    #[test]
+    fn parses_players() {
+        let players = parse_players(&players_body()).unwrap();
+        assert_eq!(players.len(), 2);
+        assert_eq!(players[0].name, "alice");
+
+        assert!(parse_players(&[0]).unwrap().is_empty());
+        // says there are 2 players but only has one
+        let body = players_body();
+        assert!(parse_players(&body[..body.len() - 8]).is_err());
+    }
+
+    #[test]
+    fn keeps_the_rules_that_fit() {
+        // says there are 3 rules, but the packet only had room for 2 and a half
+        let mut body = rules_body(3, &[("sv_cheats", "0"), ("mp_timelimit", "30")]);
+        body.extend_from_slice(b"sv_ta");
+
+        let rules = parse_rules(&body).unwrap();
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[1], (String::from("mp_timelimit"), String::from("30")));
+    }
+}
@@ -0,0 +1,2 @@
+mod a2s; // the A2S query protocol
+pub mod server; // export the server command, the servers command and its subcommands, and the status updates
@@ -0,0 +1,647 @@
+use crate::{Context, Error};
+use std::{
+    collections::{BTreeMap, HashMap},
+    env,
+    net::SocketAddr,
+    sync::Arc,
+    time::Duration,
+};
+
+use log::error;
+use poise::serenity_prelude as serenity;
+use serde::{Deserialize, Serialize};
+
+use super::a2s::{A2sClient, Player, ServerInfo};
+use crate::helpers::{is_public, truncate, Store};
+
+const DEFAULT_PORT: u16 = 27015; // the usual query port of source servers
+const QUERY_TIMEOUT: Duration = Duration::from_secs(3);
+const LOOKUP_TIMEOUT: Duration = Duration::from_secs(3);
+const MAX_ALIASES: usize = 25; // per server
+const MAX_ALIAS_LENGTH: usize = 32;
+const MAX_STATUS_MESSAGES: usize = 5; // per server, every one of them is queried on every update
+const MAX_EDIT_FAILURES: u32 = 5; // a status message that can't be edited this many times in a row was deleted
+const DEFAULT_UPDATE_INTERVAL: u64 = 60; // seconds between status updates, unless SERVER_STATUS_INTERVAL says otherwise
+const MIN_UPDATE_INTERVAL: u64 = 30;
+const MAX_PLAYER_LIST_LENGTH: usize = 950; // embed fields can only be 1024 characters long
+const MAX_FIELD_LENGTH: usize = 1024;
+const MAX_TITLE_LENGTH: usize = 256;
+const MAX_VERSION_LENGTH: usize = 64; // it's in the footer, which has room for 2048
+
+/// The saved game servers and status messages of every Discord server, saved in servers.json
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ServerSettings {
+    pub guilds: HashMap<u64, GuildServers>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct GuildServers {
+    #[serde(default)]
+    pub aliases: BTreeMap<String, String>, // addresses, keyed by their lowercase name
+    #[serde(default)]
+    pub statuses: Vec<StatusMessage>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusMessage {
+    pub channel_id: u64,
+    pub message_id: u64,
+    pub label: String,   // the name or address it was made with
+    pub address: String, // looked up again on every update, in case it points somewhere else now
+    #[serde(default)]
+    pub failures: u32, // how many updates in a row couldn't edit the message
+}
+
+/// Everything a query found out about a game server
+pub struct ServerStatus {
+    address: SocketAddr,
+    info: ServerInfo,
+    players: Option<Vec<Player>>, // None if the server doesn't share its players
+    rules: Option<usize>,         // the number of rules, None if the server doesn't share them
+}
+
+/// Look up a Source engine game server
+///
+/// Shows the map, players, VAC status and more, straight from the server.
+///
+/// **Usage:**
+/// `/server <address or saved name>`
+/// The port is 27015 if it's left out.
+///
+/// **Examples:**
+/// `/server 192.0.2.10:27015`
+/// `/server community`
+#[poise::command(
+    prefix_command,
+    slash_command,
+    track_edits,
+    defer_response,
+    broadcast_typing
+)]
+pub async fn server(
+    ctx: Context<'_>,
+    #[description = "An address like 1.2.3.4:27015, or a name saved with /servers save"]
+    #[rest]
+    server: String,
+) -> Result<(), Error> {
+    let (label, address) = find_server(ctx, &server).await;
+
+    let address = match resolve_address(&address).await {
+        Ok(address) => address,
+        Err(reason) => {
+            poise::say_reply(ctx, reason).await?;
+            return Ok(());
+        }
+    };
+    let status = match query_server(address).await {
+        Ok(status) => status,
+        Err(why) => {
+            poise::say_reply(ctx, format!("🔴 **{}** didn't answer: {}", label, why)).await?;
+            return Ok(());
+        }
+    };
+
+    let bot = ctx.discord().cache.current_user();
+    poise::send_reply(ctx, |message| {
+        message.embed(|embed| {
+            server_embed(embed, &bot, &label, &Ok(status));
+            embed.author(|author| {
+                if let Some(icon_url) = ctx.author().avatar_url() {
+                    author.icon_url(icon_url);
+                } else {
+                    author.icon_url(ctx.author().default_avatar_url());
+                }
+                author.name(&ctx.author().name);
+                author
+            })
+        })
+    })
+    .await?;
+
+    Ok(())
+}
+
+/// Save game servers under a name and keep their status up to date
+///
+/// **Subcommands**
+///
+/// **save** `/servers save <name> <address>` (admins only)
+/// **forget** `/servers forget <name>` (admins only)
+/// **saved** `/servers saved` lists the saved servers
+/// Saved servers can be looked up by name with `/server <name>`.
+///
+/// **status** `/servers status <address or saved name>` (admins only)
+/// Posts a status message that keeps itself up to date. Delete the message to stop it.
+#[poise::command(prefix_command, slash_command)]
+pub async fn servers(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Save a game server under a name (admins only)
+#[poise::command(prefix_command, slash_command, required_permissions = "MANAGE_GUILD")]
+pub async fn save(
+    ctx: Context<'_>,
+    #[description = "The name to look it up with"] name: String,
+    #[description = "The address, like 1.2.3.4:27015"] address: String,
+) -> Result<(), Error> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id.0,
+        None => {
+            poise::say_reply(ctx, "This only works in a server.").await?;
+            return Ok(());
+        }
+    };
+
+    let name = name.trim().to_lowercase();
+    let valid_name = !name.is_empty()
+        && name.len() <= MAX_ALIAS_LENGTH
+        && name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_');
+    if !valid_name {
+        poise::say_reply(
+            ctx,
+            format!("Names can only have letters, numbers, - and _, and be up to {} characters long.", MAX_ALIAS_LENGTH),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    // don't save anything that can't be queried
+    let address = address.trim().to_string();
+    if let Err(reason) = resolve_address(&address).await {
+        poise::say_reply(ctx, reason).await?;
+        return Ok(());
+    }
+
+    let saved = ctx
+        .data()
+        .servers
+        .update(|settings| {
+            let aliases = &mut settings.guilds.entry(guild_id).or_default().aliases;
+            if !aliases.contains_key(&name) && aliases.len() >= MAX_ALIASES {
+                return false;
+            }
+            aliases.insert(name.clone(), address.clone());
+            true
+        })
+        .await?;
+
+    if saved {
+        poise::say_reply(ctx, format!("Saved `{}` as **{}**. Look it up with `/server {}`.", address, name, name)).await?;
+    } else {
+        poise::say_reply(
+            ctx,
+            format!("This server can only save {} game servers. Remove some with `/servers forget`.", MAX_ALIASES),
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Remove a saved game server (admins only)
+#[poise::command(prefix_command, slash_command, required_permissions = "MANAGE_GUILD")]
+pub async fn forget(
+    ctx: Context<'_>,
+    #[description = "The name it was saved as"] name: String,
+) -> Result<(), Error> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id.0,
+        None => {
+            poise::say_reply(ctx, "This only works in a server.").await?;
+            return Ok(());
+        }
+    };
+
+    let name = name.trim().to_lowercase();
+    let removed = ctx
+        .data()
+        .servers
+        .update(|settings| {
+            settings
+                .guilds
+                .get_mut(&guild_id)
+                .and_then(|guild| guild.aliases.remove(&name))
+        })
+        .await?;
+
+    match removed {
+        Some(address) => poise::say_reply(ctx, format!("Forgot **{}** (`{}`).", name, address)).await?,
+        None => poise::say_reply(ctx, format!("There's no game server saved as \"{}\".", name)).await?,
+    };
+
+    Ok(())
+}
+
+/// Show the game servers saved in this server
+#[poise::command(prefix_command, slash_command)]
+pub async fn saved(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id.0,
+        None => {
+            poise::say_reply(ctx, "This only works in a server.").await?;
+            return Ok(());
+        }
+    };
+
+    let aliases = ctx
+        .data()
+        .servers
+        .read()
+        .await
+        .guilds
+        .get(&guild_id)
+        .map(|guild| guild.aliases.clone())
+        .unwrap_or_default();
+    if aliases.is_empty() {
+        poise::say_reply(ctx, "No game servers saved yet. Admins can save one with `/servers save`.").await?;
+        return Ok(());
+    }
+
+    let lines = aliases
+        .iter()
+        .map(|(name, address)| format!("**{}** — `{}`", name, address))
+        .collect::<Vec<String>>();
+
+    poise::send_reply(ctx, |message| {
+        message.embed(|embed| {
+            embed.title("Saved game servers");
+            embed.description(lines.join("\n"));
+
+            embed.footer(|footer| {
+                if let Some(icon_url) = &ctx.discord().cache.current_user().avatar_url() {
+                    footer.icon_url(icon_url);
+                } else {
+                    footer.icon_url(ctx.discord().cache.current_user().default_avatar_url());
+                }
+                footer.text(format!("{} | Game servers", ctx.discord().cache.current_user().name));
+                footer
+            });
+
+            embed.timestamp(chrono::Utc::now());
+
+            embed
+        })
+    })
+    .await?;
+
+    Ok(())
+}
+
+/// Post a game server status message that keeps itself up to date (admins only)
+///
+/// Delete the message to stop updating it.
+#[poise::command(prefix_command, slash_command, defer_response, required_permissions = "MANAGE_GUILD")]
+pub async fn status(
+    ctx: Context<'_>,
+    #[description = "An address like 1.2.3.4:27015, or a name saved with /servers save"]
+    #[rest]
+    server: String,
+) -> Result<(), Error> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id.0,
+        None => {
+            poise::say_reply(ctx, "This only works in a server.").await?;
+            return Ok(());
+        }
+    };
+
+    let status_count = ctx
+        .data()
+        .servers
+        .read()
+        .await
+        .guilds
+        .get(&guild_id)
+        .map_or(0, |guild| guild.statuses.len());
+    if status_count >= MAX_STATUS_MESSAGES {
+        poise::say_reply(
+            ctx,
+            format!(
+                "This server already has {} status messages. Delete one to make a new one.",
+                MAX_STATUS_MESSAGES
+            ),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let (label, address) = find_server(ctx, &server).await;
+    let result = match resolve_address(&address).await {
+        Ok(resolved) => query_server(resolved).await.map_err(|why| why.to_string()),
+        Err(reason) => {
+            poise::say_reply(ctx, reason).await?;
+            return Ok(());
+        }
+    };
+
+    let bot = ctx.discord().cache.current_user();
+    let reply = poise::send_reply(ctx, |message| {
+        message.embed(|embed| server_embed(embed, &bot, &label, &result))
+    })
+    .await?;
+    let message = reply.message().await?;
+
+    let status = StatusMessage {
+        channel_id: message.channel_id.0,
+        message_id: message.id.0,
+        label,
+        address,
+        failures: 0,
+    };
+    // checked again, another /servers status could have made one in the meantime
+    let saved = ctx
+        .data()
+        .servers
+        .update_if(|settings| {
+            let statuses = &mut settings.guilds.entry(guild_id).or_default().statuses;
+            if statuses.len() >= MAX_STATUS_MESSAGES {
+                return (false, false);
+            }
+            statuses.push(status);
+            (true, true)
+        })
+        .await?;
+    if !saved {
+        message.delete(ctx.discord()).await?;
+        poise::say_reply(
+            ctx,
+            format!(
+                "This server already has {} status messages. Delete one to make a new one.",
+                MAX_STATUS_MESSAGES
+            ),
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Starts keeping every status message up to date in the background,
+/// every SERVER_STATUS_INTERVAL seconds (60 by default)
+pub fn start_status_updates(ctx: serenity::Context, servers: Arc<Store<ServerSettings>>) {
+    let seconds = env::var("SERVER_STATUS_INTERVAL")
+        .ok()
+        .and_then(|seconds| seconds.parse::<u64>().ok())
+        .unwrap_or(DEFAULT_UPDATE_INTERVAL)
+        .max(MIN_UPDATE_INTERVAL);
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(seconds));
+        loop {
+            interval.tick().await;
+            if let Err(why) = update_statuses(&ctx, &servers).await {
+                error!("Couldn't update the game server status messages: {}", why);
+            }
+        }
+    });
+}
+
+async fn update_statuses(ctx: &serenity::Context, servers: &Store<ServerSettings>) -> Result<(), Error> {
+    let statuses = servers
+        .read()
+        .await
+        .guilds
+        .values()
+        .flat_map(|guild| guild.statuses.iter().cloned())
+        .collect::<Vec<StatusMessage>>();
+    if statuses.is_empty() {
+        return Ok(());
+    }
+
+    let bot = ctx.cache.current_user();
+    let mut edited = HashMap::new(); // whether each message could be edited, keyed by message id
+    for status in statuses {
+        let result = match resolve_address(&status.address).await {
+            Ok(address) => query_server(address).await.map_err(|why| why.to_string()),
+            Err(reason) => Err(reason),
+        };
+
+        let edit = serenity::ChannelId(status.channel_id)
+            .edit_message(ctx, status.message_id, |message| {
+                message.embed(|embed| server_embed(embed, &bot, &status.label, &result))
+            })
+            .await;
+        edited.insert(status.message_id, edit.is_ok());
+    }
+
+    servers
+        .update(|settings| {
+            for guild in settings.guilds.values_mut() {
+                for status in &mut guild.statuses {
+                    match edited.get(&status.message_id) {
+                        Some(true) => status.failures = 0,
+                        Some(false) => status.failures += 1,
+                        None => (), // made during this update
+                    }
+                }
+                guild.statuses.retain(|status| status.failures < MAX_EDIT_FAILURES);
+            }
+        })
+        .await?;
+
+    Ok(())
+}
+
+/// Finds the address of a saved server. Anything that isn't a saved name is used as the address.
+/// Returns the name to show for the server and its address.
+async fn find_server(ctx: Context<'_>, server: &str) -> (String, String) {
+    let server = server.trim();
+
+    if let Some(guild_id) = ctx.guild_id() {
+        let name = server.to_lowercase();
+        let settings = ctx.data().servers.read().await;
+        if let Some(address) = settings.guilds.get(&guild_id.0).and_then(|guild| guild.aliases.get(&name)) {
+            return (name, address.clone());
+        }
+    }
+
+    (server.to_string(), server.to_string())
+}
+
+/// Looks up the address of a game server.
+/// Returns the reason to show the user if it can't be found or isn't on the internet.
+async fn resolve_address(address: &str) -> Result<SocketAddr, String> {
+    let (host, port) = split_address(address)
+        .ok_or_else(|| format!("\"{}\" isn't a server address. Try something like 1.2.3.4:27015.", address))?;
+
+    let lookup = tokio::net::lookup_host((host.as_str(), port));
+    let addresses = tokio::time::timeout(LOOKUP_TIMEOUT, lookup)
+        .await
+        .map_err(|_| format!("Looking up \"{}\" took too long.", host))?
+        .map_err(|_| format!("Couldn't find a server called \"{}\".", host))?
+        .collect::<Vec<SocketAddr>>();
+
+    // the bot shouldn't be used to poke at its own network
+    if addresses.iter().any(|address| !is_public(address.ip())) {
+        return Err(String::from("That address isn't on the internet."));
+    }
+
+    addresses
+        .into_iter()
+        .next()
+        .ok_or_else(|| format!("Couldn't find a server called \"{}\".", host))
+}
+
+/// Splits "host:port", "[ipv6]:port" or just a host (with the default port) into the host and port
+fn split_address(address: &str) -> Option<(String, u16)> {
+    let address = address.trim();
+
+    let (host, port) = if let Some(rest) = address.strip_prefix('[') {
+        let end = rest.find(']')?;
+        let port = match rest[end + 1..].strip_prefix(':') {
+            Some(port) => port.parse().ok()?,
+            None if rest.len() == end + 1 => DEFAULT_PORT,
+            None => return None,
+        };
+        (&rest[..end], port)
+    } else {
+        match address.rfind(':') {
+            // more than one colon is an ipv6 address without a port
+            Some(colon) if address[..colon].contains(':') => (address, DEFAULT_PORT),
+            Some(colon) => (&address[..colon], address[colon + 1..].parse().ok()?),
+            None => (address, DEFAULT_PORT),
+        }
+    };
+
+    if host.is_empty() || host.contains(char::is_whitespace) {
+        return None;
+    }
+    Some((host.to_string(), port))
+}
+
+/// Asks a game server for its info, players and rules at the same time.
+/// Only the info is needed, many servers don't share their players or rules.
+async fn query_server(address: SocketAddr) -> Result<ServerStatus, Error> {
+    let client = A2sClient::new(QUERY_TIMEOUT);
+    let (info, players, rules) = tokio::join!(client.info(address), client.players(address), client.rules(address));
+
+    Ok(ServerStatus {
+        address,
+        info: info?,
+        players: players.ok(),
+        rules: rules.ok().map(|rules| rules.len()),
+    })
+}
+
+/// Fills an embed with the status of a game server, or the reason it couldn't be reached
+fn server_embed<'a>(
+    embed: &'a mut serenity::CreateEmbed,
+    bot: &serenity::CurrentUser,
+    label: &str,
+    status: &Result<ServerStatus, String>,
+) -> &'a mut serenity::CreateEmbed {
+    match status {
+        Ok(status) => {
+            let info = &status.info;
+            // everything the server says about itself is capped, or one bad server would break its status message
+            embed.title(truncate(&info.name, MAX_TITLE_LENGTH));
+            embed.colour(serenity::Colour::from_rgb(67, 181, 129));
+
+            let port = info.port.unwrap_or_else(|| status.address.port());
+            embed.description(format!("🟢 Online\n`connect {}:{}`", status.address.ip(), port));
+
+            embed.field("Map", truncate(&info.map, MAX_FIELD_LENGTH), true);
+            let mut players = format!("{}/{}", info.players, info.max_players);
+            if info.bots > 0 {
+                players.push_str(&format!(" ({} bots)", info.bots));
+            }
+            embed.field("Players", players, true);
+            embed.field("Game", truncate(&info.game, MAX_FIELD_LENGTH), true);
+
+            embed.field("VAC", if info.vac { "🛡️ Secured" } else { "Not secured" }, true);
+            embed.field("Password", if info.password { "🔒 Yes" } else { "No" }, true);
+            let server_type = match info.server_type {
+                'd' => "Dedicated",
+                'l' => "Listen",
+                'p' => "SourceTV",
+                _ => "Unknown",
+            };
+            let environment = match info.environment {
+                'l' => "Linux",
+                'w' => "Windows",
+                'm' | 'o' => "macOS",
+                _ => "Unknown",
+            };
+            embed.field("Server", format!("{}, {}", server_type, environment), true);
+
+            match &status.players {
+                Some(players) if !players.is_empty() => {
+                    embed.field("Player list", player_list(players), false);
+                }
+                Some(_) => (),
+                None => {
+                    embed.field("Player list", "Unavailable", false);
+                }
+            }
+
+            if let Some(keywords) = info.keywords.as_ref().filter(|keywords| !keywords.is_empty()) {
+                embed.field("Tags", truncate(keywords, MAX_FIELD_LENGTH), false);
+            }
+
+            let rules = match status.rules {
+                Some(rules) => format!("{} rules", rules),
+                None => String::from("rules unavailable"),
+            };
+            embed.footer(|footer| {
+                footer.icon_url(bot.avatar_url().unwrap_or_else(|| bot.default_avatar_url()));
+                footer.text(format!(
+                    "{} | Version {} | {} | App {}",
+                    bot.name,
+                    truncate(&info.version, MAX_VERSION_LENGTH),
+                    rules,
+                    info.app_id
+                ));
+                footer
+            });
+        }
+        Err(why) => {
+            embed.title(label);
+            embed.colour(serenity::Colour::from_rgb(240, 71, 71));
+            embed.description(format!("🔴 Offline\n{}", why));
+
+            embed.footer(|footer| {
+                footer.icon_url(bot.avatar_url().unwrap_or_else(|| bot.default_avatar_url()));
+                footer.text(format!("{} | Game servers", bot.name));
+                footer
+            });
+        }
+    }
+
+    embed.timestamp(chrono::Utc::now());
+
+    embed
+}
+
+/// The players with the highest score first, e.g. "`  12` **name** — 1h 05m"
+fn player_list(players: &[Player]) -> String {
+    let mut players = players.iter().collect::<Vec<&Player>>();
+    players.sort_by(|a, b| b.score.cmp(&a.score));
+
+    let mut list = String::new();
+    for (shown, player) in players.iter().enumerate() {
+        let name = if player.name.is_empty() {
+            "*connecting*"
+        } else {
+            player.name.as_str()
+        };
+        let line = format!("`{:>4}` **{}** — {}\n", player.score, name, format_duration(player.duration));
+
+        if list.len() + line.len() > MAX_PLAYER_LIST_LENGTH {
+            list.push_str(&format!("...and {} more", players.len() - shown));
+            break;
+        }
+        list.push_str(&line);
+    }
+
+    list
+}
+
+/// e.g. "1h 05m" or "12m"
+fn format_duration(seconds: f32) -> String {
+    let minutes = (seconds.max(0.0) / 60.0) as u64;
+    if minutes >= 60 {
+        format!("{}h {:02}m", minutes / 60, minutes % 60)
+    } else {
+        format!("{}m", minutes)
+    }
+}
@@ -0,0 +1,287 @@
+use crate::{Context, Error};
+use std::collections::HashMap;
+
+use poise::serenity_prelude as serenity;
+
+use super::api::{achievement_schema, api_key, player_achievements, player_summary, PlayerSummary};
+use super::apps::achievement_percentages;
+use super::game::app_id_for;
+use super::links::steam_id_for;
+use crate::helpers::{check_quota, paginate, truncate, Api};
+
+const OVERVIEW_ACHIEVEMENTS: usize = 5; // how many recent and rare achievements to show
+const LOCKED_PER_PAGE: usize = 10;
+const MAX_DESCRIPTION_LENGTH: usize = 100; // so a page of locked achievements fits in one embed
+const PROGRESS_BAR_LENGTH: usize = 10;
+
+/// Show a steam user's achievement progress in a game
+///
+/// The first page has the completion, the latest unlocks and the rarest unlocks.
+/// The other pages list the locked achievements, the most common ones first.
+///
+/// **Usage:**
+/// `/steam achievements <user> <name or app id>`
+///
+/// **Examples:**
+/// `/steam achievements robinwalker portal 2`
+/// `/steam achievements @someone 620`
+#[poise::command(
+    prefix_command,
+    slash_command,
+    track_edits,
+    defer_response,
+    broadcast_typing
+)]
+pub async fn achievements(
+    ctx: Context<'_>,
+    #[description = "A profile url, steam id, vanity name or mention"] user: String,
+    #[description = "The name or app id of the game"]
+    #[rest]
+    game: String,
+) -> Result<(), Error> {
+    let api_key = api_key();
+
+    let steam_id = match steam_id_for(ctx, &api_key, Some(&user)).await? {
+        Some(steam_id) => steam_id,
+        None => return Ok(()),
+    };
+    let app_id = match app_id_for(ctx, &game).await? {
+        Some(app_id) => app_id,
+        None => return Ok(()),
+    };
+
+    // the profile, the unlocked achievements and the achievement names
+    if !check_quota(ctx, Api::Steam, 3).await? {
+        return Ok(());
+    }
+
+    let (player, unlocked, schema, percentages) = tokio::join!(
+        player_summary(&api_key, &steam_id),
+        player_achievements(&api_key, &steam_id, app_id),
+        achievement_schema(&api_key, app_id),
+        achievement_percentages(app_id)
+    );
+    let player = match player? {
+        Some(player) => player,
+        None => {
+            poise::say_reply(ctx, format!("Steam user \"{}\" not found.", steam_id)).await?;
+            return Ok(());
+        }
+    };
+    let schema = schema?;
+    let game_name = if schema.game_name.is_empty() {
+        format!("App {}", app_id)
+    } else {
+        schema.game_name.clone()
+    };
+
+    let unlocked = match unlocked? {
+        Ok(unlocked) if !unlocked.is_empty() => unlocked,
+        Ok(_) => {
+            poise::say_reply(ctx, format!("**{}** doesn't have achievements.", game_name)).await?;
+            return Ok(());
+        }
+        Err(reason) => {
+            let reason = match reason.as_str() {
+                "Profile is not public" => format!("{}'s game details are private.", player.persona_name),
+                "Requested app has no stats" => format!("**{}** doesn't have achievements.", game_name),
+                _ => format!("Steam wouldn't say: {}", reason),
+            };
+            poise::say_reply(ctx, reason).await?;
+            return Ok(());
+        }
+    };
+    // the global percentages are a nice-to-have, don't fail the whole command over them
+    let percentages = percentages.unwrap_or_default();
+
+    // put the names, unlock times and percentages of every achievement together
+    let unlock_times = unlocked
+        .iter()
+        .map(|achievement| {
+            let unlocked_at = if achievement.achieved == 1 {
+                Some(achievement.unlock_time)
+            } else {
+                None
+            };
+            (achievement.api_name.as_str(), unlocked_at)
+        })
+        .collect::<HashMap<&str, Option<i64>>>();
+    let achievements = schema
+        .achievements
+        .iter()
+        .filter_map(|schema| {
+            let unlocked_at = *unlock_times.get(schema.name.as_str())?;
+            let description = match &schema.description {
+                Some(description) if !description.is_empty() => truncate(description, MAX_DESCRIPTION_LENGTH),
+                _ if schema.hidden == 1 => String::from("*Hidden achievement*"),
+                _ => String::new(),
+            };
+            Some(Achievement {
+                name: schema.display_name.clone(),
+                description,
+                unlocked_at,
+                percent: percentages.get(&schema.name).copied(),
+            })
+        })
+        .collect::<Vec<Achievement>>();
+
+    let (mut unlocked, mut locked): (Vec<Achievement>, Vec<Achievement>) =
+        achievements.into_iter().partition(|achievement| achievement.unlocked_at.is_some());
+    let total = unlocked.len() + locked.len();
+
+    // latest first
+    unlocked.sort_by(|a, b| b.unlocked_at.cmp(&a.unlocked_at));
+    let recent = unlocked.iter().take(OVERVIEW_ACHIEVEMENTS).cloned().collect::<Vec<Achievement>>();
+
+    // rarest first
+    let mut rarest = unlocked
+        .iter()
+        .filter(|achievement| achievement.percent.is_some())
+        .cloned()
+        .collect::<Vec<Achievement>>();
+    rarest.sort_by(|a, b| a.percent.partial_cmp(&b.percent).unwrap_or(std::cmp::Ordering::Equal));
+    rarest.truncate(OVERVIEW_ACHIEVEMENTS);
+
+    // the easiest to get first
+    locked.sort_by(|a, b| b.percent.partial_cmp(&a.percent).unwrap_or(std::cmp::Ordering::Equal));
+
+    let progress = Progress {
+        game_name,
+        app_id,
+        unlocked: unlocked.len(),
+        total,
+        recent,
+        rarest,
+        locked,
+    };
+    // the overview, then the locked achievements
+    let pages = 1 + (progress.locked.len() + LOCKED_PER_PAGE - 1) / LOCKED_PER_PAGE;
+    paginate(ctx, pages, |embed, page| achievements_embed(ctx, embed, &player, &progress, page, pages)).await?;
+
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+struct Achievement {
+    name: String,
+    description: String,
+    unlocked_at: Option<i64>, // unix timestamp, None if it's locked
+    percent: Option<f64>,     // of all players who unlocked it
+}
+
+struct Progress {
+    game_name: String,
+    app_id: u32,
+    unlocked: usize,
+    total: usize,
+    recent: Vec<Achievement>,
+    rarest: Vec<Achievement>,
+    locked: Vec<Achievement>,
+}
+
+/// Fills an embed with the overview on the first page, or a page of locked achievements
+fn achievements_embed<'a>(
+    ctx: Context<'_>,
+    embed: &'a mut serenity::CreateEmbed,
+    player: &PlayerSummary,
+    progress: &Progress,
+    page: usize,
+    pages: usize,
+) -> &'a mut serenity::CreateEmbed {
+    embed
+        .title(format!("{}'s achievements in {}", player.persona_name, progress.game_name))
+        .url(format!("{}stats/{}/achievements", player.profile_url, progress.app_id));
+    embed.thumbnail(&player.avatar);
+    embed.colour(serenity::Colour::from_rgb(0, 0, 0));
+    embed.author(|author| {
+        if let Some(icon_url) = ctx.author().avatar_url() {
+            author.icon_url(icon_url);
+        } else {
+            author.icon_url(ctx.author().default_avatar_url());
+        }
+        author.name(&ctx.author().name);
+        author
+    });
+
+    let completion = progress.unlocked as f64 / progress.total.max(1) as f64;
+    let filled = (completion * PROGRESS_BAR_LENGTH as f64).round() as usize;
+    let progress_bar = format!("{}{}", "▰".repeat(filled), "▱".repeat(PROGRESS_BAR_LENGTH - filled));
+    let summary = format!(
+        "{} **{}/{}** unlocked ({:.1}%)",
+        progress_bar,
+        progress.unlocked,
+        progress.total,
+        completion * 100.0
+    );
+
+    if page == 0 {
+        embed.description(summary);
+
+        if !progress.recent.is_empty() {
+            let recent = progress
+                .recent
+                .iter()
+                .map(|achievement| {
+                    format!("**{}** — <t:{}:R>", achievement.name, achievement.unlocked_at.unwrap_or_default())
+                })
+                .collect::<Vec<String>>();
+            embed.field("Recently unlocked", recent.join("\n"), false);
+        }
+
+        if !progress.rarest.is_empty() {
+            let rarest = progress
+                .rarest
+                .iter()
+                .map(|achievement| format!("**{}** — {}", achievement.name, format_percent(achievement.percent)))
+                .collect::<Vec<String>>();
+            embed.field("Rarest unlocked", rarest.join("\n"), false);
+        }
+
+        if progress.locked.is_empty() {
+            embed.field("Locked", "Nothing left, 100% complete! 🏆", false);
+        }
+    } else {
+        let first = (page - 1) * LOCKED_PER_PAGE;
+        let locked = progress
+            .locked
+            .iter()
+            .skip(first)
+            .take(LOCKED_PER_PAGE)
+            .map(|achievement| {
+                let mut line = format!("🔒 **{}** — {}", achievement.name, format_percent(achievement.percent));
+                if !achievement.description.is_empty() {
+                    line.push_str(&format!("\n{}", achievement.description));
+                }
+                line
+            })
+            .collect::<Vec<String>>();
+        embed.description(format!("{}\n\n{}", summary, locked.join("\n")));
+    }
+
+    embed.footer(|footer| {
+        if let Some(icon_url) = &ctx.discord().cache.current_user().avatar_url() {
+            footer.icon_url(icon_url);
+        } else {
+            footer.icon_url(ctx.discord().cache.current_user().default_avatar_url());
+        }
+        footer.text(format!(
+            "{} | Steam | Page {}/{}",
+            ctx.discord().cache.current_user().name,
+            page + 1,
+            pages
+        ));
+        footer
+    });
+
+    embed.timestamp(chrono::Utc::now());
+
+    embed
+}
+
+/// e.g. "3.2% of players", or "unknown rarity" when steam doesn't know
+fn format_percent(percent: Option<f64>) -> String {
+    match percent {
+        Some(percent) => format!("{:.1}% of players", percent),
+        None => String::from("unknown rarity"),
+    }
+}
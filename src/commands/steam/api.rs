@@ -0,0 +1,403 @@
+use crate::Error;
+use std::env;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use super::steam_id::Identifier;
+
+const API_URL: &str = "https://api.steampowered.com";
+
+// how long each endpoint gets before we give up on it
+const VANITY_TIMEOUT: Duration = Duration::from_secs(5);
+const SUMMARY_TIMEOUT: Duration = Duration::from_secs(5);
+const LEVEL_TIMEOUT: Duration = Duration::from_secs(5);
+const BANS_TIMEOUT: Duration = Duration::from_secs(5);
+const OWNED_GAMES_TIMEOUT: Duration = Duration::from_secs(10); // big libraries take a while
+const RECENT_GAMES_TIMEOUT: Duration = Duration::from_secs(5);
+const ACHIEVEMENTS_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The steam web api key, from the STEAM_API_KEY environment variable
+pub fn api_key() -> String {
+    env::var("STEAM_API_KEY").expect("Expected STEAM_API_KEY environment variable.")
+}
+
+/// Gets the SteamID64 of the user with a vanity name (steamcommunity.com/id/<vanity name>)
+pub async fn resolve_vanity(api_key: &str, vanity: &str) -> Result<Option<String>, Error> {
+    Ok(reqwest::Client::new()
+        .get(format!("{}/ISteamUser/ResolveVanityURL/v0001/", API_URL))
+        .query(&[("key", api_key), ("vanityurl", vanity)])
+        .timeout(VANITY_TIMEOUT)
+        .send()
+        .await?
+        .json::<SteamIDResponse>()
+        .await?
+        .response
+        .steam_id)
+}
+
+/// Gets the SteamID64 of an account given in any format (see `parse_identifier`)
+pub async fn resolve_identifier(api_key: &str, identifier: &Identifier) -> Result<Option<String>, Error> {
+    match identifier {
+        Identifier::SteamId(steam_id) => Ok(Some(steam_id.to_string())),
+        Identifier::Vanity(vanity) => resolve_vanity(api_key, vanity).await,
+    }
+}
+
+/// Gets the profile of a user. Returns `None` if there's no user with that id.
+pub async fn player_summary(api_key: &str, steam_id: &str) -> Result<Option<PlayerSummary>, Error> {
+    Ok(reqwest::Client::new()
+        .get(format!("{}/ISteamUser/GetPlayerSummaries/v0002/", API_URL))
+        .query(&[("key", api_key), ("steamids", steam_id)])
+        .timeout(SUMMARY_TIMEOUT)
+        .send()
+        .await?
+        .json::<PlayerSummaryResponse>()
+        .await?
+        .response
+        .players
+        .into_iter()
+        .next())
+}
+
+/// Gets the profiles of up to 100 users at once. Users that don't exist are left out.
+pub async fn player_summaries(api_key: &str, steam_ids: &[String]) -> Result<Vec<PlayerSummary>, Error> {
+    Ok(reqwest::Client::new()
+        .get(format!("{}/ISteamUser/GetPlayerSummaries/v0002/", API_URL))
+        .query(&[("key", api_key), ("steamids", steam_ids.join(",").as_str())])
+        .timeout(SUMMARY_TIMEOUT)
+        .send()
+        .await?
+        .json::<PlayerSummaryResponse>()
+        .await?
+        .response
+        .players)
+}
+
+/// Gets every game a user owns, with names and playtimes.
+/// Returns `None` if the user's game details are private.
+pub async fn owned_games(api_key: &str, steam_id: &str) -> Result<Option<Vec<OwnedGame>>, Error> {
+    let response = reqwest::Client::new()
+        .get(format!("{}/IPlayerService/GetOwnedGames/v0001/", API_URL))
+        .query(&[
+            ("key", api_key),
+            ("steamid", steam_id),
+            ("include_appinfo", "1"),
+            ("include_played_free_games", "1"),
+            ("format", "json"),
+        ])
+        .timeout(OWNED_GAMES_TIMEOUT)
+        .send()
+        .await?
+        .json::<OwnedGamesResponse>()
+        .await?
+        .response;
+
+    // private profiles get an empty response instead of an empty list
+    Ok(response.game_count.map(|_| response.games))
+}
+
+/// Gets how many games a user owns, without the game details.
+/// Returns `None` if the user's game details are private.
+pub async fn owned_game_count(api_key: &str, steam_id: &str) -> Result<Option<u32>, Error> {
+    Ok(reqwest::Client::new()
+        .get(format!("{}/IPlayerService/GetOwnedGames/v0001/", API_URL))
+        .query(&[
+            ("key", api_key),
+            ("steamid", steam_id),
+            ("include_played_free_games", "1"),
+            ("format", "json"),
+        ])
+        .timeout(OWNED_GAMES_TIMEOUT)
+        .send()
+        .await?
+        .json::<OwnedGamesResponse>()
+        .await?
+        .response
+        .game_count)
+}
+
+/// Gets the steam level of a user. Returns `None` if their profile is private.
+pub async fn steam_level(api_key: &str, steam_id: &str) -> Result<Option<u32>, Error> {
+    Ok(reqwest::Client::new()
+        .get(format!("{}/IPlayerService/GetSteamLevel/v1/", API_URL))
+        .query(&[("key", api_key), ("steamid", steam_id), ("format", "json")])
+        .timeout(LEVEL_TIMEOUT)
+        .send()
+        .await?
+        .json::<SteamLevelResponse>()
+        .await?
+        .response
+        .player_level)
+}
+
+/// Gets the VAC and game bans on a user. Returns `None` if there's no user with that id.
+pub async fn player_bans(api_key: &str, steam_id: &str) -> Result<Option<PlayerBans>, Error> {
+    Ok(reqwest::Client::new()
+        .get(format!("{}/ISteamUser/GetPlayerBans/v1/", API_URL))
+        .query(&[("key", api_key), ("steamids", steam_id)])
+        .timeout(BANS_TIMEOUT)
+        .send()
+        .await?
+        .json::<PlayerBansResponse>()
+        .await?
+        .players
+        .into_iter()
+        .next())
+}
+
+/// Gets the games a user played in the last two weeks, most played first
+pub async fn recently_played(api_key: &str, steam_id: &str) -> Result<Vec<RecentGame>, Error> {
+    Ok(reqwest::Client::new()
+        .get(format!("{}/IPlayerService/GetRecentlyPlayedGames/v0001/", API_URL))
+        .query(&[("key", api_key), ("steamid", steam_id), ("format", "json")])
+        .timeout(RECENT_GAMES_TIMEOUT)
+        .send()
+        .await?
+        .json::<RecentlyPlayedResponse>()
+        .await?
+        .response
+        .games)
+}
+
+/// Gets which achievements of a game a user unlocked, and when.
+/// The inner error is steam's reason when it won't say, e.g. "Profile is not public" or "Requested app has no stats".
+pub async fn player_achievements(
+    api_key: &str,
+    steam_id: &str,
+    app_id: u32,
+) -> Result<Result<Vec<PlayerAchievement>, String>, Error> {
+    // steam answers with an error status and a json body for private profiles, so the status isn't checked
+    let stats = reqwest::Client::new()
+        .get(format!("{}/ISteamUserStats/GetPlayerAchievements/v1/", API_URL))
+        .query(&[
+            ("key", api_key),
+            ("steamid", steam_id),
+            ("appid", app_id.to_string().as_str()),
+        ])
+        .timeout(ACHIEVEMENTS_TIMEOUT)
+        .send()
+        .await?
+        .json::<PlayerAchievementsResponse>()
+        .await?
+        .playerstats;
+
+    if stats.success {
+        Ok(Ok(stats.achievements))
+    } else {
+        Ok(Err(stats.error.unwrap_or_else(|| String::from("Unknown error"))))
+    }
+}
+
+/// Gets the name of a game and the names, descriptions and icons of every achievement in it
+pub async fn achievement_schema(api_key: &str, app_id: u32) -> Result<GameSchema, Error> {
+    let game = reqwest::Client::new()
+        .get(format!("{}/ISteamUserStats/GetSchemaForGame/v2/", API_URL))
+        .query(&[("key", api_key), ("appid", app_id.to_string().as_str()), ("l", "english")])
+        .timeout(ACHIEVEMENTS_TIMEOUT)
+        .send()
+        .await?
+        .json::<SchemaResponse>()
+        .await?
+        .game;
+
+    Ok(GameSchema {
+        game_name: game.game_name,
+        achievements: game
+            .available_game_stats
+            .map(|stats| stats.achievements)
+            .unwrap_or_default(),
+    })
+}
+
+/// Turns minutes of playtime into hours, e.g. "12.5 hrs"
+pub fn format_hours(minutes: u32) -> String {
+    format!("{:.1} hrs", minutes as f64 / 60.0)
+}
+
+#[derive(Debug, Deserialize)]
+struct SteamIDResponse {
+    response: SteamID,
+}
+
+#[derive(Debug, Deserialize)]
+struct SteamID {
+    #[serde(rename = "steamid")]
+    steam_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PlayerSummaryResponse {
+    response: Players,
+}
+
+#[derive(Debug, Deserialize)]
+struct Players {
+    players: Vec<PlayerSummary>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PlayerSummary {
+    #[serde(rename = "steamid")]
+    pub steam_id: String, // the unique steam_id of the user [public]
+
+    #[serde(rename = "personaname")]
+    pub persona_name: String, // the displayed name or username (not unique on steam) [public]
+
+    #[serde(rename = "avatarfull")]
+    pub avatar: String, // the url for the steam avatar of the user [public]
+
+    #[serde(rename = "lastlogoff")]
+    pub last_logoff: Option<i64>, // the last date of logoff of the user [public]
+
+    #[serde(rename = "timecreated")]
+    pub time_created: Option<i64>, // the time at which the user's account was created [private]
+
+    #[serde(rename = "communityvisibilitystate")]
+    pub community_visibility_state: u8, // 1 if the user's profile is private, 3 if it's public [public]
+
+    #[serde(rename = "profileurl")]
+    pub profile_url: String, // the complete steam url of the user's profile [public]
+
+    #[serde(rename = "gameextrainfo")]
+    pub game_extra_info: Option<String>, // the name of the game that the user is currently playing [private]
+
+    #[serde(rename = "gameid")]
+    pub game_id: Option<String>, // the app id of the game that the user is currently playing [private]
+
+    #[serde(rename = "personastate")]
+    pub persona_state: u8,
+}
+
+#[derive(Debug, Deserialize)]
+struct OwnedGamesResponse {
+    response: OwnedGames,
+}
+
+#[derive(Debug, Deserialize)]
+struct OwnedGames {
+    game_count: Option<u32>,
+    #[serde(default)]
+    games: Vec<OwnedGame>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct OwnedGame {
+    #[serde(rename = "appid")]
+    pub app_id: u32,
+    #[serde(default)]
+    pub name: String,
+    pub playtime_forever: u32, // in minutes
+    #[serde(default)]
+    pub playtime_2weeks: u32, // in minutes
+}
+
+#[derive(Debug, Deserialize)]
+struct SteamLevelResponse {
+    response: SteamLevel,
+}
+
+#[derive(Debug, Deserialize)]
+struct SteamLevel {
+    player_level: Option<u32>, // missing when the profile is private
+}
+
+#[derive(Debug, Deserialize)]
+struct PlayerBansResponse {
+    #[serde(default)]
+    players: Vec<PlayerBans>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PlayerBans {
+    #[serde(rename = "VACBanned")]
+    pub vac_banned: bool,
+    #[serde(rename = "NumberOfVACBans")]
+    pub number_of_vac_bans: u32,
+    #[serde(rename = "NumberOfGameBans")]
+    pub number_of_game_bans: u32,
+    #[serde(rename = "DaysSinceLastBan")]
+    pub days_since_last_ban: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct PlayerAchievementsResponse {
+    playerstats: PlayerStats,
+}
+
+#[derive(Debug, Deserialize)]
+struct PlayerStats {
+    #[serde(default)]
+    success: bool,
+    error: Option<String>,
+    #[serde(default)]
+    achievements: Vec<PlayerAchievement>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PlayerAchievement {
+    #[serde(rename = "apiname")]
+    pub api_name: String, // matches `AchievementSchema::name`
+    pub achieved: u8,     // 1 if unlocked
+    #[serde(rename = "unlocktime")]
+    pub unlock_time: i64, // unix timestamp, 0 if locked
+}
+
+#[derive(Debug, Deserialize)]
+struct SchemaResponse {
+    #[serde(default)]
+    game: Schema,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Schema {
+    #[serde(rename = "gameName", default)]
+    game_name: String,
+    #[serde(rename = "availableGameStats")]
+    available_game_stats: Option<GameStats>, // missing for games without stats
+}
+
+#[derive(Debug, Deserialize)]
+struct GameStats {
+    #[serde(default)]
+    achievements: Vec<AchievementSchema>,
+}
+
+#[derive(Debug, Clone)]
+pub struct GameSchema {
+    pub game_name: String,
+    pub achievements: Vec<AchievementSchema>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AchievementSchema {
+    pub name: String, // the api name
+    #[serde(rename = "displayName")]
+    pub display_name: String,
+    #[serde(default)]
+    pub description: Option<String>, // usually missing for hidden achievements
+    #[serde(default)]
+    pub hidden: u8, // 1 if the description is a secret until it's unlocked
+    pub icon: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RecentlyPlayedResponse {
+    response: RecentGames,
+}
+
+#[derive(Debug, Deserialize)]
+struct RecentGames {
+    #[serde(default)]
+    games: Vec<RecentGame>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RecentGame {
+    #[serde(rename = "appid")]
+    pub app_id: u32,
+    #[serde(default)]
+    pub name: String,
+    pub playtime_2weeks: u32, // in minutes
+    pub playtime_forever: u32, // in minutes
+}
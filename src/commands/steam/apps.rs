@@ -0,0 +1,416 @@
+use crate::Error;
+use std::{
+    collections::HashMap,
+    env,
+    time::{Duration, Instant},
+};
+
+use serde::Deserialize;
+use tokio::sync::RwLock;
+
+const APP_LIST_URL: &str = "https://api.steampowered.com/ISteamApps/GetAppList/v2/";
+const APP_DETAILS_URL: &str = "https://store.steampowered.com/api/appdetails";
+const ACHIEVEMENT_PERCENTAGES_URL: &str =
+    "https://api.steampowered.com/ISteamUserStats/GetGlobalAchievementPercentagesForApp/v2/";
+const NEWS_URL: &str = "https://api.steampowered.com/ISteamNews/GetNewsForApp/v2/";
+const CURRENT_PLAYERS_URL: &str = "https://api.steampowered.com/ISteamUserStats/GetNumberOfCurrentPlayers/v1/";
+const APP_LIST_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60); // new games come out every day
+const APP_LIST_TIMEOUT: Duration = Duration::from_secs(30); // every app on steam is a big download
+const APP_DETAILS_TIMEOUT: Duration = Duration::from_secs(5);
+const CURRENT_PLAYERS_TIMEOUT: Duration = Duration::from_secs(5);
+const ACHIEVEMENT_PERCENTAGES_TIMEOUT: Duration = Duration::from_secs(10);
+const NEWS_TIMEOUT: Duration = Duration::from_secs(10); // whole posts are asked for
+
+/// Every app on steam, used to find games by name.
+///
+/// The list is big (well over 100k apps), so it's only fetched when it's first needed
+/// and then refreshed once a day.
+pub struct AppList {
+    apps: RwLock<Option<CachedApps>>,
+}
+
+struct CachedApps {
+    fetched_at: Instant,
+    apps: Vec<(String, App)>, // the simplified name (see `simplify`) and the app
+}
+
+impl AppList {
+    pub fn new() -> Self {
+        AppList {
+            apps: RwLock::new(None),
+        }
+    }
+
+    /// Finds the apps whose name matches `query`, best matches first.
+    ///
+    /// Exact matches come first, then names that start with the query, then names that contain it.
+    /// Shorter names win ties, so "Portal 2" comes before "Portal 2 - The Final Hours".
+    pub async fn search(&self, query: &str, limit: usize) -> Result<Vec<App>, Error> {
+        self.refresh_if_stale().await?;
+
+        let query = simplify(query);
+        if query.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let cached = self.apps.read().await;
+        let mut matches = cached
+            .as_ref()
+            .map(|cached| cached.apps.as_slice())
+            .unwrap_or(&[])
+            .iter()
+            .filter_map(|(name, app)| {
+                let rank = if *name == query {
+                    0
+                } else if name.starts_with(&query) {
+                    1
+                } else if name.contains(&query) {
+                    2
+                } else {
+                    return None;
+                };
+                Some((rank, name.len(), app))
+            })
+            .collect::<Vec<_>>();
+        matches.sort_by_key(|(rank, length, app)| (*rank, *length, app.app_id));
+
+        Ok(matches.into_iter().take(limit).map(|(_, _, app)| app.clone()).collect())
+    }
+
+    async fn refresh_if_stale(&self) -> Result<(), Error> {
+        let is_stale = |cached: &Option<CachedApps>| {
+            cached
+                .as_ref()
+                .map_or(true, |cached| cached.fetched_at.elapsed() > APP_LIST_MAX_AGE)
+        };
+
+        if !is_stale(&*self.apps.read().await) {
+            return Ok(());
+        }
+
+        let mut cached = self.apps.write().await;
+        // someone else might have refreshed it while we waited for the lock
+        if !is_stale(&*cached) {
+            return Ok(());
+        }
+
+        let apps = reqwest::Client::new()
+            .get(APP_LIST_URL)
+            .timeout(APP_LIST_TIMEOUT)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<AppListResponse>()
+            .await?
+            .applist
+            .apps
+            .into_iter()
+            .filter(|app| !app.name.trim().is_empty())
+            .map(|app| (simplify(&app.name), app))
+            .collect();
+
+        *cached = Some(CachedApps {
+            fetched_at: Instant::now(),
+            apps,
+        });
+
+        Ok(())
+    }
+}
+
+/// Lowercases a name and removes everything but letters, numbers and single spaces,
+/// so "Half-Life 2" matches "half life 2" and "Half-Life™ 2"
+fn simplify(name: &str) -> String {
+    name.to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+/// The country that store prices are shown for, from STEAM_REGION (e.g. "US", "DE"). Defaults to US.
+pub fn region() -> String {
+    env::var("STEAM_REGION").unwrap_or_else(|_| String::from("US"))
+}
+
+/// Gets the store page details of an app, with prices in the currency of `region`.
+/// Returns `None` if the app doesn't exist or has no store page.
+pub async fn app_details(app_id: u32, region: &str) -> Result<Option<AppDetails>, Error> {
+    let mut response = reqwest::Client::new()
+        .get(APP_DETAILS_URL)
+        .query(&[
+            ("appids", app_id.to_string().as_str()),
+            ("cc", region),
+            ("l", "english"),
+        ])
+        .timeout(APP_DETAILS_TIMEOUT)
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<HashMap<String, AppDetailsResponse>>()
+        .await?;
+
+    // the response is keyed by the app id
+    Ok(response
+        .remove(&app_id.to_string())
+        .filter(|response| response.success)
+        .and_then(|response| response.data))
+}
+
+/// Gets the store categories of an app ("Multi-player", "Steam Achievements", etc).
+/// Much smaller than the full details, for when that's all that's needed.
+pub async fn app_categories(app_id: u32) -> Result<Vec<String>, Error> {
+    let mut response = reqwest::Client::new()
+        .get(APP_DETAILS_URL)
+        .query(&[("appids", app_id.to_string().as_str()), ("filters", "categories")])
+        .timeout(APP_DETAILS_TIMEOUT)
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<HashMap<String, serde_json::Value>>()
+        .await?;
+
+    // "data" is an empty list instead of an object when the app has no categories, so don't use a struct
+    let categories = response
+        .remove(&app_id.to_string())
+        .and_then(|response| response["data"]["categories"].as_array().cloned())
+        .unwrap_or_default();
+
+    Ok(categories
+        .iter()
+        .filter_map(|category| category["description"].as_str().map(|description| description.to_string()))
+        .collect())
+}
+
+/// Gets the prices of many apps at once, in the currency of `region`.
+/// Apps that are free, not for sale or don't exist are `None`.
+pub async fn app_prices(app_ids: &[u32], region: &str) -> Result<HashMap<u32, Option<PriceOverview>>, Error> {
+    let app_ids_param = app_ids.iter().map(|app_id| app_id.to_string()).collect::<Vec<String>>().join(",");
+    let mut response = reqwest::Client::new()
+        .get(APP_DETAILS_URL)
+        .query(&[
+            ("appids", app_ids_param.as_str()),
+            ("cc", region),
+            ("filters", "price_overview"), // the store only answers for more than one app with this filter
+        ])
+        .timeout(APP_DETAILS_TIMEOUT)
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<HashMap<String, serde_json::Value>>()
+        .await?;
+
+    // "data" is an empty list instead of an object for free apps, so don't use a struct
+    Ok(app_ids
+        .iter()
+        .map(|app_id| {
+            let price = response
+                .remove(&app_id.to_string())
+                .and_then(|response| serde_json::from_value(response["data"]["price_overview"].clone()).ok());
+            (*app_id, price)
+        })
+        .collect())
+}
+
+/// Gets the number of people playing an app right now
+pub async fn current_players(app_id: u32) -> Result<Option<u32>, Error> {
+    let response = reqwest::Client::new()
+        .get(CURRENT_PLAYERS_URL)
+        .query(&[("appid", app_id)])
+        .timeout(CURRENT_PLAYERS_TIMEOUT)
+        .send()
+        .await?;
+
+    // steam answers 404 for apps it doesn't count players for
+    if !response.status().is_success() {
+        return Ok(None);
+    }
+
+    Ok(response.json::<CurrentPlayersResponse>().await?.response.player_count)
+}
+
+/// Gets the percentage of players who unlocked each achievement of an app, keyed by the achievement's api name
+pub async fn achievement_percentages(app_id: u32) -> Result<HashMap<String, f64>, Error> {
+    let response = reqwest::Client::new()
+        .get(ACHIEVEMENT_PERCENTAGES_URL)
+        .query(&[("gameid", app_id)])
+        .timeout(ACHIEVEMENT_PERCENTAGES_TIMEOUT)
+        .send()
+        .await?;
+
+    // steam answers 403 for apps without achievements
+    if !response.status().is_success() {
+        return Ok(HashMap::new());
+    }
+
+    // the percentages are sometimes numbers and sometimes strings, so don't use a struct
+    let response = response.json::<serde_json::Value>().await?;
+    let achievements = response["achievementpercentages"]["achievements"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default();
+
+    Ok(achievements
+        .iter()
+        .filter_map(|achievement| {
+            let name = achievement["name"].as_str()?.to_string();
+            let percent = match &achievement["percent"] {
+                serde_json::Value::String(percent) => percent.parse().ok()?,
+                percent => percent.as_f64()?,
+            };
+            Some((name, percent))
+        })
+        .collect())
+}
+
+/// Gets the latest official announcements of an app (patch notes, events, etc), newest first
+pub async fn app_news(app_id: u32, count: u32) -> Result<Vec<NewsItem>, Error> {
+    let response = reqwest::Client::new()
+        .get(NEWS_URL)
+        .query(&[
+            ("appid", app_id.to_string().as_str()),
+            ("count", count.to_string().as_str()),
+            ("maxlength", "0"), // the whole post
+            ("feeds", "steam_community_announcements"), // leave out the news sites steam collects
+        ])
+        .timeout(NEWS_TIMEOUT)
+        .send()
+        .await?;
+
+    // steam answers 403 for apps that don't exist
+    if !response.status().is_success() {
+        return Ok(Vec::new());
+    }
+
+    Ok(response.json::<NewsResponse>().await?.appnews.newsitems)
+}
+
+/// The store page of an app
+pub fn store_url(app_id: u32) -> String {
+    format!("https://store.steampowered.com/app/{}", app_id)
+}
+
+#[derive(Debug, Deserialize)]
+struct AppListResponse {
+    applist: Apps,
+}
+
+#[derive(Debug, Deserialize)]
+struct Apps {
+    apps: Vec<App>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct App {
+    #[serde(rename = "appid")]
+    pub app_id: u32,
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct AppDetailsResponse {
+    success: bool,
+    data: Option<AppDetails>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AppDetails {
+    #[serde(rename = "steam_appid")]
+    pub app_id: u32,
+    pub name: String,
+    #[serde(rename = "type")]
+    pub app_type: String, // "game", "dlc", "demo", etc
+    #[serde(default)]
+    pub is_free: bool,
+    #[serde(default)]
+    pub short_description: String,
+    pub header_image: Option<String>,
+    #[serde(default)]
+    pub developers: Vec<String>,
+    #[serde(default)]
+    pub publishers: Vec<String>,
+    pub price_overview: Option<PriceOverview>, // missing for free games and games that aren't sold
+    pub platforms: Option<Platforms>,
+    pub metacritic: Option<Metacritic>,
+    #[serde(default)]
+    pub genres: Vec<Tag>,
+    #[serde(default)]
+    pub categories: Vec<Tag>, // "Multi-player", "Steam Achievements", etc
+    pub release_date: Option<ReleaseDate>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PriceOverview {
+    pub currency: String,
+    pub initial: u32, // in cents
+    #[serde(rename = "final")]
+    pub final_price: u32, // in cents, after the discount
+    pub discount_percent: u32,
+    #[serde(default)]
+    pub initial_formatted: String, // empty when there's no discount
+    pub final_formatted: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Platforms {
+    #[serde(default)]
+    pub windows: bool,
+    #[serde(default)]
+    pub mac: bool,
+    #[serde(default)]
+    pub linux: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Metacritic {
+    pub score: u32,
+    pub url: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Tag {
+    pub description: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReleaseDate {
+    #[serde(default)]
+    pub coming_soon: bool,
+    #[serde(default)]
+    pub date: String, // e.g. "10 Oct, 2007", can be empty or vague ("Coming soon")
+}
+
+#[derive(Debug, Deserialize)]
+struct NewsResponse {
+    appnews: AppNews,
+}
+
+#[derive(Debug, Deserialize)]
+struct AppNews {
+    #[serde(default)]
+    newsitems: Vec<NewsItem>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewsItem {
+    pub gid: String, // unique for every post
+    pub title: String,
+    pub url: String,
+    #[serde(default)]
+    pub author: String,
+    #[serde(default)]
+    pub contents: String, // in BBCode
+    pub date: i64, // unix timestamp
+}
+
+#[derive(Debug, Deserialize)]
+struct CurrentPlayersResponse {
+    response: CurrentPlayers,
+}
+
+#[derive(Debug, Deserialize)]
+struct CurrentPlayers {
+    player_count: Option<u32>,
+}
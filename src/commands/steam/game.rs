@@ -0,0 +1,223 @@
+use crate::{Context, Error};
+
+use poise::serenity_prelude as serenity;
+
+use super::apps::{app_details, current_players, region, store_url, AppDetails};
+
+const OTHER_MATCHES: usize = 4; // how many other games with a similar name to mention
+
+/// Look up a game on the steam store
+///
+/// Shows the price, release date, developers, genres, Metacritic score and how many people are playing it.
+///
+/// **Usage:**
+/// `/steam game <name or app id>`
+///
+/// **Examples:**
+/// `/steam game portal 2`
+/// `/steam game 620`
+#[poise::command(
+    prefix_command,
+    slash_command,
+    track_edits,
+    defer_response,
+    broadcast_typing
+)]
+pub async fn game(
+    ctx: Context<'_>,
+    #[description = "The name or app id of the game"]
+    #[rest] game: String,
+) -> Result<(), Error> {
+    let game = game.trim();
+
+    // find the app id. either directly from the user, or by searching the names of every app
+    let (app_id, other_matches) = match game.parse::<u32>() {
+        Ok(app_id) => (app_id, Vec::new()),
+        Err(_) => {
+            let mut matches = ctx.data().steam_apps.search(game, OTHER_MATCHES + 1).await?;
+            if matches.is_empty() {
+                poise::say_reply(ctx, format!("No game called \"{}\" found on steam.", game)).await?;
+                return Ok(());
+            }
+            let best_match = matches.remove(0);
+            (best_match.app_id, matches)
+        }
+    };
+
+    let region = region();
+    let (details, players) = tokio::join!(app_details(app_id, &region), current_players(app_id));
+    let details = match details? {
+        Some(details) => details,
+        None => {
+            poise::say_reply(ctx, format!("App {} doesn't have a store page.", app_id)).await?;
+            return Ok(());
+        }
+    };
+    // the player count is a nice-to-have, don't fail the whole command over it
+    let players = players.ok().flatten();
+
+    let other_matches = other_matches
+        .iter()
+        .map(|app| format!("{} (`{}`)", app.name, app.app_id))
+        .collect::<Vec<String>>();
+
+    poise::send_reply(ctx, |message| {
+        message
+            .embed(|embed| {
+                game_embed(ctx, embed, &details, players);
+                if !other_matches.is_empty() {
+                    embed.field("Not the right game?", other_matches.join("\n"), false);
+                }
+                embed
+            })
+            .components(|components| {
+                components.create_action_row(|action_row| {
+                    action_row.create_button(|button| {
+                        button
+                            .style(serenity::ButtonStyle::Link)
+                            .label("Open store page")
+                            .url(store_url(details.app_id))
+                    });
+                    if let Some(url) = details.metacritic.as_ref().and_then(|metacritic| metacritic.url.as_ref()) {
+                        action_row.create_button(|button| {
+                            button
+                                .style(serenity::ButtonStyle::Link)
+                                .label("Metacritic")
+                                .url(url)
+                        });
+                    }
+                    action_row
+                })
+            })
+    })
+    .await?;
+
+    Ok(())
+}
+
+/// Finds the app id of a game given by name or app id.
+/// Tells the user and returns `None` if there's no game with that name, so the command should stop there.
+pub async fn app_id_for(ctx: Context<'_>, game: &str) -> Result<Option<u32>, Error> {
+    let game = game.trim();
+    if let Ok(app_id) = game.parse::<u32>() {
+        return Ok(Some(app_id));
+    }
+
+    match ctx.data().steam_apps.search(game, 1).await?.into_iter().next() {
+        Some(app) => Ok(Some(app.app_id)),
+        None => {
+            poise::say_reply(ctx, format!("No game called \"{}\" found on steam.", game)).await?;
+            Ok(None)
+        }
+    }
+}
+
+/// Fills an embed with the store details of a game
+pub fn game_embed<'a>(
+    ctx: Context<'_>,
+    embed: &'a mut serenity::CreateEmbed,
+    details: &AppDetails,
+    players: Option<u32>,
+) -> &'a mut serenity::CreateEmbed {
+    embed.title(&details.name).url(store_url(details.app_id));
+    embed.colour(serenity::Colour::from_rgb(0, 0, 0));
+    if !details.short_description.is_empty() {
+        embed.description(&details.short_description);
+    }
+    if let Some(header_image) = &details.header_image {
+        embed.image(header_image);
+    }
+    embed.author(|author| {
+        if let Some(icon_url) = ctx.author().avatar_url() {
+            author.icon_url(icon_url);
+        } else {
+            author.icon_url(ctx.author().default_avatar_url());
+        }
+        author.name(&ctx.author().name);
+        author
+    });
+
+    embed.field("Price", format_price(details), true);
+
+    if let Some(release_date) = &details.release_date {
+        if !release_date.date.is_empty() {
+            let release_date = if release_date.coming_soon {
+                format!("Coming {}", release_date.date)
+            } else {
+                release_date.date.clone()
+            };
+            embed.field("Release date", release_date, true);
+        }
+    }
+
+    if let Some(players) = players {
+        embed.field("Playing now", players, true);
+    }
+
+    if !details.developers.is_empty() {
+        embed.field("Developers", details.developers.join(", "), true);
+    }
+    if !details.publishers.is_empty() {
+        embed.field("Publishers", details.publishers.join(", "), true);
+    }
+
+    if let Some(metacritic) = &details.metacritic {
+        embed.field("Metacritic", format!("{}/100", metacritic.score), true);
+    }
+
+    if !details.genres.is_empty() {
+        let genres = details
+            .genres
+            .iter()
+            .map(|genre| genre.description.as_str())
+            .collect::<Vec<&str>>()
+            .join(", ");
+        embed.field("Genres", genres, false);
+    }
+
+    if let Some(platforms) = &details.platforms {
+        let platforms = [
+            (platforms.windows, "Windows"),
+            (platforms.mac, "macOS"),
+            (platforms.linux, "Linux"),
+        ]
+        .iter()
+        .filter(|(supported, _)| *supported)
+        .map(|(_, platform)| *platform)
+        .collect::<Vec<&str>>();
+        if !platforms.is_empty() {
+            embed.field("Platforms", platforms.join(", "), true);
+        }
+    }
+
+    embed.footer(|footer| {
+        if let Some(icon_url) = &ctx.discord().cache.current_user().avatar_url() {
+            footer.icon_url(icon_url);
+        } else {
+            footer.icon_url(ctx.discord().cache.current_user().default_avatar_url());
+        }
+        footer.text(format!(
+            "{} | Steam | App {}",
+            ctx.discord().cache.current_user().name,
+            details.app_id
+        ));
+        footer
+    });
+
+    embed.timestamp(chrono::Utc::now());
+
+    embed
+}
+
+/// e.g. "~~$19.99~~ **$9.99** (-50%)", "Free" or "Not for sale"
+pub fn format_price(details: &AppDetails) -> String {
+    match &details.price_overview {
+        Some(price) if price.discount_percent > 0 => format!(
+            "~~{}~~ **{}** (-{}%)",
+            price.initial_formatted, price.final_formatted, price.discount_percent
+        ),
+        Some(price) => price.final_formatted.clone(),
+        None if details.is_free => String::from("Free"),
+        None => String::from("Not for sale"),
+    }
+}
@@ -0,0 +1,182 @@
+use crate::{Context, Error};
+
+use poise::serenity_prelude as serenity;
+
+use super::api::{api_key, format_hours, owned_games, player_summary, recently_played};
+use super::api::{OwnedGame, PlayerSummary, RecentGame};
+use super::links::steam_id_for;
+use crate::helpers::{check_quota, paginate, Api};
+
+const GAMES_PER_PAGE: usize = 10;
+const RECENT_GAMES: usize = 5;
+const MAX_FIELD_LENGTH: usize = 950; // embed fields can only be 1024 characters long
+
+/// Show the most played and recently played games of a steam user
+///
+/// **Usage:**
+/// `/steam games [vanity name, steam id or mention]`
+/// Leave it empty to see your own linked account.
+///
+/// **Example:**
+/// `/steam games robinwalker`
+#[poise::command(
+    prefix_command,
+    slash_command,
+    track_edits,
+    defer_response,
+    broadcast_typing
+)]
+pub async fn games(
+    ctx: Context<'_>,
+    #[description = "A profile url, steam id, vanity name or mention. Leave empty for yourself"] account: Option<String>,
+) -> Result<(), Error> {
+    let api_key = api_key();
+
+    let steam_id = match steam_id_for(ctx, &api_key, account.as_deref()).await? {
+        Some(steam_id) => steam_id,
+        None => return Ok(()),
+    };
+
+    // the profile, owned games and recent games
+    if !check_quota(ctx, Api::Steam, 3).await? {
+        return Ok(());
+    }
+
+    let player = match player_summary(&api_key, &steam_id).await? {
+        Some(player) => player,
+        None => {
+            poise::say_reply(ctx, format!("Steam user \"{}\" not found.", steam_id)).await?;
+            return Ok(());
+        }
+    };
+
+    let (owned, recent) = tokio::join!(
+        owned_games(&api_key, &player.steam_id),
+        recently_played(&api_key, &player.steam_id)
+    );
+    let mut owned = match owned? {
+        Some(owned) => owned,
+        None => {
+            poise::say_reply(
+                ctx,
+                format!("{}'s game details are private.", player.persona_name),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+    let recent = recent.unwrap_or_default(); // not worth failing over
+
+    // most played first, games that were never played aren't listed
+    owned.sort_by(|a, b| b.playtime_forever.cmp(&a.playtime_forever));
+    let total_games = owned.len();
+    owned.retain(|game| game.playtime_forever > 0);
+    let pages = ((owned.len() + GAMES_PER_PAGE - 1) / GAMES_PER_PAGE).max(1);
+    paginate(ctx, pages, |embed, page| {
+        games_embed(ctx, embed, &player, &owned, total_games, &recent, page, pages)
+    })
+    .await?;
+
+    Ok(())
+}
+
+/// Fills an embed with one page of the most played games.
+/// The first page also has the games played in the last two weeks.
+#[allow(clippy::too_many_arguments)]
+fn games_embed<'a>(
+    ctx: Context<'_>,
+    embed: &'a mut serenity::CreateEmbed,
+    player: &PlayerSummary,
+    played: &[OwnedGame],
+    total_games: usize,
+    recent: &[RecentGame],
+    page: usize,
+    pages: usize,
+) -> &'a mut serenity::CreateEmbed {
+    embed.title(format!("{}'s games", player.persona_name)).url(&player.profile_url);
+    embed.thumbnail(&player.avatar);
+    embed.colour(serenity::Colour::from_rgb(0, 0, 0));
+    embed.author(|author| {
+        if let Some(icon_url) = ctx.author().avatar_url() {
+            author.icon_url(icon_url);
+        } else {
+            author.icon_url(ctx.author().default_avatar_url());
+        }
+        author.name(&ctx.author().name);
+        author
+    });
+
+    let total_minutes = played.iter().map(|game| game.playtime_forever).sum::<u32>();
+    let recent_minutes = recent.iter().map(|game| game.playtime_2weeks).sum::<u32>();
+    embed.description(format!(
+        "**{}** games, **{}** played in total ({} in the last two weeks)",
+        total_games,
+        format_hours(total_minutes),
+        format_hours(recent_minutes)
+    ));
+
+    if page == 0 && !recent.is_empty() {
+        let recent_list = recent
+            .iter()
+            .take(RECENT_GAMES)
+            .map(|game| {
+                format!(
+                    "**{}** — {} ({} total)",
+                    game.name,
+                    format_hours(game.playtime_2weeks),
+                    format_hours(game.playtime_forever)
+                )
+            })
+            .collect::<Vec<String>>();
+        embed.field("Last two weeks", field_list(&recent_list), false);
+    }
+
+    let first = page * GAMES_PER_PAGE;
+    let most_played = played
+        .iter()
+        .enumerate()
+        .skip(first)
+        .take(GAMES_PER_PAGE)
+        .map(|(rank, game)| format!("`{}.` **{}** — {}", rank + 1, game.name, format_hours(game.playtime_forever)))
+        .collect::<Vec<String>>();
+    if most_played.is_empty() {
+        embed.field("Most played", "No playtime yet.", false);
+    } else {
+        embed.field("Most played", field_list(&most_played), false);
+    }
+
+    embed.footer(|footer| {
+        if let Some(icon_url) = &ctx.discord().cache.current_user().avatar_url() {
+            footer.icon_url(icon_url);
+        } else {
+            footer.icon_url(ctx.discord().cache.current_user().default_avatar_url());
+        }
+        footer.text(format!(
+            "{} | Steam | Page {}/{}",
+            ctx.discord().cache.current_user().name,
+            page + 1,
+            pages
+        ));
+        footer
+    });
+
+    embed.timestamp(chrono::Utc::now());
+
+    embed
+}
+
+/// Puts the lines in an embed field, as many as fit.
+/// Long game names can make even a few lines too long.
+fn field_list(lines: &[String]) -> String {
+    let mut list = String::new();
+    for (shown, line) in lines.iter().enumerate() {
+        if list.len() + line.len() + 1 > MAX_FIELD_LENGTH {
+            list.push_str(&format!("...and {} more", lines.len() - shown));
+            break;
+        }
+        list.push_str(line);
+        list.push('\n');
+    }
+
+    list
+}
@@ -0,0 +1,107 @@
+use crate::{Context, Error};
+
+use poise::serenity_prelude as serenity;
+
+use super::api::{api_key, player_summary};
+use super::links::steam_id_for;
+use super::steam_id::{parse_identifier, Identifier};
+use crate::helpers::{check_quota, Api};
+
+/// Show every form of a steam account's id
+///
+/// Converts between profile urls, SteamID2, SteamID3, SteamID64 and vanity names.
+///
+/// **Usage:**
+/// `/steam id [profile url, id, vanity name or mention]`
+/// Leave it empty for your own linked account.
+///
+/// **Examples:**
+/// `/steam id STEAM_0:1:6115800`
+/// `/steam id https://steamcommunity.com/id/robinwalker`
+#[poise::command(
+    prefix_command,
+    slash_command,
+    track_edits,
+    defer_response,
+    broadcast_typing
+)]
+pub async fn id(
+    ctx: Context<'_>,
+    #[description = "A profile url, steam id, vanity name or mention. Leave empty for yourself"]
+    #[rest]
+    account: Option<String>,
+) -> Result<(), Error> {
+    let api_key = api_key();
+
+    let steam_id = match steam_id_for(ctx, &api_key, account.as_deref()).await? {
+        Some(steam_id) => steam_id,
+        None => return Ok(()),
+    };
+    let steam_id = match parse_identifier(&steam_id) {
+        Some(Identifier::SteamId(steam_id)) => steam_id,
+        _ => {
+            poise::say_reply(ctx, format!("\"{}\" isn't an individual steam account.", steam_id)).await?;
+            return Ok(());
+        }
+    };
+
+    // the profile has the name and the vanity url
+    if !check_quota(ctx, Api::Steam, 1).await? {
+        return Ok(());
+    }
+    let player = player_summary(&api_key, &steam_id.to_string()).await?;
+
+    poise::send_reply(ctx, |message| {
+        message.embed(|embed| {
+            match &player {
+                Some(player) => {
+                    embed.title(&player.persona_name).url(&player.profile_url);
+                    embed.thumbnail(&player.avatar);
+                }
+                None => {
+                    embed.title("Unknown account").url(steam_id.profile_url());
+                }
+            }
+            embed.colour(serenity::Colour::from_rgb(0, 0, 0));
+            embed.author(|author| {
+                if let Some(icon_url) = ctx.author().avatar_url() {
+                    author.icon_url(icon_url);
+                } else {
+                    author.icon_url(ctx.author().default_avatar_url());
+                }
+                author.name(&ctx.author().name);
+                author
+            });
+
+            embed.field("SteamID64", format!("`{}`", steam_id), true);
+            embed.field("SteamID2", format!("`{}`", steam_id.steam_id2()), true);
+            embed.field("SteamID3", format!("`{}`", steam_id.steam_id3()), true);
+            embed.field("Account id", format!("`{}`", steam_id.account_id()), true);
+            embed.field("Permanent profile url", steam_id.profile_url(), false);
+
+            // the profile url only has the vanity name in it if the user set one
+            if let Some(player) = &player {
+                if player.profile_url.contains("/id/") {
+                    embed.field("Custom profile url", &player.profile_url, false);
+                }
+            }
+
+            embed.footer(|footer| {
+                if let Some(icon_url) = &ctx.discord().cache.current_user().avatar_url() {
+                    footer.icon_url(icon_url);
+                } else {
+                    footer.icon_url(ctx.discord().cache.current_user().default_avatar_url());
+                }
+                footer.text(format!("{} | Steam", ctx.discord().cache.current_user().name));
+                footer
+            });
+
+            embed.timestamp(chrono::Utc::now());
+
+            embed
+        })
+    })
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,314 @@
+use crate::{Context, Error};
+use std::collections::{HashMap, HashSet};
+
+use poise::serenity_prelude as serenity;
+use serde::{Deserialize, Serialize};
+
+use super::api::{api_key, player_summaries, player_summary, resolve_identifier};
+use super::steam_id::{parse_identifier, Identifier};
+use crate::helpers::{check_quota, quota_exhausted_message, Api};
+
+const MAX_ROSTER_LINES: usize = 40; // it's one GetPlayerSummaries call
+const MAX_ROSTER_LENGTH: usize = 3900; // embed descriptions can only be 4096 characters long
+
+/// The steam accounts that Discord users linked, saved in steam_links.json
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SteamLinks {
+    pub users: HashMap<u64, String>, // SteamID64s, keyed by Discord user id
+    #[serde(default)]
+    pub guilds: HashMap<u64, HashSet<u64>>, // the servers each user linked their account in, keyed by Discord user id
+}
+
+/// Finds a user who linked their account in a server.
+///
+/// Looks in the cache first. Asking Discord costs a request per user,
+/// so that's only done for servers the user linked their account in
+/// (or any server, for links made before those were saved).
+/// Returns `None` if they aren't in the server, as far as the bot knows.
+pub async fn linked_member(
+    ctx: &serenity::Context,
+    guilds: &HashMap<u64, HashSet<u64>>,
+    guild_id: u64,
+    user_id: u64,
+) -> Option<serenity::Member> {
+    if let Some(member) = ctx.cache.member(guild_id, user_id) {
+        return Some(member);
+    }
+
+    let linked_here = guilds.get(&user_id).map_or(true, |guilds| guilds.contains(&guild_id));
+    if !linked_here {
+        return None;
+    }
+    // they might have left since
+    serenity::GuildId(guild_id).member(ctx, user_id).await.ok()
+}
+
+/// Works out which steam account a command is about.
+///
+/// `account` can be a Discord mention of someone who linked their account,
+/// or a steam account in any format `parse_identifier` understands (profile urls, ids and vanity names).
+/// Without `account`, it's the account of the user who used the command.
+/// Returns the reason to show the user if there's no account to use.
+pub async fn resolve_account(
+    ctx: Context<'_>,
+    api_key: &str,
+    account: Option<&str>,
+) -> Result<Result<String, String>, Error> {
+    let account = account.map(|account| account.trim()).filter(|account| !account.is_empty());
+
+    let discord_user = match account {
+        None => Some(ctx.author().id.0),
+        Some(account) => parse_mention(account),
+    };
+    if let Some(discord_user) = discord_user {
+        let steam_id = ctx.data().steam_links.read().await.users.get(&discord_user).cloned();
+        return Ok(match steam_id {
+            Some(steam_id) => Ok(steam_id),
+            None if discord_user == ctx.author().id.0 => {
+                Err(String::from("You haven't linked a steam account yet. Link one with `/steam link`."))
+            }
+            None => Err(format!("<@{}> hasn't linked a steam account.", discord_user)),
+        });
+    }
+
+    let account = account.unwrap_or_default();
+    let identifier = match parse_identifier(account) {
+        Some(identifier) => identifier,
+        None => return Ok(Err(format!("\"{}\" isn't a steam profile, id or vanity name.", account))),
+    };
+
+    // vanity names cost a call to resolve
+    let guild_id = ctx.guild_id().map(|guild_id| guild_id.0);
+    if matches!(identifier, Identifier::Vanity(_)) && !ctx.data().usage.try_use(Api::Steam, guild_id, 1).await? {
+        return Ok(Err(quota_exhausted_message(Api::Steam)));
+    }
+
+    Ok(resolve_identifier(api_key, &identifier)
+        .await?
+        .ok_or_else(|| format!("Steam user \"{}\" not found.", account)))
+}
+
+/// Like `resolve_account`, but tells the user why if there's no account to use.
+/// The command should stop there when this returns `None`.
+pub async fn steam_id_for(ctx: Context<'_>, api_key: &str, account: Option<&str>) -> Result<Option<String>, Error> {
+    match resolve_account(ctx, api_key, account).await? {
+        Ok(steam_id) => Ok(Some(steam_id)),
+        Err(reason) => {
+            poise::say_reply(ctx, reason).await?;
+            Ok(None)
+        }
+    }
+}
+
+/// Gets the Discord user id out of a mention like <@123> or <@!123>
+fn parse_mention(mention: &str) -> Option<u64> {
+    mention
+        .strip_prefix("<@")?
+        .strip_suffix('>')?
+        .trim_start_matches('!')
+        .parse()
+        .ok()
+}
+
+/// Link your Discord account to your steam account
+///
+/// The steam commands use your linked account when you don't give them one,
+/// and anyone can look you up by mentioning you.
+///
+/// **Usage:**
+/// `/steam link <profile url, steam id or vanity name>`
+///
+/// **Example:**
+/// `/steam link robinwalker`
+#[poise::command(prefix_command, slash_command, track_edits, defer_response)]
+pub async fn link(
+    ctx: Context<'_>,
+    #[description = "Your steam profile url, steam id or vanity name"] account: String,
+) -> Result<(), Error> {
+    let api_key = api_key();
+    if parse_mention(account.trim()).is_some() {
+        poise::say_reply(ctx, "Give me a steam account, not a Discord user.").await?;
+        return Ok(());
+    }
+
+    let steam_id = match steam_id_for(ctx, &api_key, Some(&account)).await? {
+        Some(steam_id) => steam_id,
+        None => return Ok(()),
+    };
+
+    // make sure the account exists before linking it
+    if !check_quota(ctx, Api::Steam, 1).await? {
+        return Ok(());
+    }
+    let player = match player_summary(&api_key, &steam_id).await? {
+        Some(player) => player,
+        None => {
+            poise::say_reply(ctx, format!("Steam user \"{}\" not found.", account)).await?;
+            return Ok(());
+        }
+    };
+
+    let user_id = ctx.author().id.0;
+    let guild_id = ctx.guild_id().map(|guild_id| guild_id.0);
+    ctx.data()
+        .steam_links
+        .update(|links| {
+            links.users.insert(user_id, player.steam_id.clone());
+            if let Some(guild_id) = guild_id {
+                links.guilds.entry(user_id).or_default().insert(guild_id);
+            }
+        })
+        .await?;
+
+    poise::say_reply(
+        ctx,
+        format!("Linked your account to **{}** ({}).", player.persona_name, player.profile_url),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Unlink your steam account
+///
+/// This also deletes the play sessions recorded for it.
+#[poise::command(prefix_command, slash_command)]
+pub async fn unlink(ctx: Context<'_>) -> Result<(), Error> {
+    let user_id = ctx.author().id.0;
+    let removed = ctx
+        .data()
+        .steam_links
+        .update(|links| {
+            let steam_id = links.users.remove(&user_id)?;
+            links.guilds.remove(&user_id);
+            // keep the play sessions if someone else linked the same account
+            let still_linked = links.users.values().any(|linked| *linked == steam_id);
+            Some((steam_id, still_linked))
+        })
+        .await?;
+
+    if let Some((steam_id, still_linked)) = &removed {
+        if !still_linked {
+            ctx.data()
+                .steam_presence
+                .update(|presence| presence.forget(steam_id))
+                .await?;
+        }
+    }
+
+    if removed.is_some() {
+        poise::say_reply(ctx, "Your steam account isn't linked anymore.").await?;
+    } else {
+        poise::say_reply(ctx, "You haven't linked a steam account.").await?;
+    }
+
+    Ok(())
+}
+
+/// Show the members of this server who linked their steam account
+#[poise::command(prefix_command, slash_command, defer_response, broadcast_typing)]
+pub async fn linked(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id,
+        None => {
+            poise::say_reply(ctx, "This only works in a server.").await?;
+            return Ok(());
+        }
+    };
+
+    let (links, guilds) = {
+        let links = ctx.data().steam_links.read().await;
+        (links.users.clone(), links.guilds.clone())
+    };
+
+    // only the links of people in this server
+    let mut members = Vec::new();
+    let mut found = Vec::new();
+    for (user_id, steam_id) in links {
+        if let Some(member) = linked_member(ctx.discord(), &guilds, guild_id.0, user_id).await {
+            members.push((member.display_name().to_string(), steam_id));
+            found.push(user_id);
+        }
+    }
+
+    // remember the server for people who weren't known to be in it, so the next lookup is cheaper
+    let unknown = found
+        .into_iter()
+        .filter(|user_id| !guilds.get(user_id).map_or(false, |guilds| guilds.contains(&guild_id.0)))
+        .collect::<Vec<u64>>();
+    if !unknown.is_empty() {
+        ctx.data()
+            .steam_links
+            .update(|links| {
+                // someone could have unlinked in the meantime
+                for user_id in unknown.into_iter().filter(|user_id| links.users.contains_key(user_id)) {
+                    links.guilds.entry(user_id).or_default().insert(guild_id.0);
+                }
+            })
+            .await?;
+    }
+    if members.is_empty() {
+        poise::say_reply(ctx, "Nobody in this server has linked a steam account yet.").await?;
+        return Ok(());
+    }
+    members.sort_by_key(|(name, _)| name.to_lowercase());
+
+    // get the steam names of everyone who's shown, in one call
+    if !check_quota(ctx, Api::Steam, 1).await? {
+        return Ok(());
+    }
+    let steam_ids = members
+        .iter()
+        .take(MAX_ROSTER_LINES)
+        .map(|(_, steam_id)| steam_id.clone())
+        .collect::<Vec<String>>();
+    let players = player_summaries(&api_key(), &steam_ids)
+        .await?
+        .into_iter()
+        .map(|player| (player.steam_id.clone(), player))
+        .collect::<HashMap<_, _>>();
+
+    // as many people as fit, names and profile links add up quickly
+    let mut roster = String::new();
+    for (shown, (name, steam_id)) in members.iter().enumerate() {
+        let line = match players.get(steam_id) {
+            Some(player) => format!("**{}** — [{}]({})\n", name, player.persona_name, player.profile_url),
+            None => format!("**{}** — `{}`\n", name, steam_id),
+        };
+
+        if shown == MAX_ROSTER_LINES || roster.len() + line.len() > MAX_ROSTER_LENGTH {
+            roster.push_str(&format!("...and {} more", members.len() - shown));
+            break;
+        }
+        roster.push_str(&line);
+    }
+
+    poise::send_reply(ctx, |message| {
+        message.embed(|embed| {
+            embed.title("Linked steam accounts");
+            embed.colour(serenity::Colour::from_rgb(0, 0, 0));
+            embed.description(roster);
+
+            embed.footer(|footer| {
+                if let Some(icon_url) = &ctx.discord().cache.current_user().avatar_url() {
+                    footer.icon_url(icon_url);
+                } else {
+                    footer.icon_url(ctx.discord().cache.current_user().default_avatar_url());
+                }
+                footer.text(format!(
+                    "{} | Steam | {} linked",
+                    ctx.discord().cache.current_user().name,
+                    members.len()
+                ));
+                footer
+            });
+
+            embed.timestamp(chrono::Utc::now());
+
+            embed
+        })
+    })
+    .await?;
+
+    Ok(())
+}
@@ -1,2 +1,15 @@
+pub mod achievements; // export the achievements subcommand
+pub mod api; // the steam web api calls the subcommands share
+pub mod apps; // the store api and the list of every app
+pub mod game; // export the game subcommand
+pub mod games; // export the games subcommand
+pub mod id; // export the id subcommand
+pub mod links; // export the link, unlink and linked subcommands
+pub mod news; // export the follow, unfollow and following subcommands, the news checks and the BBCode conversion
+pub mod presence; // export the presence and sessions subcommands, and the presence checks
 pub mod steam; // export the steam main command
+pub mod steam_id; // parsing and converting steam ids
+pub mod together; // export the together subcommand
 pub mod user; // export the user subcommand
+pub mod watch; // export the watch, watches, unwatch and alerts subcommands, and the price checks
+pub mod workshop; // export the workshop subcommand
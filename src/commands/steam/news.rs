@@ -0,0 +1,542 @@
+use crate::{Context, Error};
+use std::{collections::HashMap, env, sync::Arc, time::Duration};
+
+use log::{error, warn};
+use poise::serenity_prelude as serenity;
+use serde::{Deserialize, Serialize};
+
+use super::apps::{app_details, app_news, region, store_url, NewsItem};
+use super::game::app_id_for;
+use crate::helpers::Store;
+
+const MAX_FOLLOWS: usize = 25; // per server
+const NEWS_PER_CHECK: u32 = 10;
+const SEEN_PER_APP: usize = 50; // more than steam ever returns in one check, so nothing gets posted twice
+const DEFAULT_CHECK_INTERVAL: u64 = 30; // minutes between news checks, unless STEAM_NEWS_INTERVAL says otherwise
+const MAX_POST_LENGTH: usize = 1500; // patch notes can be huge, the rest is on steam
+const CLAN_IMAGE_URL: &str = "https://clan.akamai.steamstatic.com/images";
+const BBCODE_TAGS: [&str; 26] = [
+    "b", "i", "u", "s", "strike", "h1", "h2", "h3", "url", "img", "previewyoutube", "*", "list", "olist", "p", "br",
+    "hr", "quote", "code", "table", "tr", "th", "td", "spoiler", "noparse", "video",
+];
+
+/// The games each channel follows, and the posts that were already seen. Saved in steam_news.json
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SteamNews {
+    pub follows: Vec<NewsFollow>,
+    pub seen: HashMap<u32, Vec<String>>, // the gids of the latest posts, keyed by app id
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewsFollow {
+    pub guild_id: u64,
+    pub channel_id: u64,
+    pub app_id: u32,
+    pub name: String,
+    pub followed_by: u64,
+}
+
+/// Post a game's steam news in a channel (admins only)
+///
+/// New announcements and patch notes are posted as they come out.
+///
+/// **Usage:**
+/// `/steam follow <name or app id> [channel]`
+/// Posts in the current channel if no channel is given.
+///
+/// **Examples:**
+/// `/steam follow dota 2`
+/// `&steam follow 570 #patch-notes`
+#[poise::command(
+    prefix_command,
+    slash_command,
+    track_edits,
+    defer_response,
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn follow(
+    ctx: Context<'_>,
+    #[description = "The name or app id of the game"] game: String,
+    #[description = "The channel to post the news in. Leave empty for this channel"] channel: Option<
+        serenity::GuildChannel,
+    >,
+) -> Result<(), Error> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id.0,
+        None => {
+            poise::say_reply(ctx, "This only works in a server.").await?;
+            return Ok(());
+        }
+    };
+    let channel_id = match channel {
+        Some(channel) if channel.guild_id.0 != guild_id => {
+            poise::say_reply(ctx, "That channel isn't in this server.").await?;
+            return Ok(());
+        }
+        Some(channel) => channel.id.0,
+        None => ctx.channel_id().0,
+    };
+
+    let app_id = match app_id_for(ctx, &game).await? {
+        Some(app_id) => app_id,
+        None => return Ok(()),
+    };
+    let details = match app_details(app_id, &region()).await? {
+        Some(details) => details,
+        None => {
+            poise::say_reply(ctx, format!("App {} doesn't have a store page.", app_id)).await?;
+            return Ok(());
+        }
+    };
+
+    // what's out already isn't news, only post what comes after this
+    let news = app_news(app_id, NEWS_PER_CHECK).await?;
+    let gids = news.iter().map(|item| item.gid.clone()).collect::<Vec<String>>();
+
+    let follow = NewsFollow {
+        guild_id,
+        channel_id,
+        app_id,
+        name: details.name.clone(),
+        followed_by: ctx.author().id.0,
+    };
+    let followed = ctx
+        .data()
+        .steam_news
+        .update(|steam_news| {
+            if steam_news
+                .follows
+                .iter()
+                .any(|existing| existing.channel_id == channel_id && existing.app_id == app_id)
+            {
+                return Err(format!("<#{}> already follows **{}**.", channel_id, follow.name));
+            }
+            if steam_news.follows.iter().filter(|existing| existing.guild_id == guild_id).count() >= MAX_FOLLOWS {
+                return Err(format!(
+                    "This server can only follow {} games. Remove some with `/steam unfollow`.",
+                    MAX_FOLLOWS
+                ));
+            }
+
+            // the newest are at the end
+            let seen = steam_news.seen.entry(app_id).or_default();
+            for gid in gids.into_iter().rev() {
+                if !seen.contains(&gid) {
+                    seen.push(gid);
+                }
+            }
+            steam_news.follows.push(follow.clone());
+            Ok(())
+        })
+        .await?;
+
+    match followed {
+        Ok(()) => {
+            poise::say_reply(
+                ctx,
+                format!("New posts about **{}** will be posted in <#{}>.", details.name, channel_id),
+            )
+            .await?
+        }
+        Err(reason) => poise::say_reply(ctx, reason).await?,
+    };
+
+    Ok(())
+}
+
+/// Stop posting a game's steam news (admins only)
+///
+/// **Usage:**
+/// `/steam unfollow <name or app id> [channel]`
+/// Stops it in every channel if no channel is given.
+#[poise::command(prefix_command, slash_command, required_permissions = "MANAGE_GUILD")]
+pub async fn unfollow(
+    ctx: Context<'_>,
+    #[description = "The name or app id of the game, as shown in /steam following"] game: String,
+    #[description = "Only stop posting in this channel"] channel: Option<serenity::GuildChannel>,
+) -> Result<(), Error> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id.0,
+        None => {
+            poise::say_reply(ctx, "This only works in a server.").await?;
+            return Ok(());
+        }
+    };
+    let game = game.trim().to_string();
+    let channel_id = channel.map(|channel| channel.id.0);
+
+    let removed = ctx
+        .data()
+        .steam_news
+        .update(|steam_news| {
+            let before = steam_news.follows.len();
+            steam_news.follows.retain(|follow| {
+                let matches = follow.guild_id == guild_id
+                    && channel_id.map_or(true, |channel_id| follow.channel_id == channel_id)
+                    && (follow.app_id.to_string() == game || follow.name.eq_ignore_ascii_case(&game));
+                !matches
+            });
+            before - steam_news.follows.len()
+        })
+        .await?;
+
+    if removed == 0 {
+        poise::say_reply(ctx, format!("Nothing here follows \"{}\". See `/steam following`.", game)).await?;
+    } else {
+        poise::say_reply(ctx, format!("Stopped posting news about \"{}\".", game)).await?;
+    }
+
+    Ok(())
+}
+
+/// Show the games whose steam news is posted in this server
+#[poise::command(prefix_command, slash_command)]
+pub async fn following(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id.0,
+        None => {
+            poise::say_reply(ctx, "This only works in a server.").await?;
+            return Ok(());
+        }
+    };
+
+    let follows = ctx
+        .data()
+        .steam_news
+        .read()
+        .await
+        .follows
+        .iter()
+        .filter(|follow| follow.guild_id == guild_id)
+        .cloned()
+        .collect::<Vec<NewsFollow>>();
+    if follows.is_empty() {
+        poise::say_reply(ctx, "This server doesn't follow any games yet. Admins can add one with `/steam follow`.")
+            .await?;
+        return Ok(());
+    }
+
+    let lines = follows
+        .iter()
+        .map(|follow| {
+            format!(
+                "[{}]({}) (`{}`) in <#{}>",
+                follow.name,
+                store_url(follow.app_id),
+                follow.app_id,
+                follow.channel_id
+            )
+        })
+        .collect::<Vec<String>>();
+
+    poise::send_reply(ctx, |message| {
+        message.embed(|embed| {
+            embed.title("Followed steam news");
+            embed.colour(serenity::Colour::from_rgb(0, 0, 0));
+            embed.description(lines.join("\n"));
+
+            embed.footer(|footer| {
+                if let Some(icon_url) = &ctx.discord().cache.current_user().avatar_url() {
+                    footer.icon_url(icon_url);
+                } else {
+                    footer.icon_url(ctx.discord().cache.current_user().default_avatar_url());
+                }
+                footer.text(format!("{} | Steam News", ctx.discord().cache.current_user().name));
+                footer
+            });
+
+            embed.timestamp(chrono::Utc::now());
+
+            embed
+        })
+    })
+    .await?;
+
+    Ok(())
+}
+
+/// Starts checking every followed game for news in the background,
+/// every STEAM_NEWS_INTERVAL minutes (30 by default)
+pub fn start_news_checks(ctx: serenity::Context, steam_news: Arc<Store<SteamNews>>) {
+    let minutes = env::var("STEAM_NEWS_INTERVAL")
+        .ok()
+        .and_then(|minutes| minutes.parse::<u64>().ok())
+        .filter(|minutes| *minutes > 0)
+        .unwrap_or(DEFAULT_CHECK_INTERVAL);
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(minutes * 60));
+        loop {
+            interval.tick().await;
+            if let Err(why) = check_news(&ctx, &steam_news).await {
+                error!("Couldn't check the news of followed steam games: {}", why);
+            }
+        }
+    });
+}
+
+async fn check_news(ctx: &serenity::Context, steam_news: &Store<SteamNews>) -> Result<(), Error> {
+    let mut app_ids = steam_news
+        .read()
+        .await
+        .follows
+        .iter()
+        .map(|follow| follow.app_id)
+        .collect::<Vec<u32>>();
+    app_ids.sort_unstable();
+    app_ids.dedup();
+
+    for app_id in app_ids {
+        let news = match app_news(app_id, NEWS_PER_CHECK).await {
+            Ok(news) => news,
+            Err(why) => {
+                // try again on the next check
+                warn!("Couldn't get the steam news of {}: {}", app_id, why);
+                continue;
+            }
+        };
+
+        // remember the posts before posting them, so a crash halfway can't post them twice
+        let (new_posts, follows) = steam_news
+            .update(|steam_news| {
+                let seen = steam_news.seen.entry(app_id).or_default();
+                let new_posts = news
+                    .iter()
+                    .filter(|item| !seen.contains(&item.gid))
+                    .cloned()
+                    .collect::<Vec<NewsItem>>();
+
+                // the newest are at the end
+                seen.extend(new_posts.iter().rev().map(|item| item.gid.clone()));
+                if seen.len() > SEEN_PER_APP {
+                    let extra = seen.len() - SEEN_PER_APP;
+                    seen.drain(..extra);
+                }
+
+                let follows = steam_news
+                    .follows
+                    .iter()
+                    .filter(|follow| follow.app_id == app_id)
+                    .cloned()
+                    .collect::<Vec<NewsFollow>>();
+                (new_posts, follows)
+            })
+            .await?;
+
+        // oldest first, so the channel reads in order
+        for item in new_posts.iter().rev() {
+            for follow in &follows {
+                if let Err(why) = post_news(ctx, follow, item).await {
+                    warn!("Couldn't post steam news in channel {}: {}", follow.channel_id, why);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn post_news(ctx: &serenity::Context, follow: &NewsFollow, item: &NewsItem) -> Result<(), Error> {
+    let (contents, image) = bbcode_to_markdown(&item.contents);
+    let (contents, truncated) = truncate_post(&contents, MAX_POST_LENGTH);
+    let bot = ctx.cache.current_user();
+    let posted_at = chrono::DateTime::<chrono::Utc>::from_utc(chrono::NaiveDateTime::from_timestamp(item.date, 0), chrono::Utc);
+
+    serenity::ChannelId(follow.channel_id)
+        .send_message(ctx, |message| {
+            message.embed(|embed| {
+                embed.title(&item.title).url(&item.url);
+                embed.colour(serenity::Colour::from_rgb(0, 0, 0));
+                embed.author(|author| author.name(&follow.name).url(store_url(follow.app_id)));
+
+                let mut description = contents.clone();
+                if truncated {
+                    description.push_str(&format!("\n\n[Read the whole post on steam]({})", item.url));
+                }
+                if !description.trim().is_empty() {
+                    embed.description(description);
+                }
+                if let Some(image) = &image {
+                    embed.image(image);
+                }
+
+                embed.footer(|footer| {
+                    if let Some(icon_url) = bot.avatar_url() {
+                        footer.icon_url(icon_url);
+                    } else {
+                        footer.icon_url(bot.default_avatar_url());
+                    }
+                    footer.text(format!("{} | Steam News", bot.name));
+                    footer
+                });
+
+                embed.timestamp(posted_at);
+
+                embed
+            })
+        })
+        .await?;
+
+    Ok(())
+}
+
+/// Turns the BBCode that steam posts are written in into Discord markdown.
+/// Also returns the first image, since embeds can only show one.
+pub fn bbcode_to_markdown(bbcode: &str) -> (String, Option<String>) {
+    let bbcode = bbcode.replace("{STEAM_CLAN_IMAGE}", CLAN_IMAGE_URL);
+    let mut markdown = String::new();
+    let mut first_image = None;
+    let mut links = Vec::<(usize, String)>::new(); // where each open link's text starts, and where it goes
+    let mut rest = bbcode.as_str();
+
+    while let Some(start) = rest.find('[') {
+        markdown.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let end = match rest.find(']') {
+            Some(end) => end,
+            None => break,
+        };
+        let tag = &rest[1..end];
+        let closing = tag.starts_with('/');
+        let mut parts = tag.trim_start_matches('/').splitn(2, '=');
+        let name = parts.next().unwrap_or_default().trim().to_ascii_lowercase();
+        let value = parts.next().map(|value| value.trim_matches('"').to_string());
+
+        // brackets that aren't tags, like "[Bug fixes]", stay as they are
+        if !BBCODE_TAGS.contains(&name.as_str()) {
+            markdown.push('[');
+            rest = &rest[1..];
+            continue;
+        }
+        rest = &rest[end + 1..];
+
+        match (name.as_str(), closing) {
+            ("b", _) => markdown.push_str("**"),
+            ("i", _) => markdown.push('*'),
+            ("u", _) => markdown.push_str("__"),
+            ("s", _) | ("strike", _) => markdown.push_str("~~"),
+            ("h1", false) | ("h2", false) | ("h3", false) => markdown.push_str("\n**"),
+            ("h1", true) | ("h2", true) | ("h3", true) => markdown.push_str("**\n"),
+            ("url", false) => links.push((markdown.len(), value.unwrap_or_default())),
+            ("url", true) => {
+                if let Some((text_start, url)) = links.pop() {
+                    let text = markdown[text_start..].trim().to_string();
+                    markdown.truncate(text_start);
+                    // [url]https://...[/url] has no separate text, and image links have none left
+                    if url.is_empty() || text == url {
+                        markdown.push_str(&text);
+                    } else if !text.is_empty() {
+                        markdown.push_str(&format!("[{}]({})", text, url));
+                    }
+                }
+            }
+            ("img", false) => {
+                // the image url is everything up to [/img]
+                let end = rest.to_ascii_lowercase().find("[/img]").unwrap_or_else(|| rest.len());
+                if first_image.is_none() {
+                    first_image = Some(rest[..end].trim().to_string());
+                }
+                rest = &rest[(end + "[/img]".len()).min(rest.len())..];
+            }
+            ("previewyoutube", false) => {
+                // [previewyoutube=<video id>;full][/previewyoutube]
+                if let Some(video_id) = value.as_deref().and_then(|value| value.split(';').next()) {
+                    markdown.push_str(&format!("\nhttps://youtu.be/{}\n", video_id));
+                }
+            }
+            ("*", false) => markdown.push_str("\n• "),
+            ("list", _) | ("olist", _) => markdown.push('\n'),
+            ("p", true) | ("br", _) | ("hr", _) => markdown.push('\n'),
+            ("quote", false) => markdown.push_str("\n> "),
+            ("quote", true) => markdown.push('\n'),
+            ("code", _) => markdown.push_str("\n```\n"),
+            _ => (), // tables, spoilers and the rest just lose their tags
+        }
+    }
+    markdown.push_str(rest);
+
+    // lists and paragraphs leave lots of empty lines behind
+    let mut cleaned = String::new();
+    let mut empty_lines = 0;
+    for line in markdown.lines() {
+        let line = line.trim_end();
+        if line.trim().is_empty() {
+            empty_lines += 1;
+            if empty_lines > 1 {
+                continue;
+            }
+        } else {
+            empty_lines = 0;
+        }
+        cleaned.push_str(line);
+        cleaned.push('\n');
+    }
+
+    (cleaned.trim().to_string(), first_image)
+}
+
+/// Cuts a post down to at most `max` characters, at the end of a paragraph or line if possible.
+/// Returns whether anything was cut.
+pub fn truncate_post(text: &str, max: usize) -> (String, bool) {
+    if text.chars().count() <= max {
+        return (text.to_string(), false);
+    }
+
+    let cut = text.chars().take(max).collect::<String>();
+    // don't cut too early just to end on a paragraph
+    let end = [cut.rfind("\n\n"), cut.rfind('\n'), cut.rfind(' ')]
+        .iter()
+        .flatten()
+        .copied()
+        .find(|end| *end > max / 2)
+        .unwrap_or_else(|| cut.len());
+    let mut truncated = cut[..end].trim_end().to_string();
+
+    // close a code block that was cut in half, or the rest of the embed would be code too
+    if truncated.matches("```").count() % 2 == 1 {
+        truncated.push_str("\n```\n…");
+        return (truncated, true);
+    }
+    // close bold text that was cut in half
+    if truncated.matches("**").count() % 2 == 1 {
+        truncated.push_str("**");
+    }
+    truncated.push_str(" …");
+
+    (truncated, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leaves_short_posts_alone() {
+        assert_eq!(truncate_post("short", 100), (String::from("short"), false));
+    }
+
+    #[test]
+    fn cuts_between_words() {
+        let (truncated, cut) = truncate_post("first line\nsecond line that is long", 20);
+        assert!(cut);
+        assert_eq!(truncated, "first line\nsecond …");
+    }
+
+    #[test]
+    fn closes_cut_bold_text() {
+        let (truncated, _) = truncate_post("**a very long bold sentence that goes on**", 20);
+        assert_eq!(truncated.matches("**").count(), 2);
+        assert!(truncated.ends_with("** …"));
+    }
+
+    #[test]
+    fn closes_a_cut_code_block() {
+        let post = bbcode_to_markdown("Patch notes[code]fixed one thing\nfixed another thing\nfixed a third thing[/code]").0;
+        let (truncated, cut) = truncate_post(&post, post.chars().count() - 10);
+        assert!(cut);
+        assert_eq!(truncated.matches("```").count(), 2);
+        // the closing fence is on a line of its own, and nothing comes after it on that line
+        assert!(truncated.ends_with("\n```\n…"));
+    }
+}
@@ -0,0 +1,381 @@
+use crate::{Context, Error};
+use std::{collections::HashMap, env, sync::Arc, time::Duration};
+
+use log::{error, warn};
+use poise::serenity_prelude as serenity;
+use serde::{Deserialize, Serialize};
+
+use super::api::{api_key, format_hours, player_summaries, player_summary};
+use super::apps::store_url;
+use super::links::{linked_member, steam_id_for, SteamLinks};
+use crate::helpers::{check_quota, Api, Store, UsageTracker};
+
+const MAX_IDS_PER_CALL: usize = 100; // the most GetPlayerSummaries takes at once
+const DEFAULT_CHECK_INTERVAL: u64 = 2; // minutes between presence checks, unless STEAM_PRESENCE_INTERVAL says otherwise
+const SESSIONS_PER_ACCOUNT: usize = 50; // the oldest sessions are forgotten
+const SHOWN_SESSIONS: usize = 10;
+const SHOWN_GAMES: usize = 5;
+
+/// What linked steam accounts are playing, what they played, and where servers want to hear about it.
+/// Saved in steam_presence.json
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SteamPresence {
+    pub channels: HashMap<u64, u64>,            // "now playing" channel ids, keyed by guild id
+    pub playing: HashMap<String, Session>,      // the sessions going on right now, keyed by SteamID64
+    pub history: HashMap<String, Vec<Session>>, // finished sessions, oldest first, keyed by SteamID64
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub app_id: Option<u32>, // None for non-steam games
+    pub game: String,
+    pub started_at: i64,
+    pub ended_at: Option<i64>, // None while it's going on
+}
+
+impl SteamPresence {
+    /// Forgets everything about an account, for when it's unlinked
+    pub fn forget(&mut self, steam_id: &str) {
+        self.playing.remove(steam_id);
+        self.history.remove(steam_id);
+    }
+}
+
+/// Post in a channel when members start playing a game (admins only)
+///
+/// Only members who linked their steam account with `/steam link` are tracked,
+/// and only while their game details are public.
+///
+/// **Usage:**
+/// `/steam presence [channel]`
+/// Leave the channel empty to stop posting.
+#[poise::command(prefix_command, slash_command, required_permissions = "MANAGE_GUILD")]
+pub async fn presence(
+    ctx: Context<'_>,
+    #[description = "The channel to post in. Leave empty to stop posting"] channel: Option<serenity::GuildChannel>,
+) -> Result<(), Error> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id.0,
+        None => {
+            poise::say_reply(ctx, "This only works in a server.").await?;
+            return Ok(());
+        }
+    };
+
+    match channel {
+        Some(channel) => {
+            if channel.guild_id.0 != guild_id {
+                poise::say_reply(ctx, "That channel isn't in this server.").await?;
+                return Ok(());
+            }
+            ctx.data()
+                .steam_presence
+                .update(|presence| presence.channels.insert(guild_id, channel.id.0))
+                .await?;
+            poise::say_reply(
+                ctx,
+                format!("Members who linked their steam account will show up in <#{}> when they start playing.", channel.id.0),
+            )
+            .await?;
+        }
+        None => {
+            ctx.data()
+                .steam_presence
+                .update(|presence| presence.channels.remove(&guild_id))
+                .await?;
+            poise::say_reply(ctx, "Stopped posting what members are playing.").await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Show the recent play sessions of a linked steam account
+///
+/// Sessions are only recorded for accounts linked with `/steam link`.
+///
+/// **Usage:**
+/// `/steam sessions [user]`
+/// Leave it empty to see your own.
+#[poise::command(prefix_command, slash_command, track_edits, defer_response)]
+pub async fn sessions(
+    ctx: Context<'_>,
+    #[description = "A profile url, steam id, vanity name or mention. Leave empty for yourself"] account: Option<String>,
+) -> Result<(), Error> {
+    let api_key = api_key();
+    let steam_id = match steam_id_for(ctx, &api_key, account.as_deref()).await? {
+        Some(steam_id) => steam_id,
+        None => return Ok(()),
+    };
+
+    let (current, history) = {
+        let presence = ctx.data().steam_presence.read().await;
+        (
+            presence.playing.get(&steam_id).cloned(),
+            presence.history.get(&steam_id).cloned().unwrap_or_default(),
+        )
+    };
+    if current.is_none() && history.is_empty() {
+        poise::say_reply(
+            ctx,
+            "No sessions recorded for that account. Sessions are only recorded for linked accounts with public game details.",
+        )
+        .await?;
+        return Ok(());
+    }
+
+    // for the name and avatar
+    if !check_quota(ctx, Api::Steam, 1).await? {
+        return Ok(());
+    }
+    let player = player_summary(&api_key, &steam_id).await?;
+
+    let recent = current
+        .iter()
+        .chain(history.iter().rev())
+        .take(SHOWN_SESSIONS)
+        .map(|session| match session.ended_at {
+            Some(ended_at) => format!(
+                "**{}** — {}, <t:{}:R>",
+                session.game,
+                format_hours(((ended_at - session.started_at) / 60) as u32),
+                session.started_at
+            ),
+            None => format!("**{}** — playing since <t:{}:R>", session.game, session.started_at),
+        })
+        .collect::<Vec<String>>();
+
+    // the total time of every recorded session, per game
+    let mut totals = HashMap::<&str, i64>::new();
+    for session in &history {
+        let seconds = session.ended_at.unwrap_or(session.started_at) - session.started_at;
+        *totals.entry(session.game.as_str()).or_insert(0) += seconds;
+    }
+    let mut totals = totals.into_iter().collect::<Vec<(&str, i64)>>();
+    totals.sort_by(|a, b| b.1.cmp(&a.1));
+    let totals = totals
+        .iter()
+        .take(SHOWN_GAMES)
+        .map(|(game, seconds)| format!("**{}** — {}", game, format_hours((seconds / 60) as u32)))
+        .collect::<Vec<String>>();
+
+    poise::send_reply(ctx, |message| {
+        message.embed(|embed| {
+            match &player {
+                Some(player) => {
+                    embed.title(format!("{}'s play sessions", player.persona_name)).url(&player.profile_url);
+                    embed.thumbnail(&player.avatar);
+                }
+                None => {
+                    embed.title(format!("{}'s play sessions", steam_id));
+                }
+            }
+            embed.colour(serenity::Colour::from_rgb(0, 0, 0));
+            embed.author(|author| {
+                if let Some(icon_url) = ctx.author().avatar_url() {
+                    author.icon_url(icon_url);
+                } else {
+                    author.icon_url(ctx.author().default_avatar_url());
+                }
+                author.name(&ctx.author().name);
+                author
+            });
+
+            embed.field("Recent sessions", recent.join("\n"), false);
+            if !totals.is_empty() {
+                embed.field("Most played (recorded sessions)", totals.join("\n"), false);
+            }
+
+            embed.footer(|footer| {
+                if let Some(icon_url) = &ctx.discord().cache.current_user().avatar_url() {
+                    footer.icon_url(icon_url);
+                } else {
+                    footer.icon_url(ctx.discord().cache.current_user().default_avatar_url());
+                }
+                footer.text(format!("{} | Steam", ctx.discord().cache.current_user().name));
+                footer
+            });
+
+            embed.timestamp(chrono::Utc::now());
+
+            embed
+        })
+    })
+    .await?;
+
+    Ok(())
+}
+
+/// Starts checking what every linked account is playing in the background,
+/// every STEAM_PRESENCE_INTERVAL minutes (2 by default)
+pub fn start_presence_checks(
+    ctx: serenity::Context,
+    links: Arc<Store<SteamLinks>>,
+    presence: Arc<Store<SteamPresence>>,
+    usage: Arc<UsageTracker>,
+) {
+    let minutes = env::var("STEAM_PRESENCE_INTERVAL")
+        .ok()
+        .and_then(|minutes| minutes.parse::<u64>().ok())
+        .filter(|minutes| *minutes > 0)
+        .unwrap_or(DEFAULT_CHECK_INTERVAL);
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(minutes * 60));
+        loop {
+            interval.tick().await;
+            if let Err(why) = check_presence(&ctx, &links, &presence, &usage).await {
+                error!("Couldn't check what linked steam accounts are playing: {}", why);
+            }
+        }
+    });
+}
+
+async fn check_presence(
+    ctx: &serenity::Context,
+    links: &Store<SteamLinks>,
+    presence: &Store<SteamPresence>,
+    usage: &UsageTracker,
+) -> Result<(), Error> {
+    let (links, guilds) = {
+        let links = links.read().await;
+        (links.users.clone(), links.guilds.clone())
+    };
+    let mut steam_ids = links.values().cloned().collect::<Vec<String>>();
+    steam_ids.sort_unstable();
+    steam_ids.dedup();
+    if steam_ids.is_empty() {
+        return Ok(());
+    }
+
+    let api_key = api_key();
+    let mut players = Vec::new();
+    for batch in steam_ids.chunks(MAX_IDS_PER_CALL) {
+        // the commands need the quota more than this does
+        if !usage.try_use(Api::Steam, None, 1).await? {
+            warn!("The steam quota is used up, skipping the presence check.");
+            return Ok(());
+        }
+        players.extend(player_summaries(&api_key, batch).await?);
+    }
+
+    let now = chrono::Utc::now().timestamp();
+    let started = presence
+        .update_if(|presence| {
+            let mut started = Vec::new();
+            let mut changed = false;
+            for player in &players {
+                let app_id = player.game_id.as_ref().and_then(|game_id| game_id.parse::<u32>().ok());
+                let game = player.game_extra_info.clone();
+
+                let current_game = presence.playing.get(&player.steam_id).map(|session| session.game.clone());
+                if current_game == game {
+                    continue; // still playing the same thing, or still not playing
+                }
+
+                changed = true;
+
+                // the last session is over
+                if let Some(mut session) = presence.playing.remove(&player.steam_id) {
+                    session.ended_at = Some(now);
+                    let history = presence.history.entry(player.steam_id.clone()).or_default();
+                    history.push(session);
+                    if history.len() > SESSIONS_PER_ACCOUNT {
+                        let extra = history.len() - SESSIONS_PER_ACCOUNT;
+                        history.drain(..extra);
+                    }
+                }
+
+                if let Some(game) = game {
+                    let session = Session {
+                        app_id,
+                        game,
+                        started_at: now,
+                        ended_at: None,
+                    };
+                    started.push((player.steam_id.clone(), session.clone()));
+                    presence.playing.insert(player.steam_id.clone(), session);
+                }
+            }
+
+            // accounts that were unlinked aren't playing anymore as far as we know
+            let playing = presence.playing.len();
+            presence.playing.retain(|steam_id, _| steam_ids.contains(steam_id));
+            changed |= presence.playing.len() != playing;
+
+            // nothing to save when nobody started or stopped playing
+            (started, changed)
+        })
+        .await?;
+    if started.is_empty() {
+        return Ok(());
+    }
+
+    let channels = presence.read().await.channels.clone();
+    for (steam_id, session) in started {
+        let user_ids = links
+            .iter()
+            .filter(|(_, linked)| **linked == steam_id)
+            .map(|(user_id, _)| *user_id);
+        for user_id in user_ids {
+            for (guild_id, channel_id) in &channels {
+                // only post in servers the user is in
+                let member = match linked_member(ctx, &guilds, *guild_id, user_id).await {
+                    Some(member) => member,
+                    None => continue,
+                };
+                if let Err(why) = post_now_playing(ctx, *channel_id, &member, &session).await {
+                    warn!("Couldn't post what {} is playing in channel {}: {}", user_id, channel_id, why);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn post_now_playing(
+    ctx: &serenity::Context,
+    channel_id: u64,
+    member: &serenity::Member,
+    session: &Session,
+) -> Result<(), Error> {
+    let bot = ctx.cache.current_user();
+
+    serenity::ChannelId(channel_id)
+        .send_message(ctx, |message| {
+            message.embed(|embed| {
+                embed.colour(serenity::Colour::from_rgb(0, 0, 0));
+                embed.author(|author| {
+                    author.icon_url(member.user.avatar_url().unwrap_or_else(|| member.user.default_avatar_url()));
+                    author.name(member.display_name());
+                    author
+                });
+                match session.app_id {
+                    Some(app_id) => embed.description(format!(
+                        "🎮 started playing **[{}]({})**",
+                        session.game,
+                        store_url(app_id)
+                    )),
+                    None => embed.description(format!("🎮 started playing **{}**", session.game)),
+                };
+
+                embed.footer(|footer| {
+                    if let Some(icon_url) = bot.avatar_url() {
+                        footer.icon_url(icon_url);
+                    } else {
+                        footer.icon_url(bot.default_avatar_url());
+                    }
+                    footer.text(format!("{} | Steam", bot.name));
+                    footer
+                });
+
+                embed.timestamp(chrono::Utc::now());
+
+                embed
+            })
+        })
+        .await?;
+
+    Ok(())
+}
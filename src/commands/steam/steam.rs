@@ -4,12 +4,61 @@ use crate::{Context, Error};
 ///
 /// **Subcommands**
 ///
+/// Users can be given as a profile url, a steam id (SteamID64, SteamID2 or SteamID3), a vanity name,
+/// or a mention of someone who linked their account.
+/// Leave the user out to use your own linked account.
+///
 /// **user**
-/// `/steam user vanity <vanity_name>`
-/// `/steam user id <steam_id>`
+/// `/steam user [user]`
+/// *examples*
+/// `/steam user robinwalker`
+/// `/steam user 76561197972495328`
+/// `/steam user https://steamcommunity.com/id/robinwalker`
+/// `/steam user @someone`
+///
+/// **id**
+/// `/steam id [user]`
+/// Shows every form of the user's steam id.
+///
+/// **game**
+/// `/steam game <name or app id>`
 /// *examples*
-/// `/steam user vanity robinwalker`
-/// `/steam user id 76561197972495328`
+/// `/steam game portal 2`
+/// `/steam game 620`
+///
+/// **games**
+/// `/steam games [user]`
+/// Lists the most played and recently played games of a user.
+///
+/// **achievements**
+/// `/steam achievements <user> <name or app id>`
+/// Shows how many achievements of a game the user unlocked, the rarest ones and the ones left to get.
+///
+/// **workshop**
+/// `/steam workshop <link or id>`
+/// Shows a workshop item, or the items in a collection.
+///
+/// **together**
+/// `/steam together <users separated by spaces> [multiplayer]`
+/// Finds the games that everyone owns.
+///
+/// **watch**
+/// `/steam watch <name or app id> [target price] [region]`
+/// Alerts you when the game goes on sale or drops to your target price.
+/// **watches** `/steam watches` lists the games you're watching
+/// **unwatch** `/steam unwatch <number, name or app id>`
+/// **alerts** `/steam alerts [channel]` chooses where price alerts for the server go (admins only)
+///
+/// **follow** `/steam follow <name or app id> [channel]` posts a game's news and patch notes (admins only)
+/// **unfollow** `/steam unfollow <name or app id> [channel]` (admins only)
+/// **following** `/steam following` lists the games followed in the server
+///
+/// **presence** `/steam presence [channel]` posts when members start playing a game (admins only)
+/// **sessions** `/steam sessions [user]` shows the recent play sessions of a linked account
+///
+/// **link** `/steam link <profile url, steam id or vanity name>`
+/// **unlink** `/steam unlink`
+/// **linked** `/steam linked` shows everyone in the server who linked their account
 #[poise::command(prefix_command, slash_command)]
 pub async fn steam(_ctx: Context<'_>) -> Result<(), Error> {
     Ok(())
@@ -0,0 +1,216 @@
+use std::fmt;
+
+/// Every individual account's SteamID64 is this plus its account id
+const STEAM_ID64_BASE: u64 = 76561197960265728;
+
+/// A steam account, given in one of the formats people paste around
+#[derive(Debug, Clone, PartialEq)]
+pub enum Identifier {
+    SteamId(SteamId),
+    Vanity(String), // needs a call to ResolveVanityURL to get the id
+}
+
+/// The id of an individual steam account
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SteamId(pub u64);
+
+impl SteamId {
+    /// The 32 bit account id, the part of the id that's unique to the account
+    pub fn account_id(&self) -> u64 {
+        self.0 - STEAM_ID64_BASE
+    }
+
+    /// e.g. STEAM_0:0:6115800
+    pub fn steam_id2(&self) -> String {
+        format!("STEAM_0:{}:{}", self.account_id() % 2, self.account_id() / 2)
+    }
+
+    /// e.g. [U:1:12231600]
+    pub fn steam_id3(&self) -> String {
+        format!("[U:1:{}]", self.account_id())
+    }
+
+    /// The profile url that works even if the account has a vanity name
+    pub fn profile_url(&self) -> String {
+        format!("https://steamcommunity.com/profiles/{}", self.0)
+    }
+}
+
+impl fmt::Display for SteamId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Works out which format a steam account is given in.
+///
+/// Takes profile urls (`steamcommunity.com/id/<vanity name>` and `steamcommunity.com/profiles/<id>`),
+/// SteamID2 (`STEAM_0:1:6115800`), SteamID3 (`[U:1:12231601]`), SteamID64 (`76561197972497329`)
+/// and vanity names. Returns `None` if it can't be any of them.
+pub fn parse_identifier(input: &str) -> Option<Identifier> {
+    let input = input.trim();
+
+    // profile urls, with or without https:// and www.
+    let path = input
+        .trim_start_matches("https://")
+        .trim_start_matches("http://")
+        .trim_start_matches("www.");
+    if let Some(path) = path.strip_prefix("steamcommunity.com/") {
+        let path = path.split(|c| c == '?' || c == '#').next().unwrap_or("");
+        let mut segments = path.split('/');
+        return match (segments.next(), segments.next()) {
+            (Some("profiles"), Some(id)) => parse_steam_id64(id).map(Identifier::SteamId),
+            (Some("id"), Some(vanity)) => parse_vanity(vanity),
+            _ => None,
+        };
+    }
+
+    if let Some(steam_id) = parse_steam_id2(input)
+        .or_else(|| parse_steam_id3(input))
+        .or_else(|| parse_steam_id64(input))
+    {
+        return Some(Identifier::SteamId(steam_id));
+    }
+
+    parse_vanity(input)
+}
+
+/// STEAM_X:Y:Z, where the account id is Z * 2 + Y. X is the universe, which doesn't matter here.
+fn parse_steam_id2(input: &str) -> Option<SteamId> {
+    let mut parts = input.strip_prefix("STEAM_")?.split(':');
+    let _universe = parts.next()?.parse::<u8>().ok()?;
+    let y = parts.next()?.parse::<u64>().ok().filter(|y| *y <= 1)?;
+    let z = parts.next()?.parse::<u64>().ok()?;
+    if parts.next().is_some() {
+        return None;
+    }
+
+    account_id_to_steam_id(z * 2 + y)
+}
+
+/// [U:1:W] (the brackets are optional), where W is the account id
+fn parse_steam_id3(input: &str) -> Option<SteamId> {
+    let input = input.trim_start_matches('[').trim_end_matches(']');
+    let account_id = input.strip_prefix("U:1:")?.parse::<u64>().ok()?;
+
+    account_id_to_steam_id(account_id)
+}
+
+fn parse_steam_id64(input: &str) -> Option<SteamId> {
+    let id = input.parse::<u64>().ok()?;
+    if id <= STEAM_ID64_BASE || id - STEAM_ID64_BASE > u32::MAX as u64 {
+        return None;
+    }
+
+    Some(SteamId(id))
+}
+
+fn account_id_to_steam_id(account_id: u64) -> Option<SteamId> {
+    if account_id == 0 || account_id > u32::MAX as u64 {
+        return None;
+    }
+
+    Some(SteamId(STEAM_ID64_BASE + account_id))
+}
+
+/// Vanity names are letters, numbers, underscores and dashes
+fn parse_vanity(input: &str) -> Option<Identifier> {
+    let valid = !input.is_empty()
+        && input.len() <= 32
+        && input.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+
+    if valid {
+        Some(Identifier::Vanity(input.to_string()))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ID: SteamId = SteamId(76561197972497329); // account id 12231601
+
+    fn steam_id(input: &str) -> Option<Identifier> {
+        parse_identifier(input)
+    }
+
+    fn vanity(name: &str) -> Option<Identifier> {
+        Some(Identifier::Vanity(String::from(name)))
+    }
+
+    #[test]
+    fn parses_a_steam_id64() {
+        assert_eq!(steam_id("76561197972497329"), Some(Identifier::SteamId(ID)));
+        assert_eq!(steam_id("  76561197972497329\n"), Some(Identifier::SteamId(ID)));
+    }
+
+    #[test]
+    fn parses_a_steam_id2() {
+        assert_eq!(steam_id("STEAM_0:1:6115800"), Some(Identifier::SteamId(ID)));
+        // the universe doesn't matter
+        assert_eq!(steam_id("STEAM_1:1:6115800"), Some(Identifier::SteamId(ID)));
+    }
+
+    #[test]
+    fn parses_a_steam_id3() {
+        assert_eq!(steam_id("[U:1:12231601]"), Some(Identifier::SteamId(ID)));
+        assert_eq!(steam_id("U:1:12231601"), Some(Identifier::SteamId(ID)));
+    }
+
+    #[test]
+    fn parses_profile_urls() {
+        for url in &[
+            "https://steamcommunity.com/profiles/76561197972497329",
+            "https://steamcommunity.com/profiles/76561197972497329/",
+            "http://www.steamcommunity.com/profiles/76561197972497329",
+            "steamcommunity.com/profiles/76561197972497329/games?tab=all",
+        ] {
+            assert_eq!(steam_id(url), Some(Identifier::SteamId(ID)), "{}", url);
+        }
+    }
+
+    #[test]
+    fn parses_vanity_urls() {
+        assert_eq!(steam_id("https://steamcommunity.com/id/robinwalker"), vanity("robinwalker"));
+        assert_eq!(steam_id("https://steamcommunity.com/id/robinwalker/#top"), vanity("robinwalker"));
+        assert_eq!(steam_id("www.steamcommunity.com/id/robin_walker-2"), vanity("robin_walker-2"));
+    }
+
+    #[test]
+    fn parses_bare_vanity_names() {
+        assert_eq!(steam_id("robinwalker"), vanity("robinwalker"));
+        assert_eq!(steam_id("gaben_"), vanity("gaben_"));
+    }
+
+    #[test]
+    fn rejects_invalid_ids() {
+        assert_eq!(steam_id("STEAM_0:2:6115800"), None); // Y is 0 or 1
+        assert_eq!(steam_id("STEAM_0:1:6115800:5"), None);
+        assert_eq!(steam_id("STEAM_0:1"), None);
+        assert_eq!(steam_id("[U:1:0]"), None);
+        assert_eq!(steam_id("[U:1:99999999999]"), None); // bigger than 32 bits
+        assert_eq!(steam_id("https://steamcommunity.com/profiles/76561197960265728"), None); // the base isn't an account
+        assert_eq!(steam_id("https://steamcommunity.com/profiles/robinwalker"), None);
+    }
+
+    #[test]
+    fn rejects_other_urls_and_names() {
+        assert_eq!(steam_id(""), None);
+        assert_eq!(steam_id("   "), None);
+        assert_eq!(steam_id("https://steamcommunity.com/groups/valve"), None);
+        assert_eq!(steam_id("https://steamcommunity.com/id/"), None);
+        assert_eq!(steam_id("https://store.steampowered.com/app/620"), None);
+        assert_eq!(steam_id("robin walker"), None);
+        assert_eq!(steam_id(&"a".repeat(33)), None);
+    }
+
+    #[test]
+    fn formats_the_other_ids() {
+        assert_eq!(ID.account_id(), 12231601);
+        assert_eq!(ID.steam_id2(), "STEAM_0:1:6115800");
+        assert_eq!(ID.steam_id3(), "[U:1:12231601]");
+        assert_eq!(ID.profile_url(), "https://steamcommunity.com/profiles/76561197972497329");
+    }
+}
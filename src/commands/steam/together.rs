@@ -0,0 +1,245 @@
+use crate::{Context, Error};
+use std::collections::HashMap;
+
+use log::warn;
+use poise::serenity_prelude as serenity;
+
+use super::api::{api_key, format_hours, owned_games, player_summaries};
+use super::links::resolve_account;
+use super::apps::app_categories;
+use crate::helpers::{check_quota, Api};
+
+const MAX_USERS: usize = 8;
+const SHOWN_GAMES: usize = 15;
+const MAX_GAME_LIST_LENGTH: usize = 950; // embed fields can only be 1024 characters long
+const MULTIPLAYER_CHECKS: usize = 30; // the store api is rate limited, so only check the most played games
+const MULTIPLAYER_CATEGORIES: [&str; 8] = [
+    "Multi-player",
+    "Online PvP",
+    "LAN PvP",
+    "Online Co-op",
+    "LAN Co-op",
+    "Co-op",
+    "Shared/Split Screen",
+    "Cross-Platform Multiplayer",
+];
+
+/// Find the games that a group of steam users all own
+///
+/// Games are sorted by how long the group played them in total.
+/// Profiles with private game details are left out, and listed at the bottom.
+///
+/// **Usage:**
+/// `/steam together <users separated by spaces> [multiplayer]`
+/// Users are profile urls, steam ids, vanity names or mentions of people who linked their account.
+///
+/// **Examples:**
+/// `/steam together @someone robinwalker`
+/// `&steam together multiplayer robinwalker 76561197972495328`
+#[poise::command(
+    prefix_command,
+    slash_command,
+    track_edits,
+    defer_response,
+    broadcast_typing
+)]
+pub async fn together(
+    ctx: Context<'_>,
+    #[description = "Only show multiplayer games"]
+    #[flag] multiplayer: bool,
+    #[description = "Profile urls, steam ids, vanity names or mentions, separated by spaces"]
+    #[rest] users: String,
+) -> Result<(), Error> {
+    let mut users = users.split_whitespace().map(|user| user.to_string()).collect::<Vec<String>>();
+    users.sort_unstable();
+    users.dedup();
+    if users.len() < 2 || users.len() > MAX_USERS {
+        poise::say_reply(ctx, format!("Give me between 2 and {} users.", MAX_USERS)).await?;
+        return Ok(());
+    }
+
+    let api_key = api_key();
+
+    let mut steam_ids = Vec::new();
+    let mut not_found = Vec::new();
+    for user in &users {
+        match resolve_account(ctx, &api_key, Some(user)).await? {
+            Ok(steam_id) if !steam_ids.contains(&steam_id) => steam_ids.push(steam_id),
+            Ok(_) => (), // the same user twice
+            Err(reason) => not_found.push(reason),
+        }
+    }
+    if !not_found.is_empty() {
+        poise::say_reply(ctx, not_found.join("\n")).await?;
+        return Ok(());
+    }
+
+    // everyone's profiles and games
+    if !check_quota(ctx, Api::Steam, steam_ids.len() as u32 + 1).await? {
+        return Ok(());
+    }
+
+    let names = player_summaries(&api_key, &steam_ids)
+        .await?
+        .into_iter()
+        .map(|player| (player.steam_id, player.persona_name))
+        .collect::<HashMap<String, String>>();
+    let name = |steam_id: &String| names.get(steam_id).cloned().unwrap_or_else(|| steam_id.clone());
+
+    // get everyone's games at the same time
+    let handles = steam_ids
+        .iter()
+        .map(|steam_id| {
+            let api_key = api_key.clone();
+            let steam_id = steam_id.clone();
+            tokio::spawn(async move { owned_games(&api_key, &steam_id).await.map_err(|why| why.to_string()) })
+        })
+        .collect::<Vec<_>>();
+
+    let mut libraries = Vec::new();
+    let mut players = Vec::new();
+    let mut private = Vec::new();
+    let mut failed = Vec::new();
+    for (steam_id, handle) in steam_ids.iter().zip(handles) {
+        match handle.await? {
+            Ok(Some(games)) => {
+                libraries.push(games);
+                players.push(name(steam_id));
+            }
+            Ok(None) => private.push(name(steam_id)),
+            // one library that couldn't be fetched shouldn't spoil it for everyone else
+            Err(why) => {
+                warn!("Couldn't get the games of {}: {}", steam_id, why);
+                failed.push(name(steam_id));
+            }
+        }
+    }
+
+    if libraries.len() < 2 {
+        let mut text = String::from("Not enough public game libraries to compare.");
+        if !private.is_empty() {
+            text.push_str(&format!(" Private profiles: {}.", private.join(", ")));
+        }
+        if !failed.is_empty() {
+            text.push_str(&format!(" Couldn't get the games of: {}.", failed.join(", ")));
+        }
+        poise::say_reply(ctx, text).await?;
+        return Ok(());
+    }
+
+    // app id -> (name, combined playtime, how many libraries have it)
+    let mut common = HashMap::<u32, (String, u32, usize)>::new();
+    for library in &libraries {
+        for game in library {
+            let entry = common.entry(game.app_id).or_insert_with(|| (game.name.clone(), 0, 0));
+            entry.1 += game.playtime_forever;
+            entry.2 += 1;
+        }
+    }
+    let mut common = common
+        .into_iter()
+        .filter(|(_, (_, _, owners))| *owners == libraries.len())
+        .map(|(app_id, (name, playtime, _))| (app_id, name, playtime))
+        .collect::<Vec<(u32, String, u32)>>();
+    common.sort_by(|a, b| b.2.cmp(&a.2));
+
+    // the store is the only place that knows if a game is multiplayer
+    let mut checked_only = None;
+    if multiplayer {
+        if common.len() > MULTIPLAYER_CHECKS {
+            checked_only = Some(MULTIPLAYER_CHECKS);
+            common.truncate(MULTIPLAYER_CHECKS);
+        }
+
+        let handles = common
+            .iter()
+            .map(|(app_id, _, _)| tokio::spawn(app_categories(*app_id)))
+            .collect::<Vec<_>>();
+        let mut multiplayer_games = Vec::new();
+        for (game, handle) in common.into_iter().zip(handles) {
+            // a game we couldn't check is left out
+            let categories = handle.await?.unwrap_or_default();
+            if categories
+                .iter()
+                .any(|category| MULTIPLAYER_CATEGORIES.contains(&category.as_str()))
+            {
+                multiplayer_games.push(game);
+            }
+        }
+        common = multiplayer_games;
+    }
+
+    let game_list = game_list(&common);
+
+    poise::send_reply(ctx, |message| {
+        message.embed(|embed| {
+            embed.title(if multiplayer {
+                "Multiplayer games you can play together"
+            } else {
+                "Games you can play together"
+            });
+            embed.colour(serenity::Colour::from_rgb(0, 0, 0));
+            embed.author(|author| {
+                if let Some(icon_url) = ctx.author().avatar_url() {
+                    author.icon_url(icon_url);
+                } else {
+                    author.icon_url(ctx.author().default_avatar_url());
+                }
+                author.name(&ctx.author().name);
+                author
+            });
+
+            let mut description = format!("**{}** have {} games in common", players.join(", "), common.len());
+            if let Some(checked) = checked_only {
+                description.push_str(&format!(" (checked the {} most played)", checked));
+            }
+            embed.description(description);
+
+            if game_list.is_empty() {
+                embed.field("Games", "Nothing in common 😔", false);
+            } else {
+                embed.field("Games", game_list, false);
+            }
+
+            if !private.is_empty() {
+                embed.field("🔒 Private game details, left out", private.join(", "), false);
+            }
+            if !failed.is_empty() {
+                embed.field("⚠️ Couldn't get the games, left out", failed.join(", "), false);
+            }
+
+            embed.footer(|footer| {
+                if let Some(icon_url) = &ctx.discord().cache.current_user().avatar_url() {
+                    footer.icon_url(icon_url);
+                } else {
+                    footer.icon_url(ctx.discord().cache.current_user().default_avatar_url());
+                }
+                footer.text(format!("{} | Steam", ctx.discord().cache.current_user().name));
+                footer
+            });
+
+            embed.timestamp(chrono::Utc::now());
+
+            embed
+        })
+    })
+    .await?;
+
+    Ok(())
+}
+
+/// The most played games in common, as long as they fit in an embed field
+fn game_list(games: &[(u32, String, u32)]) -> String {
+    let mut list = String::new();
+    for (shown, (_, name, playtime)) in games.iter().enumerate() {
+        let line = format!("**{}** — {} combined\n", name, format_hours(*playtime));
+
+        if shown == SHOWN_GAMES || list.len() + line.len() > MAX_GAME_LIST_LENGTH {
+            list.push_str(&format!("...and {} more", games.len() - shown));
+            break;
+        }
+        list.push_str(&line);
+    }
+
+    list
+}
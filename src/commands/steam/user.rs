@@ -1,10 +1,18 @@
 use crate::{Context, Error};
-use std::env;
+use crate::helpers::{check_quota, Api};
 
 use poise::serenity_prelude as serenity;
-use serde::Deserialize;
+
+use super::api::{api_key, owned_game_count, player_bans, player_summary, steam_level};
+use super::links::steam_id_for;
+
+const UNAVAILABLE: &str = "Unavailable"; // shown for the parts of the profile steam didn't give us
 
 /// Look up information about a steam user
+///
+/// Give a profile url, a steam id, a vanity name or mention someone who linked their account.
+/// Parts of the profile that steam doesn't send in time are shown as unavailable.
+/// Leave it empty to look up your own linked account.
 #[poise::command(
     prefix_command,
     slash_command,
@@ -14,88 +22,49 @@ use serde::Deserialize;
 )]
 pub async fn user(
     ctx: Context<'_>,
-    #[description = "Type of the argument"] arg_type: ArgType,
-    #[description = "The vanity name of the user"] arg: String,
+    #[description = "A profile url, steam id, vanity name or mention. Leave empty for yourself"] account: Option<String>,
 ) -> Result<(), Error> {
     // get steam api key from the environment vars
-    let api_key = env::var("STEAM_API_KEY").expect("Expected STEAM_API_KEY environment variable.");
-
-    let url = format!(
-        "https://api.steampowered.com/ISteamUser/ResolveVanityURL/v0001/?key={}&vanityurl={}",
-        api_key, arg
-    );
+    let api_key = api_key();
 
-    // get the steam_id. either directly from the user, or from the api using the vanity name
-    let steam_id = match arg_type {
-        ArgType::Id => Some(arg.clone()),
-        ArgType::Vanity => {
-            // get the steam_id using the vanity name
-            reqwest::get(url)
-                .await?
-                .json::<SteamIDResponse>()
-                .await?
-                .response
-                .steam_id
-        }
+    // get the steam_id. either directly from the user, from a linked account, or from the api using the vanity name
+    let steam_id = match steam_id_for(ctx, &api_key, account.as_deref()).await? {
+        Some(steam_id) => steam_id,
+        None => return Ok(()),
     };
 
-    if let Some(steam_id) = steam_id {
-        // if the user has been found, getappids_filter  their info
-        let player_summary_url = format!(
-            "http://api.steampowered.com/ISteamUser/GetPlayerSummaries/v0002/?key={}&steamids={}",
-            api_key, steam_id
-        );
-        let owned_games_url = format!(
-            "http://api.steampowered.com/IPlayerService/GetOwnedGames/v0001/?key={}&steamid={}&format=json",
-            api_key, steam_id
-        );
-        let steam_level_url = format!(
-            "http://api.steampowered.com/IPlayerService/GetSteamLevel/v1/?key={}&steamid={}&format=json",
-            api_key, steam_id
-        );
-        let vac_bans_url = format!(
-            "http://api.steampowered.com/ISteamUser/GetPlayerBans/v1?key={}&steamids={}",
-            api_key, steam_id
-        );
-
-        // get information about the supplied user
-        let player_summary = &reqwest::get(player_summary_url)
-            .await?
-            .json::<PlayerSummaryResponse>()
-            .await?
-            .response
-            .players[0];
-
-        // get the number of games that the user owns
-        let game_count = reqwest::get(owned_games_url)
-            .await?
-            .json::<OwnedGamesResponse>()
-            .await?
-            .response
-            .game_count;
+    // 4 calls for the profile
+    if !check_quota(ctx, Api::Steam, 4).await? {
+        return Ok(());
+    }
 
-        // get the account level of the user
-        let steam_level = reqwest::get(steam_level_url)
-            .await?
-            .json::<SteamLevelResponse>()
-            .await?
-            .response
-            .player_level;
+    // get everything at the same time. only the summary has to work, the rest is shown as unavailable if it doesn't
+    let (player_summary, steam_level, bans, game_count) = tokio::join!(
+        player_summary(&api_key, &steam_id),
+        steam_level(&api_key, &steam_id),
+        player_bans(&api_key, &steam_id),
+        owned_game_count(&api_key, &steam_id),
+    );
 
-        // get information on VAC bans on the user
-        let vac_bans = &reqwest::get(vac_bans_url)
-            .await?
-            .json::<VACBansResponse>()
-            .await?
-            .players[0];
+    let player_summary = match player_summary {
+        Ok(player_summary) => player_summary,
+        Err(why) => {
+            log::warn!("Couldn't get the steam profile of {}: {}", steam_id, why);
+            poise::say_reply(ctx, "Couldn't reach steam right now, try again in a bit.").await?;
+            return Ok(());
+        }
+    };
 
+    if let Some(player_summary) = &player_summary {
         // send the embed
         poise::send_reply(ctx, |message| {
             message
                 .embed(|embed| {
-                    embed
-                        .title(format!("{} [{}]", player_summary.persona_name, steam_level))
-                        .url(&player_summary.profile_url);
+                    match &steam_level {
+                        Ok(Some(level)) => embed.title(format!("{} [{}]", player_summary.persona_name, level)),
+                        _ => embed.title(&player_summary.persona_name),
+                    };
+                    embed.url(&player_summary.profile_url);
                     embed.thumbnail(&player_summary.avatar);
                     embed.colour(serenity::Colour::from_rgb(0, 0, 0));
                     embed.author(|author| {
@@ -131,16 +100,26 @@ pub async fn user(
                         embed.field("Account privacy", "Public", false);
                     }
 
+                    if steam_level.is_err() {
+                        embed.field("Steam level", UNAVAILABLE, true);
+                    }
+
                     // If user has any VAC bans, show that
-                    if vac_bans.vac_banned {
-                        embed.field(
-                            "🚫 VAC bans",
-                            format!(
-                                "**{}** ({} days ago)",
-                                vac_bans.number_of_bans, vac_bans.days_since_last_ban
-                            ),
-                            false,
-                        );
+                    match &bans {
+                        Ok(Some(bans)) if bans.vac_banned => {
+                            embed.field(
+                                "🚫 VAC bans",
+                                format!(
+                                    "**{}** ({} days ago)",
+                                    bans.number_of_vac_bans, bans.days_since_last_ban
+                                ),
+                                false,
+                            );
+                        }
+                        Ok(_) => (),
+                        Err(_) => {
+                            embed.field("VAC bans", UNAVAILABLE, false);
+                        }
                     }
 
                     embed.field("Status", get_status(&player_summary.persona_state), false);
@@ -150,9 +129,11 @@ pub async fn user(
                         embed.field("Currently playing", game_extra_info, false);
                     }
 
-                    if let Some(game_count) = game_count {
-                        embed.field("Owned games", game_count, false);
-                    }
+                    match &game_count {
+                        Ok(Some(game_count)) => embed.field("Owned games", game_count, false),
+                        Ok(None) => embed.field("Owned games", "🔒 Private", false),
+                        Err(_) => embed.field("Owned games", UNAVAILABLE, false),
+                    };
 
                     embed.footer(|footer| {
                         if let Some(icon_url) = &ctx.discord().cache.current_user().avatar_url() {
@@ -186,24 +167,12 @@ pub async fn user(
         .await?;
     } else {
         // else, send an error message
-        if let ArgType::Id = arg_type {
-            poise::say_reply(ctx, format!("User with steam id \"{}\" not found.", arg)).await?;
-        } else {
-            poise::say_reply(ctx, format!("User with vanity name \"{}\" not found.", arg)).await?;
-        }
+        poise::say_reply(ctx, format!("User with steam id \"{}\" not found.", steam_id)).await?;
     }
 
     Ok(())
 }
 
-#[derive(Debug, poise::SlashChoiceParameter)]
-pub enum ArgType {
-    #[name = "vanity"]
-    Vanity,
-    #[name = "id"]
-    Id,
-}
-
 /// Takes a persona_state u8 and returns the corresponding
 /// user status (online, offline, busy, etc)
 fn get_status(persona_state: &u8) -> String {
@@ -218,91 +187,3 @@ fn get_status(persona_state: &u8) -> String {
         _ => String::from("⚪ Unknown"),
     }
 }
-
-#[derive(Debug, Deserialize)]
-struct SteamIDResponse {
-    response: SteamID,
-}
-
-#[derive(Debug, Deserialize)]
-struct SteamID {
-    #[serde(rename = "steamid")]
-    steam_id: Option<String>,
-    success: i32,
-    message: Option<String>,
-}
-
-#[derive(Debug, Deserialize)]
-struct PlayerSummaryResponse {
-    response: Players,
-}
-
-#[derive(Debug, Deserialize)]
-struct Players {
-    players: Vec<PlayerSummary>,
-}
-
-#[derive(Debug, Deserialize)]
-struct PlayerSummary {
-    #[serde(rename = "steamid")]
-    steam_id: String, // the unique steam_id of the user [public]
-
-    #[serde(rename = "personaname")]
-    persona_name: String, // the displayed name or username (not unique on steam) [public]
-
-    #[serde(rename = "avatarfull")]
-    avatar: String, // the url for the steam avatar of the user [public]
-
-    #[serde(rename = "lastlogoff")]
-    last_logoff: Option<i64>, // the last date of logoff of the user [public]
-
-    #[serde(rename = "timecreated")]
-    time_created: Option<i64>, // the time at which the user's account was created [private]
-
-    #[serde(rename = "communityvisibilitystate")]
-    community_visibility_state: u8, // 1 if the user's profile is private, 3 if it's public [public]
-
-    #[serde(rename = "profileurl")]
-    profile_url: String, // the complete steam url of the user's profile [public]
-
-    #[serde(rename = "gameextrainfo")]
-    game_extra_info: Option<String>, // the name of the game that the user is currently playing [private]
-
-    #[serde(rename = "personastate")]
-    persona_state: u8,
-}
-
-#[derive(Debug, Deserialize)]
-struct OwnedGamesResponse {
-    response: GameCount,
-}
-
-#[derive(Debug, Deserialize)]
-struct GameCount {
-    game_count: Option<u32>,
-}
-
-#[derive(Debug, Deserialize)]
-struct SteamLevelResponse {
-    response: SteamLevel,
-}
-
-#[derive(Debug, Deserialize)]
-struct SteamLevel {
-    player_level: u8,
-}
-
-#[derive(Debug, Deserialize)]
-struct VACBansResponse {
-    players: Vec<VacBans>,
-}
-
-#[derive(Debug, Deserialize)]
-struct VacBans {
-    #[serde(rename = "VACBanned")]
-    vac_banned: bool,
-    #[serde(rename = "NumberOfVACBans")]
-    number_of_bans: u8,
-    #[serde(rename = "DaysSinceLastBan")]
-    days_since_last_ban: u16,
-}
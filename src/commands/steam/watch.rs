@@ -0,0 +1,534 @@
+use crate::{Context, Error};
+use std::{collections::HashMap, env, sync::Arc, time::Duration};
+
+use log::{error, warn};
+use poise::serenity_prelude as serenity;
+use serde::{Deserialize, Serialize};
+
+use super::apps::{app_details, app_prices, region as default_region, store_url, PriceOverview};
+use super::game::app_id_for;
+use crate::helpers::Store;
+
+const MAX_WATCHES: usize = 25; // per user, so the list fits in one embed
+const DEFAULT_CHECK_INTERVAL: u64 = 60; // minutes between price checks, unless STEAM_WATCH_INTERVAL says otherwise
+const PRICE_BATCH_SIZE: usize = 50; // apps per store request
+const BATCH_DELAY: Duration = Duration::from_secs(2); // the store only allows a couple hundred requests every few minutes
+
+/// Every price watch, and where each server wants its alerts. Saved in steam_watches.json
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SteamWatches {
+    pub watches: Vec<PriceWatch>,
+    pub alert_channels: HashMap<u64, u64>, // alert channel ids, keyed by guild id
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PriceWatch {
+    pub user_id: u64,
+    pub guild_id: Option<u64>, // the server the watch was made in. alerts go to DMs without one
+    pub app_id: u32,
+    pub name: String,
+    pub region: String,           // the country the price is checked in, e.g. "US"
+    pub target: Option<u32>,      // in cents, in the currency of the region
+    pub currency: Option<String>, // e.g. "USD"
+    pub last_price: Option<u32>,  // in cents, None when it's not for sale
+    pub last_price_formatted: Option<String>,
+    #[serde(default)]
+    pub last_discount: u32,
+    #[serde(default)]
+    pub alerted: bool, // so the same sale doesn't get announced on every check
+}
+
+impl PriceWatch {
+    /// Whether a price is worth telling the user about: at or below their target, or on sale
+    fn is_deal(&self, price: &PriceOverview) -> bool {
+        self.target.map_or(false, |target| price.final_price <= target) || price.discount_percent > 0
+    }
+
+    /// Remembers the latest price. Returns true if the user should be alerted about it,
+    /// which is when it becomes a deal, or when a deal gets even cheaper.
+    fn update_price(&mut self, price: Option<&PriceOverview>) -> bool {
+        let price = match price {
+            Some(price) => price,
+            None => {
+                self.last_price = None;
+                self.last_price_formatted = None;
+                self.last_discount = 0;
+                self.alerted = false;
+                return false;
+            }
+        };
+
+        let cheaper = self.last_price.map_or(false, |last_price| price.final_price < last_price);
+        let is_deal = self.is_deal(price);
+        let alert = is_deal && (!self.alerted || cheaper);
+
+        self.alerted = is_deal;
+        self.currency = Some(price.currency.clone());
+        self.last_price = Some(price.final_price);
+        self.last_price_formatted = Some(price.final_formatted.clone());
+        self.last_discount = price.discount_percent;
+
+        alert
+    }
+
+    fn target_formatted(&self) -> Option<String> {
+        let target = self.target?;
+        Some(match &self.currency {
+            Some(currency) => format!("{}.{:02} {}", target / 100, target % 100, currency),
+            None => format!("{}.{:02}", target / 100, target % 100),
+        })
+    }
+}
+
+/// Get told when a game goes on sale
+///
+/// The bot checks the store every so often, and sends you a DM (or posts in the server's alert channel)
+/// when the game goes on sale or drops to your target price.
+///
+/// **Usage:**
+/// `/steam watch <name or app id> [target price] [region]`
+/// The target price is in the currency of the region. The region is a country code like US or DE.
+/// As a prefix command, put names with spaces in quotes, or the rest of the name is taken as the target price.
+///
+/// **Examples:**
+/// `/steam watch "portal 2"`
+/// `/steam watch 620 4.99`
+/// `&steam watch "portal 2" 4.99 DE`
+#[poise::command(prefix_command, slash_command, track_edits, defer_response)]
+pub async fn watch(
+    ctx: Context<'_>,
+    #[description = "The name or app id of the game"] game: String,
+    #[description = "Alert me when it costs this much or less"] target: Option<f64>,
+    #[description = "The country to check the price in, e.g. US or DE"] region: Option<String>,
+) -> Result<(), Error> {
+    let region = match region {
+        Some(region) if region.len() == 2 && region.chars().all(|c| c.is_ascii_alphabetic()) => {
+            region.to_ascii_uppercase()
+        }
+        Some(region) => {
+            poise::say_reply(ctx, format!("\"{}\" isn't a country code. Try something like US or DE.", region)).await?;
+            return Ok(());
+        }
+        None => default_region(),
+    };
+
+    let target = match target {
+        Some(target) if target.is_finite() && target >= 0.0 => Some((target * 100.0).round() as u32),
+        Some(_) => {
+            poise::say_reply(ctx, "The target price has to be a positive number.").await?;
+            return Ok(());
+        }
+        None => None,
+    };
+
+    let app_id = match app_id_for(ctx, &game).await? {
+        Some(app_id) => app_id,
+        None => return Ok(()),
+    };
+
+    let details = match app_details(app_id, &region).await? {
+        Some(details) => details,
+        None => {
+            poise::say_reply(ctx, format!("App {} doesn't have a store page.", app_id)).await?;
+            return Ok(());
+        }
+    };
+    if details.is_free {
+        poise::say_reply(ctx, format!("**{}** is free, no need to wait for a sale 🎉", details.name)).await?;
+        return Ok(());
+    }
+
+    let mut watch = PriceWatch {
+        user_id: ctx.author().id.0,
+        guild_id: ctx.guild_id().map(|guild_id| guild_id.0),
+        app_id,
+        name: details.name.clone(),
+        region: region.clone(),
+        target,
+        currency: None,
+        last_price: None,
+        last_price_formatted: None,
+        last_discount: 0,
+        alerted: false,
+    };
+    // a deal that's already going on is mentioned now, not in an alert later
+    let deal_now = watch.update_price(details.price_overview.as_ref());
+
+    let user_id = watch.user_id;
+    let added = ctx
+        .data()
+        .steam_watches
+        .update(|watches| {
+            // watching a game again changes the target and region of the old watch
+            if let Some(existing) = watches
+                .watches
+                .iter_mut()
+                .find(|existing| existing.user_id == user_id && existing.app_id == app_id)
+            {
+                *existing = watch.clone();
+                return Ok(false);
+            }
+
+            if watches.watches.iter().filter(|watch| watch.user_id == user_id).count() >= MAX_WATCHES {
+                return Err(format!(
+                    "You can only watch {} games at a time. Remove some with `/steam unwatch`.",
+                    MAX_WATCHES
+                ));
+            }
+            watches.watches.push(watch.clone());
+            Ok(true)
+        })
+        .await?;
+
+    let mut reply = match added {
+        Ok(true) => format!("Watching **{}** in {}.", watch.name, watch.region),
+        Ok(false) => format!("Updated your watch on **{}** in {}.", watch.name, watch.region),
+        Err(reason) => {
+            poise::say_reply(ctx, reason).await?;
+            return Ok(());
+        }
+    };
+    match &watch.last_price_formatted {
+        Some(price) => reply.push_str(&format!(" It costs {} right now.", price)),
+        None => reply.push_str(" It isn't for sale right now."),
+    }
+    if let Some(target) = watch.target_formatted() {
+        reply.push_str(&format!(" I'll tell you when it's {} or less, or on sale.", target));
+    } else {
+        reply.push_str(" I'll tell you when it goes on sale.");
+    }
+    if deal_now {
+        reply.push_str(" It's already a deal, go get it!");
+    }
+
+    poise::say_reply(ctx, reply).await?;
+
+    Ok(())
+}
+
+/// Show the games you're watching
+#[poise::command(prefix_command, slash_command)]
+pub async fn watches(ctx: Context<'_>) -> Result<(), Error> {
+    let user_id = ctx.author().id.0;
+    let (watches, alert_channel) = {
+        let steam_watches = ctx.data().steam_watches.read().await;
+        let watches = steam_watches
+            .watches
+            .iter()
+            .filter(|watch| watch.user_id == user_id)
+            .cloned()
+            .collect::<Vec<PriceWatch>>();
+        let alert_channel = ctx
+            .guild_id()
+            .and_then(|guild_id| steam_watches.alert_channels.get(&guild_id.0).copied());
+        (watches, alert_channel)
+    };
+
+    if watches.is_empty() {
+        poise::say_reply(ctx, "You aren't watching any games. Add one with `/steam watch`.").await?;
+        return Ok(());
+    }
+
+    let lines = watches
+        .iter()
+        .enumerate()
+        .map(|(index, watch)| {
+            let mut line = format!(
+                "`{}.` [{}]({}) ({}) — {}",
+                index + 1,
+                watch.name,
+                store_url(watch.app_id),
+                watch.region,
+                watch.last_price_formatted.as_deref().unwrap_or("not for sale")
+            );
+            if watch.last_discount > 0 {
+                line.push_str(&format!(" (-{}%)", watch.last_discount));
+            }
+            if let Some(target) = watch.target_formatted() {
+                line.push_str(&format!(", target {}", target));
+            }
+            line
+        })
+        .collect::<Vec<String>>();
+
+    poise::send_reply(ctx, |message| {
+        message.embed(|embed| {
+            embed.title("Your watched games");
+            embed.colour(serenity::Colour::from_rgb(0, 0, 0));
+            embed.author(|author| {
+                if let Some(icon_url) = ctx.author().avatar_url() {
+                    author.icon_url(icon_url);
+                } else {
+                    author.icon_url(ctx.author().default_avatar_url());
+                }
+                author.name(&ctx.author().name);
+                author
+            });
+            embed.description(lines.join("\n"));
+
+            match alert_channel {
+                Some(channel_id) => embed.field("Alerts", format!("Posted in <#{}>", channel_id), false),
+                None => embed.field("Alerts", "Sent to your DMs", false),
+            };
+
+            embed.footer(|footer| {
+                if let Some(icon_url) = &ctx.discord().cache.current_user().avatar_url() {
+                    footer.icon_url(icon_url);
+                } else {
+                    footer.icon_url(ctx.discord().cache.current_user().default_avatar_url());
+                }
+                footer.text(format!(
+                    "{} | Steam | Remove a game with /steam unwatch <number>",
+                    ctx.discord().cache.current_user().name
+                ));
+                footer
+            });
+
+            embed.timestamp(chrono::Utc::now());
+
+            embed
+        })
+    })
+    .await?;
+
+    Ok(())
+}
+
+/// Stop watching a game
+///
+/// **Usage:**
+/// `/steam unwatch <number from /steam watches, name or app id>`
+#[poise::command(prefix_command, slash_command)]
+pub async fn unwatch(
+    ctx: Context<'_>,
+    #[description = "The number from /steam watches, or the name or app id of the game"]
+    #[rest]
+    game: String,
+) -> Result<(), Error> {
+    let game = game.trim().to_string();
+    let user_id = ctx.author().id.0;
+
+    let removed = ctx
+        .data()
+        .steam_watches
+        .update(|watches| {
+            let own = watches
+                .watches
+                .iter()
+                .enumerate()
+                .filter(|(_, watch)| watch.user_id == user_id)
+                .map(|(index, _)| index)
+                .collect::<Vec<usize>>();
+
+            // a number from the list, then an app id, then a name
+            let index = match game.parse::<usize>() {
+                Ok(number) if number >= 1 && number <= own.len() => Some(own[number - 1]),
+                _ => own.into_iter().find(|index| {
+                    let watch = &watches.watches[*index];
+                    watch.app_id.to_string() == game || watch.name.eq_ignore_ascii_case(&game)
+                }),
+            };
+            index.map(|index| watches.watches.remove(index))
+        })
+        .await?;
+
+    match removed {
+        Some(watch) => poise::say_reply(ctx, format!("Stopped watching **{}**.", watch.name)).await?,
+        None => poise::say_reply(ctx, format!("You aren't watching \"{}\". See `/steam watches`.", game)).await?,
+    };
+
+    Ok(())
+}
+
+/// Choose where price alerts for this server go (admins only)
+///
+/// Without a channel, alerts go to the DMs of whoever watches the game.
+///
+/// **Usage:**
+/// `/steam alerts [channel]`
+#[poise::command(prefix_command, slash_command, required_permissions = "MANAGE_GUILD")]
+pub async fn alerts(
+    ctx: Context<'_>,
+    #[description = "The channel to post price alerts in. Leave empty to send them as DMs"] channel: Option<
+        serenity::GuildChannel,
+    >,
+) -> Result<(), Error> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id.0,
+        None => {
+            poise::say_reply(ctx, "This only works in a server.").await?;
+            return Ok(());
+        }
+    };
+
+    match channel {
+        Some(channel) => {
+            if channel.guild_id.0 != guild_id {
+                poise::say_reply(ctx, "That channel isn't in this server.").await?;
+                return Ok(());
+            }
+            ctx.data()
+                .steam_watches
+                .update(|watches| watches.alert_channels.insert(guild_id, channel.id.0))
+                .await?;
+            poise::say_reply(ctx, format!("Price alerts for this server now go to <#{}>.", channel.id.0)).await?;
+        }
+        None => {
+            ctx.data()
+                .steam_watches
+                .update(|watches| watches.alert_channels.remove(&guild_id))
+                .await?;
+            poise::say_reply(ctx, "Price alerts for this server now go to DMs.").await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Starts checking the prices of every watched game in the background,
+/// every STEAM_WATCH_INTERVAL minutes (60 by default)
+pub fn start_price_checks(ctx: serenity::Context, watches: Arc<Store<SteamWatches>>) {
+    let minutes = env::var("STEAM_WATCH_INTERVAL")
+        .ok()
+        .and_then(|minutes| minutes.parse::<u64>().ok())
+        .filter(|minutes| *minutes > 0)
+        .unwrap_or(DEFAULT_CHECK_INTERVAL);
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(minutes * 60));
+        loop {
+            interval.tick().await;
+            if let Err(why) = check_prices(&ctx, &watches).await {
+                error!("Couldn't check the prices of watched steam games: {}", why);
+            }
+        }
+    });
+}
+
+struct Alert {
+    watch: PriceWatch,
+    price: PriceOverview,
+    channel_id: Option<u64>, // None for DMs
+}
+
+async fn check_prices(ctx: &serenity::Context, watches: &Store<SteamWatches>) -> Result<(), Error> {
+    // the apps to check in every region, each only once
+    let mut apps = HashMap::<String, Vec<u32>>::new();
+    for watch in &watches.read().await.watches {
+        let app_ids = apps.entry(watch.region.clone()).or_default();
+        if !app_ids.contains(&watch.app_id) {
+            app_ids.push(watch.app_id);
+        }
+    }
+
+    let mut prices = HashMap::<(String, u32), Option<PriceOverview>>::new();
+    for (region, app_ids) in apps {
+        for batch in app_ids.chunks(PRICE_BATCH_SIZE) {
+            match app_prices(batch, &region).await {
+                Ok(batch_prices) => {
+                    for (app_id, price) in batch_prices {
+                        prices.insert((region.clone(), app_id), price);
+                    }
+                }
+                // try these again on the next check
+                Err(why) => warn!("Couldn't get steam prices in {}: {}", region, why),
+            }
+            tokio::time::sleep(BATCH_DELAY).await;
+        }
+    }
+    if prices.is_empty() {
+        return Ok(());
+    }
+
+    let alerts = watches
+        .update(|watches| {
+            let mut alerts = Vec::new();
+            for watch in watches.watches.iter_mut() {
+                let price = match prices.get(&(watch.region.clone(), watch.app_id)) {
+                    Some(price) => price.as_ref(),
+                    None => continue, // wasn't checked this time
+                };
+                if watch.update_price(price) {
+                    alerts.push(Alert {
+                        watch: watch.clone(),
+                        price: price.cloned().expect("only prices can be deals"),
+                        channel_id: watch
+                            .guild_id
+                            .and_then(|guild_id| watches.alert_channels.get(&guild_id).copied()),
+                    });
+                }
+            }
+            alerts
+        })
+        .await?;
+
+    for alert in alerts {
+        if let Err(why) = send_alert(ctx, &alert).await {
+            warn!(
+                "Couldn't send the price alert for {} to {}: {}",
+                alert.watch.app_id, alert.watch.user_id, why
+            );
+        }
+    }
+
+    Ok(())
+}
+
+async fn send_alert(ctx: &serenity::Context, alert: &Alert) -> Result<(), Error> {
+    let channel_id = match alert.channel_id {
+        Some(channel_id) => serenity::ChannelId(channel_id),
+        None => serenity::UserId(alert.watch.user_id).create_dm_channel(ctx).await?.id,
+    };
+
+    let watch = &alert.watch;
+    let price = &alert.price;
+    let bot = ctx.cache.current_user();
+
+    channel_id
+        .send_message(ctx, |message| {
+            // people don't see alerts in a channel unless they're pinged
+            if alert.channel_id.is_some() {
+                message.content(format!("<@{}>", watch.user_id));
+            }
+            message.embed(|embed| {
+                let reached_target = watch.target.map_or(false, |target| price.final_price <= target);
+                embed.title(if reached_target {
+                    format!("{} hit your target price!", watch.name)
+                } else {
+                    format!("{} is on sale!", watch.name)
+                });
+                embed.url(store_url(watch.app_id));
+                embed.colour(serenity::Colour::from_rgb(0, 0, 0));
+
+                if price.discount_percent > 0 {
+                    embed.description(format!(
+                        "~~{}~~ **{}** (-{}%)",
+                        price.initial_formatted, price.final_formatted, price.discount_percent
+                    ));
+                } else {
+                    embed.description(format!("**{}**", price.final_formatted));
+                }
+                if let Some(target) = watch.target_formatted() {
+                    embed.field("Your target", target, true);
+                }
+                embed.field("Region", &watch.region, true);
+
+                embed.footer(|footer| {
+                    if let Some(icon_url) = bot.avatar_url() {
+                        footer.icon_url(icon_url);
+                    } else {
+                        footer.icon_url(bot.default_avatar_url());
+                    }
+                    footer.text(format!("{} | Steam | Price watch", bot.name));
+                    footer
+                });
+
+                embed.timestamp(chrono::Utc::now());
+
+                embed
+            })
+        })
+        .await?;
+
+    Ok(())
+}
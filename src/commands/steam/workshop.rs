@@ -0,0 +1,526 @@
+use crate::{Context, Error};
+use std::time::Duration;
+
+use poise::serenity_prelude as serenity;
+use serde::Deserialize;
+
+use super::api::{api_key, player_summary, PlayerSummary};
+use super::apps::{app_details, region, store_url};
+use super::news::{bbcode_to_markdown, truncate_post};
+use crate::helpers::{check_quota, paginate, truncate, Api};
+
+const FILE_DETAILS_URL: &str =
+    "https://api.steampowered.com/ISteamRemoteStorage/GetPublishedFileDetails/v1/";
+const COLLECTION_DETAILS_URL: &str =
+    "https://api.steampowered.com/ISteamRemoteStorage/GetCollectionDetails/v1/";
+const WORKSHOP_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_FILES_PER_CALL: usize = 100;
+const MAX_COLLECTION_ITEMS: usize = 500; // some collections are huge, nobody pages through more than this
+const ITEMS_PER_PAGE: usize = 10;
+const MAX_DESCRIPTION_LENGTH: usize = 500;
+const MAX_TITLE_LENGTH: usize = 80; // so a page of items fits in one embed
+const MAX_TAGS: usize = 15;
+const RESULT_OK: u32 = 1; // steam's EResult for success
+const FILE_TYPE_COLLECTION: u32 = 2; // the file type of collections inside collections
+
+/// Look up a steam workshop item or collection
+///
+/// Shows the title, preview, author, subscriptions, file size, last update and tags.
+/// Collections also list their items, 10 per page.
+///
+/// **Usage:**
+/// `/steam workshop <link or id>`
+///
+/// **Examples:**
+/// `/steam workshop https://steamcommunity.com/sharedfiles/filedetails/?id=125438255`
+/// `/steam workshop 125438255`
+#[poise::command(
+    prefix_command,
+    slash_command,
+    track_edits,
+    defer_response,
+    broadcast_typing
+)]
+pub async fn workshop(
+    ctx: Context<'_>,
+    #[description = "A workshop link or item id"] item: String,
+) -> Result<(), Error> {
+    let file_id = match parse_file_id(&item) {
+        Some(file_id) => file_id,
+        None => {
+            poise::say_reply(ctx, "That isn't a workshop link or item id.").await?;
+            return Ok(());
+        }
+    };
+
+    let (details, children) = tokio::join!(file_details(&[file_id]), collection_children(file_id));
+    let file = match details?.into_iter().next() {
+        Some(file) if file.result == RESULT_OK => file,
+        _ => {
+            poise::say_reply(
+                ctx,
+                format!("Workshop item {} not found. It might be private or removed.", file_id),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+    // items that aren't collections have no children
+    let children = children?;
+
+    // the collection items, in the collection's order. the item lookups don't use the api key
+    let mut items = Vec::new();
+    let child_ids = children
+        .iter()
+        .take(MAX_COLLECTION_ITEMS)
+        .filter_map(|child| child.file_id.parse::<u64>().ok())
+        .collect::<Vec<u64>>();
+    for batch in child_ids.chunks(MAX_FILES_PER_CALL) {
+        items.extend(file_details(batch).await?.into_iter().filter(|item| item.result == RESULT_OK));
+    }
+    let collection = if children.is_empty() {
+        None
+    } else {
+        Some(Collection {
+            size: children.len(),
+            nested: children
+                .iter()
+                .filter(|child| child.file_type == FILE_TYPE_COLLECTION)
+                .map(|child| child.file_id.clone())
+                .collect(),
+            items,
+        })
+    };
+
+    // for the author's name
+    if !check_quota(ctx, Api::Steam, 1).await? {
+        return Ok(());
+    }
+    let api_key = api_key();
+    let region = region();
+    let (author, game) = tokio::join!(
+        player_summary(&api_key, &file.creator),
+        app_details(file.consumer_app_id, &region)
+    );
+    // both are nice-to-haves, don't fail the whole command over them
+    let item = Item {
+        file,
+        author: author.ok().flatten(),
+        game: game.ok().flatten().map(|game| game.name),
+        collection,
+    };
+
+    let pages = match &item.collection {
+        Some(collection) => 1 + (collection.items.len() + ITEMS_PER_PAGE - 1) / ITEMS_PER_PAGE,
+        None => 1,
+    };
+    paginate(ctx, pages, |embed, page| workshop_embed(ctx, embed, &item, page, pages)).await?;
+
+    Ok(())
+}
+
+struct Item {
+    file: WorkshopFile,
+    author: Option<PlayerSummary>,
+    game: Option<String>, // the name of the game it's for
+    collection: Option<Collection>,
+}
+
+struct Collection {
+    size: usize,         // every item in it, even the ones that couldn't be looked up
+    nested: Vec<String>, // the ids of collections inside it
+    items: Vec<WorkshopFile>,
+}
+
+/// Fills an embed with the item on the first page, or a page of the collection's items
+fn workshop_embed<'a>(
+    ctx: Context<'_>,
+    embed: &'a mut serenity::CreateEmbed,
+    item: &Item,
+    page: usize,
+    pages: usize,
+) -> &'a mut serenity::CreateEmbed {
+    let file = &item.file;
+    embed.title(&file.title).url(file_url(&file.file_id));
+    embed.colour(serenity::Colour::from_rgb(0, 0, 0));
+    embed.author(|author| {
+        if let Some(icon_url) = ctx.author().avatar_url() {
+            author.icon_url(icon_url);
+        } else {
+            author.icon_url(ctx.author().default_avatar_url());
+        }
+        author.name(&ctx.author().name);
+        author
+    });
+
+    match &item.collection {
+        Some(collection) if page > 0 => {
+            embed.thumbnail(&file.preview_url);
+
+            let first = (page - 1) * ITEMS_PER_PAGE;
+            let items = collection
+                .items
+                .iter()
+                .enumerate()
+                .skip(first)
+                .take(ITEMS_PER_PAGE)
+                .map(|(index, item)| {
+                    let icon = if collection.nested.contains(&item.file_id) {
+                        "📁 "
+                    } else {
+                        ""
+                    };
+                    let mut line = format!(
+                        "`{}.` {}**[{}]({})**\n{} subscribers",
+                        index + 1,
+                        icon,
+                        truncate(&item.title, MAX_TITLE_LENGTH),
+                        file_url(&item.file_id),
+                        item.subscriptions
+                    );
+                    if item.size() > 0 {
+                        line.push_str(&format!(", {}", format_size(item.size())));
+                    }
+                    line.push_str(&format!(", updated <t:{}:R>", item.time_updated));
+                    line
+                })
+                .collect::<Vec<String>>();
+            embed.description(items.join("\n"));
+        }
+        _ => {
+            if !file.preview_url.is_empty() {
+                embed.image(&file.preview_url);
+            }
+
+            let (description, _) = bbcode_to_markdown(&file.description);
+            let (description, _) = truncate_post(&description, MAX_DESCRIPTION_LENGTH);
+            if !description.is_empty() {
+                embed.description(description);
+            }
+
+            let author = match &item.author {
+                Some(author) => format!("[{}]({})", author.persona_name, author.profile_url),
+                None => format!("[{}](https://steamcommunity.com/profiles/{})", file.creator, file.creator),
+            };
+            embed.field("Author", author, true);
+            let game = match &item.game {
+                Some(game) => format!("[{}]({})", game, store_url(file.consumer_app_id)),
+                None => format!("[App {}]({})", file.consumer_app_id, store_url(file.consumer_app_id)),
+            };
+            embed.field("Game", game, true);
+
+            embed.field(
+                "Subscriptions",
+                format!("{} ({} all time)", file.subscriptions, file.lifetime_subscriptions),
+                true,
+            );
+            embed.field("Favorites", file.favorited, true);
+            embed.field("Views", file.views, true);
+            if let Some(collection) = &item.collection {
+                embed.field("Items", collection.size, true);
+            } else if file.size() > 0 {
+                embed.field("File size", format_size(file.size()), true);
+            }
+
+            embed.field("Created", format!("<t:{}:D>", file.time_created), true);
+            embed.field("Updated", format!("<t:{}:R>", file.time_updated), true);
+
+            if !file.tags.is_empty() {
+                let tags = file
+                    .tags
+                    .iter()
+                    .take(MAX_TAGS)
+                    .map(|tag| tag.tag.as_str())
+                    .collect::<Vec<&str>>();
+                embed.field("Tags", tags.join(", "), false);
+            }
+            if file.banned {
+                let reason = if file.ban_reason.is_empty() {
+                    "No reason given"
+                } else {
+                    file.ban_reason.as_str()
+                };
+                embed.field("⚠️ Banned", reason, false);
+            }
+        }
+    }
+
+    embed.footer(|footer| {
+        if let Some(icon_url) = &ctx.discord().cache.current_user().avatar_url() {
+            footer.icon_url(icon_url);
+        } else {
+            footer.icon_url(ctx.discord().cache.current_user().default_avatar_url());
+        }
+        if pages > 1 {
+            footer.text(format!(
+                "{} | Steam Workshop | Page {}/{}",
+                ctx.discord().cache.current_user().name,
+                page + 1,
+                pages
+            ));
+        } else {
+            footer.text(format!("{} | Steam Workshop", ctx.discord().cache.current_user().name));
+        }
+        footer
+    });
+
+    embed.timestamp(chrono::Utc::now());
+
+    embed
+}
+
+/// Gets the details of up to 100 workshop items at once, in the same order.
+/// Items that don't exist or are private come back with a `result` other than 1.
+async fn file_details(file_ids: &[u64]) -> Result<Vec<WorkshopFile>, Error> {
+    let mut form = vec![(String::from("itemcount"), file_ids.len().to_string())];
+    for (index, file_id) in file_ids.iter().enumerate() {
+        form.push((format!("publishedfileids[{}]", index), file_id.to_string()));
+    }
+
+    Ok(reqwest::Client::new()
+        .post(FILE_DETAILS_URL)
+        .form(&form)
+        .timeout(WORKSHOP_TIMEOUT)
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<FileDetailsResponse>()
+        .await?
+        .response
+        .publishedfiledetails)
+}
+
+/// Gets the items in a collection, in order. Returns nothing for items that aren't collections.
+async fn collection_children(file_id: u64) -> Result<Vec<CollectionChild>, Error> {
+    let form = [("collectioncount", String::from("1")), ("publishedfileids[0]", file_id.to_string())];
+
+    let mut children = reqwest::Client::new()
+        .post(COLLECTION_DETAILS_URL)
+        .form(&form)
+        .timeout(WORKSHOP_TIMEOUT)
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<CollectionDetailsResponse>()
+        .await?
+        .response
+        .collectiondetails
+        .into_iter()
+        .filter(|collection| collection.result == RESULT_OK)
+        .flat_map(|collection| collection.children)
+        .collect::<Vec<CollectionChild>>();
+    children.sort_by_key(|child| child.sort_order);
+
+    Ok(children)
+}
+
+/// Gets the item id out of a workshop link (`steamcommunity.com/sharedfiles/filedetails/?id=<id>`,
+/// `steamcommunity.com/workshop/filedetails/?id=<id>` or `steam://url/CommunityFilePage/<id>`),
+/// or takes the id as it is
+fn parse_file_id(input: &str) -> Option<u64> {
+    // discord users wrap links in <> to hide the preview
+    let input = input.trim().trim_start_matches('<').trim_end_matches('>');
+
+    if let Ok(file_id) = input.parse() {
+        return Some(file_id);
+    }
+    if let Some(file_id) = input.strip_prefix("steam://url/CommunityFilePage/") {
+        return file_id.trim_end_matches('/').parse().ok();
+    }
+    if !input.contains("steamcommunity.com/") {
+        return None;
+    }
+
+    let query = input.splitn(2, '?').nth(1)?;
+    query
+        .split(|c| c == '&' || c == '#')
+        .find_map(|pair| pair.strip_prefix("id="))
+        .and_then(|file_id| file_id.parse().ok())
+}
+
+fn file_url(file_id: &str) -> String {
+    format!("https://steamcommunity.com/sharedfiles/filedetails/?id={}", file_id)
+}
+
+/// e.g. "512 KB" or "1.2 GB"
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if size < 10.0 {
+        format!("{:.1} {}", size, UNITS[unit])
+    } else {
+        format!("{:.0} {}", size, UNITS[unit])
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct FileDetailsResponse {
+    response: FileDetails,
+}
+
+#[derive(Debug, Deserialize)]
+struct FileDetails {
+    #[serde(default)]
+    publishedfiledetails: Vec<WorkshopFile>,
+}
+
+// missing and private items only have the id and result, so everything else has a default
+#[derive(Debug, Clone, Deserialize)]
+struct WorkshopFile {
+    #[serde(rename = "publishedfileid")]
+    file_id: String,
+    result: u32,
+    #[serde(default)]
+    creator: String, // SteamID64
+    #[serde(default)]
+    consumer_app_id: u32, // the game it's for
+    #[serde(default)]
+    file_size: serde_json::Value, // a number, but sometimes a string
+    #[serde(default)]
+    preview_url: String,
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    description: String, // in BBCode
+    #[serde(default)]
+    time_created: i64,
+    #[serde(default)]
+    time_updated: i64,
+    #[serde(default, deserialize_with = "deserialize_flag")]
+    banned: bool,
+    #[serde(default)]
+    ban_reason: String,
+    #[serde(default)]
+    subscriptions: u64,
+    #[serde(default)]
+    favorited: u64,
+    #[serde(default)]
+    lifetime_subscriptions: u64,
+    #[serde(default)]
+    views: u64,
+    #[serde(default)]
+    tags: Vec<WorkshopTag>,
+}
+
+impl WorkshopFile {
+    /// The file size in bytes, 0 for collections
+    fn size(&self) -> u64 {
+        match &self.file_size {
+            serde_json::Value::String(size) => size.parse().unwrap_or_default(),
+            size => size.as_u64().unwrap_or_default(),
+        }
+    }
+}
+
+/// steam sends flags as 0 or 1
+fn deserialize_flag<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+    Ok(u8::deserialize(deserializer)? != 0)
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct WorkshopTag {
+    tag: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct CollectionDetailsResponse {
+    response: CollectionDetails,
+}
+
+#[derive(Debug, Deserialize)]
+struct CollectionDetails {
+    #[serde(default)]
+    collectiondetails: Vec<CollectionDetail>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CollectionDetail {
+    result: u32,
+    #[serde(default)]
+    children: Vec<CollectionChild>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CollectionChild {
+    #[serde(rename = "publishedfileid")]
+    file_id: String,
+    #[serde(rename = "sortorder", default)]
+    sort_order: u32,
+    #[serde(rename = "filetype", default)]
+    file_type: u32, // 0 for items, 2 for collections
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_a_file_id() {
+        assert_eq!(parse_file_id("2297447428"), Some(2297447428));
+        assert_eq!(parse_file_id("  2297447428 "), Some(2297447428));
+    }
+
+    #[test]
+    fn parses_a_filedetails_link() {
+        assert_eq!(
+            parse_file_id("https://steamcommunity.com/sharedfiles/filedetails/?id=2297447428"),
+            Some(2297447428)
+        );
+        assert_eq!(
+            parse_file_id("https://steamcommunity.com/sharedfiles/filedetails/?searchtext=&id=2297447428#comments"),
+            Some(2297447428)
+        );
+        assert_eq!(
+            parse_file_id("https://steamcommunity.com/workshop/filedetails/?id=2297447428"),
+            Some(2297447428)
+        );
+    }
+
+    #[test]
+    fn parses_a_steam_link() {
+        assert_eq!(parse_file_id("steam://url/CommunityFilePage/2297447428"), Some(2297447428));
+        assert_eq!(parse_file_id("steam://url/CommunityFilePage/2297447428/"), Some(2297447428));
+    }
+
+    #[test]
+    fn parses_a_link_wrapped_in_angle_brackets() {
+        assert_eq!(
+            parse_file_id("<https://steamcommunity.com/sharedfiles/filedetails/?id=2297447428>"),
+            Some(2297447428)
+        );
+        assert_eq!(parse_file_id("<2297447428>"), Some(2297447428));
+    }
+
+    #[test]
+    fn rejects_what_isnt_a_workshop_item() {
+        assert_eq!(parse_file_id(""), None);
+        assert_eq!(parse_file_id("portal 2"), None);
+        assert_eq!(parse_file_id("https://example.com/?id=2297447428"), None);
+        assert_eq!(parse_file_id("https://steamcommunity.com/sharedfiles/filedetails/"), None);
+        assert_eq!(parse_file_id("https://steamcommunity.com/sharedfiles/filedetails/?id=abc"), None);
+    }
+
+    #[test]
+    fn formats_sizes() {
+        assert_eq!(format_size(0), "0 B");
+        assert_eq!(format_size(1023), "1023 B");
+        assert_eq!(format_size(1024), "1.0 KB");
+        assert_eq!(format_size(512 * 1024), "512 KB");
+        assert_eq!(format_size(1288490189), "1.2 GB");
+        assert_eq!(format_size(50 * 1024 * 1024), "50 MB");
+    }
+
+    #[test]
+    fn formats_huge_sizes_in_terabytes() {
+        assert_eq!(format_size(2048 * 1024 * 1024 * 1024 * 1024), "2048 TB");
+    }
+}
@@ -0,0 +1,107 @@
+use crate::{Context, Error};
+
+use poise::serenity_prelude as serenity;
+
+use crate::helpers::{today, Api};
+
+/// Show how many calls the bot made to paid APIs (owner only)
+///
+/// Shows today's calls and budgets for every API, the servers that made the most calls today,
+/// and the totals of the last 7 days.
+///
+/// **Usage:**
+/// `/usage`
+#[poise::command(prefix_command, slash_command, owners_only, hide_in_help)]
+pub async fn usage(ctx: Context<'_>) -> Result<(), Error> {
+    let tracker = &ctx.data().usage;
+    let history = tracker.history(7).await;
+
+    let today = today();
+    let today_usage = history
+        .iter()
+        .find(|(date, _)| *date == today)
+        .map(|(_, usage)| usage.clone())
+        .unwrap_or_default();
+
+    // e.g. "**Google Search**: 42/100 (max 20 per server)"
+    let today_lines = Api::ALL
+        .iter()
+        .map(|api| {
+            let budget = tracker.budget(*api);
+            let used = today_usage.totals.get(api).copied().unwrap_or(0);
+
+            let mut line = match budget.daily {
+                Some(daily) => format!("**{}**: {}/{}", api, used, daily),
+                None => format!("**{}**: {} (unlimited)", api, used),
+            };
+            if let Some(daily_per_guild) = budget.daily_per_guild {
+                line.push_str(&format!(" (max {} per server)", daily_per_guild));
+            }
+            line
+        })
+        .collect::<Vec<String>>();
+
+    // the 5 servers that made the most calls today, across all APIs
+    let mut guilds = today_usage
+        .guilds
+        .iter()
+        .map(|(guild_id, calls)| (*guild_id, calls.values().sum::<u32>()))
+        .collect::<Vec<(u64, u32)>>();
+    guilds.sort_by(|a, b| b.1.cmp(&a.1));
+    let guild_lines = guilds
+        .iter()
+        .take(5)
+        .map(|(guild_id, calls)| {
+            let name = serenity::GuildId(*guild_id)
+                .name(ctx.discord())
+                .unwrap_or_else(|| guild_id.to_string());
+            format!("{}: {} calls", name, calls)
+        })
+        .collect::<Vec<String>>();
+
+    let history_lines = history
+        .iter()
+        .map(|(date, usage)| {
+            let calls = Api::ALL
+                .iter()
+                .map(|api| format!("{} {}", api, usage.totals.get(api).copied().unwrap_or(0)))
+                .collect::<Vec<String>>()
+                .join(" · ");
+            format!("`{}` {}", date, calls)
+        })
+        .collect::<Vec<String>>();
+
+    poise::send_reply(ctx, |message| {
+        message.embed(|embed| {
+            embed.title("API usage");
+            embed.colour(serenity::Colour::from_rgb(88, 101, 242));
+            embed.field(format!("Today ({} UTC)", today), today_lines.join("\n"), false);
+            if !guild_lines.is_empty() {
+                embed.field("Top servers today", guild_lines.join("\n"), false);
+            }
+            if !history_lines.is_empty() {
+                embed.field("Last 7 days", history_lines.join("\n"), false);
+            }
+
+            embed.footer(|footer| {
+                if let Some(icon_url) = &ctx.discord().cache.current_user().avatar_url() {
+                    footer.icon_url(icon_url);
+                } else {
+                    footer.icon_url(ctx.discord().cache.current_user().default_avatar_url());
+                }
+                footer.text(format!(
+                    "{} | Usage",
+                    ctx.discord().cache.current_user().name
+                ));
+                footer
+            });
+
+            embed.timestamp(chrono::Utc::now());
+
+            embed
+        })
+    })
+    .await?;
+
+    Ok(())
+}
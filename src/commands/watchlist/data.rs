@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+/// Every watchlist the bot knows about, saved in watchlists.json
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Watchlists {
+    pub users: HashMap<u64, Watchlist>,  // personal watchlists, keyed by user id
+    pub guilds: HashMap<u64, Watchlist>, // shared server watchlists, keyed by guild id
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Watchlist {
+    pub entries: Vec<Entry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub imdb_id: String,
+    pub title: String,
+    pub added_by: u64,
+    pub added_at: i64,
+    pub watched_at: Option<i64>,
+    #[serde(default)]
+    pub ratings: HashMap<u64, u8>, // ratings out of 10, keyed by user id
+}
+
+impl Watchlist {
+    /// Finds an entry by IMDb ID or by (case insensitive) title
+    pub fn find(&self, movie: &str) -> Option<&Entry> {
+        self.entries.iter().find(|entry| entry.matches(movie))
+    }
+
+    pub fn find_mut(&mut self, movie: &str) -> Option<&mut Entry> {
+        self.entries.iter_mut().find(|entry| entry.matches(movie))
+    }
+
+    /// Adds an entry unless a movie with the same IMDb ID is already on the list.
+    /// Returns false if the movie was already on the list.
+    pub fn add(&mut self, entry: Entry) -> bool {
+        if self.find(&entry.imdb_id).is_some() {
+            return false;
+        }
+
+        self.entries.push(entry);
+        true
+    }
+
+    /// Removes an entry by IMDb ID or title. Returns the removed entry.
+    pub fn remove(&mut self, movie: &str) -> Option<Entry> {
+        let index = self.entries.iter().position(|entry| entry.matches(movie))?;
+        Some(self.entries.remove(index))
+    }
+}
+
+impl Entry {
+    fn matches(&self, movie: &str) -> bool {
+        self.imdb_id.eq_ignore_ascii_case(movie) || self.title.eq_ignore_ascii_case(movie.trim())
+    }
+
+    /// The average of all ratings given to this entry
+    pub fn average_rating(&self) -> Option<f32> {
+        if self.ratings.is_empty() {
+            return None;
+        }
+
+        let total = self.ratings.values().map(|rating| *rating as f32).sum::<f32>();
+        Some(total / self.ratings.len() as f32)
+    }
+}
+
+/// Whose watchlist to use
+#[derive(Debug, Clone, Copy)]
+pub enum Owner {
+    User(u64),
+    Guild(u64),
+}
+
+impl Watchlists {
+    pub fn list(&self, owner: Owner) -> Option<&Watchlist> {
+        match owner {
+            Owner::User(id) => self.users.get(&id),
+            Owner::Guild(id) => self.guilds.get(&id),
+        }
+    }
+
+    /// Returns the watchlist of `owner`, creating an empty one if they don't have one yet
+    pub fn list_mut(&mut self, owner: Owner) -> &mut Watchlist {
+        match owner {
+            Owner::User(id) => self.users.entry(id).or_default(),
+            Owner::Guild(id) => self.guilds.entry(id).or_default(),
+        }
+    }
+}
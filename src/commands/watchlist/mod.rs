@@ -0,0 +1,2 @@
+pub mod data;
+pub mod watchlist; // export the watchlist main command and its subcommands
@@ -0,0 +1,464 @@
+use crate::{Context, Error};
+use std::time::Duration;
+
+use log::error;
+use poise::serenity_prelude as serenity;
+use rand::seq::SliceRandom;
+
+use super::data::{Entry, Owner};
+use crate::helpers::check_quota;
+use crate::commands::movie::{
+    movie::{movie_buttons, movie_embed},
+    provider::MovieDetails,
+};
+
+/// The custom id of the "Add to watchlist" button on movie embeds
+pub const ADD_BUTTON_ID: &str = "watchlist_add";
+
+/// How long the "Add to watchlist" button on a movie embed keeps working
+const ADD_BUTTON_TIMEOUT: Duration = Duration::from_secs(600);
+const MAX_LIST_LENGTH: usize = 3900; // embed descriptions can only be 4096 characters long
+
+/// Keep a list of movies to watch, for yourself or for the whole server
+///
+/// Every subcommand takes an optional `server` argument at the end
+/// to use the server's shared watchlist instead of your personal one.
+///
+/// **Subcommands**
+///
+/// **add** `/watchlist add <movie> [server]`
+/// **remove** `/watchlist remove <movie> [server]`
+/// **list** `/watchlist list [server]`
+/// **random** `/watchlist random [server]` picks a movie you haven't watched yet
+/// **watched** `/watchlist watched <movie> <rating> [server]` marks a movie as watched and rates it out of 10
+///
+/// *examples*
+/// `/watchlist add "The Matrix" server`
+/// `/watchlist watched tt0133093 9`
+#[poise::command(prefix_command, slash_command)]
+pub async fn watchlist(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+#[derive(Debug, poise::SlashChoiceParameter)]
+pub enum Scope {
+    #[name = "personal"]
+    Personal,
+    #[name = "server"]
+    Server,
+}
+
+/// Add a movie to a watchlist
+#[poise::command(prefix_command, slash_command, track_edits, defer_response)]
+pub async fn add(
+    ctx: Context<'_>,
+    #[description = "The name or IMDb ID of the movie"] movie: String,
+    #[description = "Which watchlist to use (personal by default)"] scope: Option<Scope>,
+) -> Result<(), Error> {
+    let owner = match owner(ctx, scope).await? {
+        Some(owner) => owner,
+        None => return Ok(()),
+    };
+
+    let (api, calls) = ctx.data().movie_provider.quota();
+    if !check_quota(ctx, api, calls).await? {
+        return Ok(());
+    }
+
+    let details = match find_movie(ctx, &movie).await {
+        Some(details) => details,
+        None => {
+            poise::say_reply(ctx, format!("No movie with title: \"{}\" found.", movie)).await?;
+            return Ok(());
+        }
+    };
+
+    let imdb_id = match &details.imdb_id {
+        Some(imdb_id) => imdb_id.clone(),
+        None => {
+            poise::say_reply(ctx, format!("\"{}\" doesn't have an IMDb ID, so it can't be added.", details.title)).await?;
+            return Ok(());
+        }
+    };
+
+    let entry = Entry {
+        imdb_id,
+        title: details.title.clone(),
+        added_by: ctx.author().id.0,
+        added_at: chrono::Utc::now().timestamp(),
+        watched_at: None,
+        ratings: Default::default(),
+    };
+
+    let added = ctx
+        .data()
+        .watchlists
+        .update(|watchlists| watchlists.list_mut(owner).add(entry))
+        .await?;
+
+    if added {
+        poise::say_reply(ctx, format!("Added **{}** to {}.", details.title, describe(owner))).await?;
+    } else {
+        poise::say_reply(ctx, format!("**{}** is already on {}.", details.title, describe(owner))).await?;
+    }
+
+    Ok(())
+}
+
+/// Remove a movie from a watchlist
+#[poise::command(prefix_command, slash_command, track_edits)]
+pub async fn remove(
+    ctx: Context<'_>,
+    #[description = "The title or IMDb ID of the movie"] movie: String,
+    #[description = "Which watchlist to use (personal by default)"] scope: Option<Scope>,
+) -> Result<(), Error> {
+    let owner = match owner(ctx, scope).await? {
+        Some(owner) => owner,
+        None => return Ok(()),
+    };
+
+    let removed = ctx
+        .data()
+        .watchlists
+        .update(|watchlists| watchlists.list_mut(owner).remove(&movie))
+        .await?;
+
+    match removed {
+        Some(entry) => {
+            poise::say_reply(ctx, format!("Removed **{}** from {}.", entry.title, describe(owner))).await?
+        }
+        None => poise::say_reply(ctx, format!("\"{}\" isn't on {}.", movie, describe(owner))).await?,
+    };
+
+    Ok(())
+}
+
+/// Show the movies on a watchlist
+#[poise::command(prefix_command, slash_command, track_edits)]
+pub async fn list(
+    ctx: Context<'_>,
+    #[description = "Which watchlist to show (personal by default)"] scope: Option<Scope>,
+) -> Result<(), Error> {
+    let owner = match owner(ctx, scope).await? {
+        Some(owner) => owner,
+        None => return Ok(()),
+    };
+
+    let entries = ctx
+        .data()
+        .watchlists
+        .read()
+        .await
+        .list(owner)
+        .map(|watchlist| watchlist.entries.clone())
+        .unwrap_or_default();
+
+    if entries.is_empty() {
+        poise::say_reply(ctx, format!("There's nothing on {} yet.", describe(owner))).await?;
+        return Ok(());
+    }
+
+    let watched_count = entries.iter().filter(|entry| entry.watched_at.is_some()).count();
+
+    // as many entries as fit, titles can be long
+    let mut description = String::new();
+    for (shown, entry) in entries.iter().enumerate() {
+        let mut line = match entry.watched_at {
+            Some(_) => format!("✅ ~~{}~~", entry.title),
+            None => format!("🎬 **{}**", entry.title),
+        };
+        if let Some(rating) = entry.average_rating() {
+            line.push_str(&format!(" ⭐ {:.1}/10", rating));
+        }
+        line.push_str(&format!(" [`{}`](https://imdb.com/title/{})\n", entry.imdb_id, entry.imdb_id));
+
+        if description.len() + line.len() > MAX_LIST_LENGTH {
+            description.push_str(&format!("*...and {} more*", entries.len() - shown));
+            break;
+        }
+        description.push_str(&line);
+    }
+
+    poise::send_reply(ctx, |message| {
+        message.embed(|embed| {
+            match owner {
+                Owner::User(_) => embed.title(format!("{}'s watchlist", ctx.author().name)),
+                Owner::Guild(_) => embed.title("Server watchlist"),
+            };
+            embed.description(description);
+            embed.colour(serenity::Colour::from_rgb(245, 197, 24));
+            embed.author(|author| {
+                if let Some(icon_url) = ctx.author().avatar_url() {
+                    author.icon_url(icon_url);
+                } else {
+                    author.icon_url(ctx.author().default_avatar_url());
+                }
+                author.name(&ctx.author().name);
+                author
+            });
+
+            embed.footer(|footer| {
+                if let Some(icon_url) = &ctx.discord().cache.current_user().avatar_url() {
+                    footer.icon_url(icon_url);
+                } else {
+                    footer.icon_url(ctx.discord().cache.current_user().default_avatar_url());
+                }
+                footer.text(format!(
+                    "{} | Watchlist | {}/{} watched",
+                    ctx.discord().cache.current_user().name,
+                    watched_count,
+                    entries.len()
+                ));
+                footer
+            });
+
+            embed.timestamp(chrono::Utc::now());
+
+            embed
+        })
+    })
+    .await?;
+
+    Ok(())
+}
+
+/// Pick a random movie that hasn't been watched yet from a watchlist
+#[poise::command(prefix_command, slash_command, track_edits, defer_response)]
+pub async fn random(
+    ctx: Context<'_>,
+    #[description = "Which watchlist to pick from (personal by default)"] scope: Option<Scope>,
+) -> Result<(), Error> {
+    let owner = match owner(ctx, scope).await? {
+        Some(owner) => owner,
+        None => return Ok(()),
+    };
+
+    let entry = ctx.data().watchlists.read().await.list(owner).and_then(|watchlist| {
+        let unwatched = watchlist
+            .entries
+            .iter()
+            .filter(|entry| entry.watched_at.is_none())
+            .collect::<Vec<&Entry>>();
+
+        unwatched.choose(&mut rand::thread_rng()).map(|entry| (*entry).clone())
+    });
+
+    let entry = match entry {
+        Some(entry) => entry,
+        None => {
+            poise::say_reply(ctx, format!("There are no unwatched movies on {}.", describe(owner))).await?;
+            return Ok(());
+        }
+    };
+
+    let provider = &ctx.data().movie_provider;
+
+    // the pick doesn't need the movie details, so skip them if the quota is used up
+    let (api, calls) = provider.quota();
+    let guild_id = ctx.guild_id().map(|guild_id| guild_id.0);
+    let result = if ctx.data().usage.try_use(api, guild_id, calls).await? {
+        provider.find_by_imdb_id(&entry.imdb_id).await
+    } else {
+        Ok(None)
+    };
+
+    match result {
+        Ok(Some(details)) => {
+            poise::send_reply(ctx, |message| {
+                message
+                    .content(format!("🎲 Tonight's pick from {}:", describe(owner)))
+                    .embed(|embed| movie_embed(ctx, embed, &details, provider.name()))
+                    .components(|components| {
+                        components.create_action_row(|action_row| movie_buttons(action_row, &details))
+                    })
+            })
+            .await?;
+        }
+        result => {
+            // still give the pick if the provider doesn't know the movie (anymore)
+            if let Err(why) = result {
+                error!("Couldn't fetch movie {} from {}: {}", entry.imdb_id, provider.name(), why);
+            }
+            poise::say_reply(
+                ctx,
+                format!(
+                    "🎲 Tonight's pick from {}: **{}** (https://imdb.com/title/{})",
+                    describe(owner),
+                    entry.title,
+                    entry.imdb_id
+                ),
+            )
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Mark a movie on a watchlist as watched and rate it
+#[poise::command(prefix_command, slash_command, track_edits)]
+pub async fn watched(
+    ctx: Context<'_>,
+    #[description = "The title or IMDb ID of the movie"] movie: String,
+    #[description = "Your rating out of 10"] rating: u8,
+    #[description = "Which watchlist to use (personal by default)"] scope: Option<Scope>,
+) -> Result<(), Error> {
+    if !(1..=10).contains(&rating) {
+        poise::say_reply(ctx, "The rating has to be between 1 and 10.").await?;
+        return Ok(());
+    }
+
+    let owner = match owner(ctx, scope).await? {
+        Some(owner) => owner,
+        None => return Ok(()),
+    };
+
+    let user_id = ctx.author().id.0;
+    let entry = ctx
+        .data()
+        .watchlists
+        .update(|watchlists| {
+            watchlists.list_mut(owner).find_mut(&movie).map(|entry| {
+                if entry.watched_at.is_none() {
+                    entry.watched_at = Some(chrono::Utc::now().timestamp());
+                }
+                entry.ratings.insert(user_id, rating);
+                entry.clone()
+            })
+        })
+        .await?;
+
+    match entry {
+        Some(entry) => {
+            poise::say_reply(
+                ctx,
+                format!(
+                    "Marked **{}** as watched with a rating of ⭐ {}/10. Average rating: {:.1}/10",
+                    entry.title,
+                    rating,
+                    entry.average_rating().unwrap_or(rating as f32)
+                ),
+            )
+            .await?
+        }
+        None => poise::say_reply(ctx, format!("\"{}\" isn't on {}.", movie, describe(owner))).await?,
+    };
+
+    Ok(())
+}
+
+/// Handles clicks on the "Add to watchlist" button of a movie embed,
+/// adding the movie to the personal watchlist of whoever clicked it.
+///
+/// Removes the button once it times out.
+pub async fn handle_add_button(
+    ctx: Context<'_>,
+    mut message: serenity::Message,
+    details: &MovieDetails,
+) -> Result<(), Error> {
+    let imdb_id = match &details.imdb_id {
+        Some(imdb_id) => imdb_id,
+        None => return Ok(()),
+    };
+
+    while let Some(interaction) = serenity::CollectComponentInteraction::new(ctx.discord())
+        .message_id(message.id)
+        .timeout(ADD_BUTTON_TIMEOUT)
+        .await
+    {
+        if interaction.data.custom_id != ADD_BUTTON_ID {
+            continue;
+        }
+
+        let entry = Entry {
+            imdb_id: imdb_id.clone(),
+            title: details.title.clone(),
+            added_by: interaction.user.id.0,
+            added_at: chrono::Utc::now().timestamp(),
+            watched_at: None,
+            ratings: Default::default(),
+        };
+        let owner = Owner::User(interaction.user.id.0);
+
+        let reply = match ctx
+            .data()
+            .watchlists
+            .update(|watchlists| watchlists.list_mut(owner).add(entry))
+            .await
+        {
+            Ok(true) => format!("Added **{}** to your watchlist.", details.title),
+            Ok(false) => format!("**{}** is already on your watchlist.", details.title),
+            Err(why) => {
+                error!("Couldn't save the watchlist: {}", why);
+                String::from("Something went wrong while saving your watchlist.")
+            }
+        };
+
+        interaction
+            .create_interaction_response(ctx.discord(), |response| {
+                response
+                    .kind(serenity::InteractionResponseType::ChannelMessageWithSource)
+                    .interaction_response_data(|data| {
+                        data.content(reply)
+                            .flags(serenity::InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
+                    })
+            })
+            .await?;
+    }
+
+    // the button doesn't work anymore, so only leave the links
+    message
+        .edit(ctx.discord(), |edit| {
+            edit.components(|components| {
+                components.create_action_row(|action_row| movie_buttons(action_row, details))
+            })
+        })
+        .await?;
+
+    Ok(())
+}
+
+/// Finds a movie with the configured provider, either by IMDb ID or by title
+async fn find_movie(ctx: Context<'_>, movie: &str) -> Option<MovieDetails> {
+    let provider = &ctx.data().movie_provider;
+    let movie = movie.trim();
+
+    let result = if is_imdb_id(movie) {
+        provider.find_by_imdb_id(movie).await
+    } else {
+        provider.find(movie).await
+    };
+
+    result.unwrap_or_else(|why| {
+        error!("Couldn't fetch movie \"{}\" from {}: {}", movie, provider.name(), why);
+        None
+    })
+}
+
+/// Returns true for strings that look like IMDb IDs (tt0133093, etc)
+fn is_imdb_id(movie: &str) -> bool {
+    movie.len() > 2 && movie.starts_with("tt") && movie[2..].chars().all(|c| c.is_ascii_digit())
+}
+
+/// Works out whose watchlist to use.
+/// Replies with an error and returns `None` if a server watchlist is asked for outside a server.
+async fn owner(ctx: Context<'_>, scope: Option<Scope>) -> Result<Option<Owner>, Error> {
+    match scope.unwrap_or(Scope::Personal) {
+        Scope::Personal => Ok(Some(Owner::User(ctx.author().id.0))),
+        Scope::Server => match ctx.guild_id() {
+            Some(guild_id) => Ok(Some(Owner::Guild(guild_id.0))),
+            None => {
+                poise::say_reply(ctx, "Server watchlists only work in a server.").await?;
+                Ok(None)
+            }
+        },
+    }
+}
+
+/// Describes a watchlist for use in replies ("your watchlist", "the server watchlist")
+fn describe(owner: Owner) -> &'static str {
+    match owner {
+        Owner::User(_) => "your watchlist",
+        Owner::Guild(_) => "the server watchlist",
+    }
+}
@@ -0,0 +1,23 @@
+use crate::{Context, Error};
+
+use poise::serenity_prelude as serenity;
+
+/// Replies to a button click with a message that only the user who clicked can see
+pub async fn reply_ephemeral(
+    ctx: Context<'_>,
+    interaction: &serenity::MessageComponentInteraction,
+    content: impl ToString,
+) -> Result<(), Error> {
+    interaction
+        .create_interaction_response(ctx.discord(), |response| {
+            response
+                .kind(serenity::InteractionResponseType::ChannelMessageWithSource)
+                .interaction_response_data(|data| {
+                    data.content(content)
+                        .flags(serenity::InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
+                })
+        })
+        .await?;
+
+    Ok(())
+}
@@ -1,4 +1,16 @@
 mod get_versions;
+mod interaction;
+mod network;
+mod pagination;
+mod store;
+mod text;
+mod usage;
 
 pub use get_versions::get_versions;
-pub use get_versions::Runtimes;
\ No newline at end of file
+pub use get_versions::Runtimes;
+pub use interaction::reply_ephemeral;
+pub use network::is_public;
+pub use pagination::paginate;
+pub use store::Store;
+pub use text::truncate;
+pub use usage::{check_quota, quota_exhausted_message, today, Api, UsageTracker};
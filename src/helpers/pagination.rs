@@ -0,0 +1,98 @@
+use crate::{Context, Error};
+use std::time::Duration;
+
+use poise::serenity_prelude as serenity;
+
+use super::reply_ephemeral;
+
+const PAGINATION_TIMEOUT: Duration = Duration::from_secs(120);
+const PREVIOUS_BUTTON_ID: &str = "page_previous";
+const NEXT_BUTTON_ID: &str = "page_next";
+
+/// Replies with the first of `pages` pages, with buttons to go to the previous and next page.
+///
+/// `embed` fills an embed with the page at the given index. Only the user who used the command
+/// can change the page. The buttons are disabled once nobody clicked them for 2 minutes.
+/// With only one page, there are no buttons.
+pub async fn paginate<F>(ctx: Context<'_>, pages: usize, embed: F) -> Result<(), Error>
+where
+    F: for<'a> Fn(&'a mut serenity::CreateEmbed, usize) -> &'a mut serenity::CreateEmbed,
+{
+    let mut page = 0;
+
+    let reply = poise::send_reply(ctx, |message| {
+        message.embed(|e| embed(e, page));
+        if pages > 1 {
+            message.components(|components| page_buttons(components, page, pages, false));
+        }
+        message
+    })
+    .await?;
+    if pages <= 1 {
+        return Ok(());
+    }
+    let mut message = reply.message().await?;
+
+    while let Some(interaction) = serenity::CollectComponentInteraction::new(ctx.discord())
+        .message_id(message.id)
+        .timeout(PAGINATION_TIMEOUT)
+        .await
+    {
+        if interaction.user.id != ctx.author().id {
+            reply_ephemeral(ctx, &interaction, "Only the person who used the command can change the page.").await?;
+            continue;
+        }
+
+        match interaction.data.custom_id.as_str() {
+            PREVIOUS_BUTTON_ID if page > 0 => page -= 1,
+            NEXT_BUTTON_ID if page + 1 < pages => page += 1,
+            _ => continue,
+        }
+
+        interaction
+            .create_interaction_response(ctx.discord(), |response| {
+                response
+                    .kind(serenity::InteractionResponseType::UpdateMessage)
+                    .interaction_response_data(|data| {
+                        data.create_embed(|e| embed(e, page))
+                            .components(|components| page_buttons(components, page, pages, false))
+                    })
+            })
+            .await?;
+    }
+
+    // the buttons stop working after the timeout, so disable them
+    message
+        .edit(ctx.discord(), |edit| {
+            edit.components(|components| page_buttons(components, page, pages, true))
+        })
+        .await?;
+
+    Ok(())
+}
+
+/// Adds the previous and next page buttons.
+/// Each button is disabled when there's no page to go to, or when `disabled` is true.
+fn page_buttons(
+    components: &mut serenity::CreateComponents,
+    page: usize,
+    pages: usize,
+    disabled: bool,
+) -> &mut serenity::CreateComponents {
+    components.create_action_row(|action_row| {
+        action_row.create_button(|button| {
+            button
+                .style(serenity::ButtonStyle::Secondary)
+                .label("◀ Previous")
+                .custom_id(PREVIOUS_BUTTON_ID)
+                .disabled(disabled || page == 0)
+        });
+        action_row.create_button(|button| {
+            button
+                .style(serenity::ButtonStyle::Secondary)
+                .label("Next ▶")
+                .custom_id(NEXT_BUTTON_ID)
+                .disabled(disabled || page + 1 >= pages)
+        })
+    })
+}
@@ -0,0 +1,88 @@
+use crate::Error;
+use std::{env, fs, path::PathBuf};
+
+use log::{error, info};
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::sync::{RwLock, RwLockReadGuard};
+
+/// Data that is kept in memory and saved to a JSON file on every change,
+/// so that it survives restarts of the bot.
+///
+/// The file lives in the directory set with the DATA_DIR environment variable
+/// (`./data` by default).
+pub struct Store<T> {
+    path: PathBuf,
+    value: RwLock<T>,
+}
+
+impl<T> Store<T>
+where
+    T: Serialize + DeserializeOwned + Default,
+{
+    /// Loads the store from `<DATA_DIR>/<file_name>`.
+    /// Starts with an empty store if the file doesn't exist yet.
+    pub fn load(file_name: &str) -> Self {
+        let data_dir = env::var("DATA_DIR").unwrap_or_else(|_| String::from("data"));
+        let path = PathBuf::from(data_dir).join(file_name);
+
+        let value = match fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|why| {
+                // don't start the bot with a corrupt file, it would get overwritten on the next change
+                error!("Couldn't parse {}: {}", path.display(), why);
+                std::process::exit(1);
+            }),
+            Err(_) => {
+                info!("{} doesn't exist yet. Starting with an empty store.", path.display());
+                T::default()
+            }
+        };
+
+        Store {
+            path,
+            value: RwLock::new(value),
+        }
+    }
+
+    /// Locks the store for reading
+    pub async fn read(&self) -> RwLockReadGuard<'_, T> {
+        self.value.read().await
+    }
+
+    /// Changes the data in the store and saves it to disk.
+    /// Returns whatever `change` returns.
+    pub async fn update<R>(&self, change: impl FnOnce(&mut T) -> R) -> Result<R, Error> {
+        self.update_if(|value| (change(value), true)).await
+    }
+
+    /// Like `update`, but `change` also returns whether it changed anything,
+    /// and the store is only saved if it did.
+    pub async fn update_if<R>(&self, change: impl FnOnce(&mut T) -> (R, bool)) -> Result<R, Error> {
+        let mut value = self.value.write().await;
+        let (result, changed) = change(&mut value);
+        if changed {
+            // still locked while saving, so saves can't finish out of order
+            self.save(&value).await?;
+        }
+
+        Ok(result)
+    }
+
+    async fn save(&self, value: &T) -> Result<(), Error> {
+        let contents = serde_json::to_string_pretty(value)?;
+        let path = self.path.clone();
+
+        // file system calls block, so they run outside the async runtime
+        tokio::task::spawn_blocking(move || -> std::io::Result<()> {
+            // write to a temporary file first, so a crash while saving can't leave a half-written file
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let temp_path = path.with_extension("json.tmp");
+            fs::write(&temp_path, contents)?;
+            fs::rename(&temp_path, &path)
+        })
+        .await??;
+
+        Ok(())
+    }
+}
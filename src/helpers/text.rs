@@ -0,0 +1,33 @@
+/// Cuts text down to `max` characters, with "..." at the end if anything was cut
+pub fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let truncated = text.chars().take(max - 3).collect::<String>();
+    format!("{}...", truncated.trim_end())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leaves_short_text_alone() {
+        assert_eq!(truncate("hello", 5), "hello");
+        assert_eq!(truncate("", 5), "");
+    }
+
+    #[test]
+    fn cuts_long_text_to_the_limit() {
+        assert_eq!(truncate("hello world", 8), "hello...");
+        assert_eq!(truncate("hello world", 8).chars().count(), 8);
+        // no space before the dots
+        assert_eq!(truncate("hello world", 9), "hello...");
+    }
+
+    #[test]
+    fn counts_characters_not_bytes() {
+        assert_eq!(truncate("ééééé", 5), "ééééé");
+        assert_eq!(truncate("éééééé", 5), "éé...");
+    }
+}
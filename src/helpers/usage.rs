@@ -0,0 +1,208 @@
+use crate::{Context, Error};
+use std::{
+    collections::{BTreeMap, HashMap},
+    env, fmt,
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::Store;
+
+/// How many days of usage history to keep
+const HISTORY_DAYS: usize = 30;
+
+/// The quota-limited upstream APIs the bot calls
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Api {
+    GoogleSearch,
+    Omdb,
+    Tmdb,
+    Steam,
+}
+
+impl Api {
+    pub const ALL: [Api; 4] = [Api::GoogleSearch, Api::Omdb, Api::Tmdb, Api::Steam];
+
+    /// The prefix of the environment variables that set the budgets for this API
+    fn env_prefix(&self) -> &'static str {
+        match self {
+            Api::GoogleSearch => "GOOGLE_SEARCH",
+            Api::Omdb => "OMDB",
+            Api::Tmdb => "TMDB",
+            Api::Steam => "STEAM",
+        }
+    }
+
+    /// The daily budget used when none is configured
+    fn default_daily_budget(&self) -> Option<u32> {
+        match self {
+            Api::GoogleSearch => Some(100), // the free tier of the custom search api
+            Api::Omdb => Some(1000),        // the free tier of omdb
+            Api::Tmdb => None,
+            Api::Steam => Some(100_000), // the limit in the steam web api terms of use
+        }
+    }
+}
+
+impl fmt::Display for Api {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Api::GoogleSearch => write!(f, "Google Search"),
+            Api::Omdb => write!(f, "OMDb"),
+            Api::Tmdb => write!(f, "TMDb"),
+            Api::Steam => write!(f, "Steam"),
+        }
+    }
+}
+
+/// The calls made to every API, saved in usage.json
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Usage {
+    pub days: BTreeMap<String, DayUsage>, // keyed by the date in UTC (YYYY-MM-DD)
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct DayUsage {
+    pub totals: HashMap<Api, u32>,
+    pub guilds: HashMap<u64, HashMap<Api, u32>>, // keyed by guild id, calls made outside servers aren't in here
+}
+
+/// The daily limits for one API. `None` means unlimited.
+#[derive(Debug, Clone, Copy)]
+pub struct Budget {
+    pub daily: Option<u32>,
+    pub daily_per_guild: Option<u32>,
+}
+
+/// Counts the calls made to every quota-limited API, per server and per day,
+/// and refuses calls once a daily budget is used up.
+///
+/// Budgets are set with `<API>_DAILY_BUDGET` and `<API>_GUILD_DAILY_BUDGET` environment variables,
+/// e.g. GOOGLE_SEARCH_DAILY_BUDGET=100. Set a budget to 0 to make that API unlimited.
+pub struct UsageTracker {
+    store: Store<Usage>,
+    budgets: HashMap<Api, Budget>,
+}
+
+impl UsageTracker {
+    pub fn from_env() -> Self {
+        let budgets = Api::ALL
+            .iter()
+            .map(|api| {
+                let daily = budget_from_env(&format!("{}_DAILY_BUDGET", api.env_prefix()))
+                    .unwrap_or_else(|| api.default_daily_budget());
+                let daily_per_guild =
+                    budget_from_env(&format!("{}_GUILD_DAILY_BUDGET", api.env_prefix())).flatten();
+
+                (*api, Budget { daily, daily_per_guild })
+            })
+            .collect();
+
+        UsageTracker {
+            store: Store::load("usage.json"),
+            budgets,
+        }
+    }
+
+    pub fn budget(&self, api: Api) -> Budget {
+        self.budgets[&api]
+    }
+
+    /// Counts `calls` calls to `api` if they fit in today's budgets.
+    /// Returns false (and counts nothing) if they don't.
+    pub async fn try_use(&self, api: Api, guild_id: Option<u64>, calls: u32) -> Result<bool, Error> {
+        let budget = self.budget(api);
+        let today = today();
+
+        self.store
+            .update_if(|usage| {
+                let day = usage.days.entry(today).or_default();
+
+                let total = day.totals.get(&api).copied().unwrap_or(0);
+                if budget.daily.map_or(false, |daily| total + calls > daily) {
+                    return (false, false); // nothing counted, nothing to save
+                }
+
+                if let Some(guild_id) = guild_id {
+                    let guild_total = day
+                        .guilds
+                        .get(&guild_id)
+                        .and_then(|guild| guild.get(&api))
+                        .copied()
+                        .unwrap_or(0);
+                    if budget.daily_per_guild.map_or(false, |daily| guild_total + calls > daily) {
+                        return (false, false); // nothing counted, nothing to save
+                    }
+
+                    *day.guilds.entry(guild_id).or_default().entry(api).or_insert(0) += calls;
+                }
+                *day.totals.entry(api).or_insert(0) += calls;
+
+                // forget the oldest days
+                while usage.days.len() > HISTORY_DAYS {
+                    let oldest = usage.days.keys().next().cloned().unwrap_or_default();
+                    usage.days.remove(&oldest);
+                }
+
+                (true, true)
+            })
+            .await
+    }
+
+    /// Returns the usage of the last `days` days, newest first
+    pub async fn history(&self, days: usize) -> Vec<(String, DayUsage)> {
+        self.store
+            .read()
+            .await
+            .days
+            .iter()
+            .rev()
+            .take(days)
+            .map(|(date, usage)| (date.clone(), usage.clone()))
+            .collect()
+    }
+}
+
+/// Counts `calls` calls to `api` for the server of `ctx`.
+///
+/// If that would go over a daily budget, tells the user that the quota is used up
+/// and returns false. The command should stop there.
+pub async fn check_quota(ctx: Context<'_>, api: Api, calls: u32) -> Result<bool, Error> {
+    let guild_id = ctx.guild_id().map(|guild_id| guild_id.0);
+
+    if ctx.data().usage.try_use(api, guild_id, calls).await? {
+        Ok(true)
+    } else {
+        poise::say_reply(ctx, quota_exhausted_message(api)).await?;
+        Ok(false)
+    }
+}
+
+/// The friendly reply for when the budget of an API is used up
+pub fn quota_exhausted_message(api: Api) -> String {
+    format!(
+        "⏳ The {} quota is exhausted for today. Please try again tomorrow!",
+        api
+    )
+}
+
+/// Today's date in UTC, used as the key for the usage of the day
+pub fn today() -> String {
+    chrono::Utc::now().format("%Y-%m-%d").to_string()
+}
+
+/// Reads a budget from an environment variable.
+/// Returns `None` if it isn't set, and `Some(None)` (unlimited) if it's set to 0.
+fn budget_from_env(name: &str) -> Option<Option<u32>> {
+    let budget = env::var(name).ok()?;
+    let budget = budget
+        .trim()
+        .parse::<u32>()
+        .unwrap_or_else(|_| panic!("Expected {} to be a number", name));
+
+    if budget == 0 {
+        Some(None)
+    } else {
+        Some(Some(budget))
+    }
+}
@@ -4,10 +4,19 @@ mod helpers;
 use log::{error, info, LevelFilter};
 use simple_logger::SimpleLogger;
 use std::env;
+use std::sync::Arc;
 use tokio::sync::RwLock;
 
-use commands::{code, movie, ping, steam, user, clear, google};
-use helpers::{get_versions, Runtimes};
+use commands::{code, movie, ping, clear, modlog, ModLogs, google, web, images, provider_from_env, MovieProvider};
+use commands::{steam, user, id, game, games, achievements, workshop, together, link, unlink, linked, AppList, SteamLinks};
+use commands::{watch, watches, unwatch, alerts, start_price_checks, SteamWatches};
+use commands::{follow, unfollow, following, start_news_checks, SteamNews};
+use commands::{presence, sessions, start_presence_checks, SteamPresence};
+use commands::{watchlist, add, remove, list, random, watched, Watchlists, movienight};
+use commands::{searchsettings, safesearch, block, unblock, blocklist, provider, SearchProviders, SearchSettings};
+use commands::usage;
+use commands::{server, servers, save, forget, saved, status, start_status_updates, ServerSettings};
+use helpers::{get_versions, Runtimes, Store, UsageTracker};
 
 // Types used by all command functions
 type Error = Box<dyn std::error::Error + Send + Sync>;
@@ -15,6 +24,19 @@ type Context<'a> = poise::Context<'a, Data, Error>;
 
 pub struct Data {
     runtimes: RwLock<Runtimes>,
+    movie_provider: Box<dyn MovieProvider>,
+    watchlists: Store<Watchlists>,
+    search_settings: Store<SearchSettings>,
+    search_providers: SearchProviders,
+    mod_logs: Store<ModLogs>,
+    steam_apps: AppList,
+    // shared with the checks that run in the background
+    steam_links: Arc<Store<SteamLinks>>,
+    steam_watches: Arc<Store<SteamWatches>>,
+    steam_news: Arc<Store<SteamNews>>,
+    steam_presence: Arc<Store<SteamPresence>>,
+    usage: Arc<UsageTracker>,
+    servers: Arc<Store<ServerSettings>>,
 }
 
 #[tokio::main]
@@ -37,17 +59,51 @@ async fn main() {
     // Initialize the bot token
     let token = env::var("BOT_TOKEN").expect("Expected a BOT_TOKEN environment variable.");
 
+    // The users allowed to run owner-only commands, as comma separated user ids
+    let owners = env::var("OWNER_IDS")
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|id| id.trim().parse::<u64>().ok())
+        .map(poise::serenity_prelude::UserId)
+        .collect();
+
     if let Err(why) = poise::Framework::build()
         .prefix("&")
         .token(token)
-        .user_data_setup(move |_ctx, _ready, _framework| Box::pin(async move {
+        .user_data_setup(move |ctx, _ready, _framework| Box::pin(async move {
+            // the background checks share these with the commands
+            let steam_links = Arc::new(Store::load("steam_links.json"));
+            let steam_watches = Arc::new(Store::load("steam_watches.json"));
+            let steam_news = Arc::new(Store::load("steam_news.json"));
+            let steam_presence = Arc::new(Store::load("steam_presence.json"));
+            let usage = Arc::new(UsageTracker::from_env());
+            let servers = Arc::new(Store::load("servers.json"));
+
+            start_price_checks(ctx.clone(), steam_watches.clone());
+            start_news_checks(ctx.clone(), steam_news.clone());
+            start_presence_checks(ctx.clone(), steam_links.clone(), steam_presence.clone(), usage.clone());
+            start_status_updates(ctx.clone(), servers.clone());
+
             Ok(
                 Data {
-                    runtimes: RwLock::new(Vec::new())
+                    runtimes: RwLock::new(Vec::new()),
+                    movie_provider: provider_from_env(),
+                    watchlists: Store::load("watchlists.json"),
+                    search_settings: Store::load("search_settings.json"),
+                    search_providers: SearchProviders::from_env(),
+                    mod_logs: Store::load("mod_logs.json"),
+                    steam_apps: AppList::new(),
+                    steam_links,
+                    steam_watches,
+                    steam_news,
+                    steam_presence,
+                    usage,
+                    servers,
                 }
             )
         }))
         .options(poise::FrameworkOptions {
+            owners,
             prefix_options: poise::PrefixFrameworkOptions {
                 case_insensitive_commands: true,
                 edit_tracker: Some(poise::EditTracker::for_timespan(
@@ -96,10 +152,54 @@ async fn main() {
         .command(register(), |f| f)
         .command(ping(), |f| f)
         .command(movie(), |f| f)
-        .command(steam(), |f| f.subcommand(user(), |s| s))
+        .command(steam(), |f| {
+            f.subcommand(user(), |s| s)
+                .subcommand(id(), |s| s)
+                .subcommand(game(), |s| s)
+                .subcommand(games(), |s| s)
+                .subcommand(achievements(), |s| s)
+                .subcommand(workshop(), |s| s)
+                .subcommand(together(), |s| s)
+                .subcommand(link(), |s| s)
+                .subcommand(unlink(), |s| s)
+                .subcommand(linked(), |s| s)
+                .subcommand(watch(), |s| s)
+                .subcommand(watches(), |s| s)
+                .subcommand(unwatch(), |s| s)
+                .subcommand(alerts(), |s| s)
+                .subcommand(follow(), |s| s)
+                .subcommand(unfollow(), |s| s)
+                .subcommand(following(), |s| s)
+                .subcommand(presence(), |s| s)
+                .subcommand(sessions(), |s| s)
+        })
         .command(code(), |f| f)
         .command(clear(), |f| f)
-        .command(google(), |f| f)
+        .command(modlog(), |f| f)
+        .command(google(), |f| f.subcommand(web(), |s| s).subcommand(images(), |s| s))
+        .command(watchlist(), |f| {
+            f.subcommand(add(), |s| s)
+                .subcommand(remove(), |s| s)
+                .subcommand(list(), |s| s)
+                .subcommand(random(), |s| s)
+                .subcommand(watched(), |s| s)
+        })
+        .command(movienight(), |f| f)
+        .command(usage(), |f| f)
+        .command(server(), |f| f)
+        .command(servers(), |f| {
+            f.subcommand(save(), |s| s)
+                .subcommand(forget(), |s| s)
+                .subcommand(saved(), |s| s)
+                .subcommand(status(), |s| s)
+        })
+        .command(searchsettings(), |f| {
+            f.subcommand(safesearch(), |s| s)
+                .subcommand(block(), |s| s)
+                .subcommand(unblock(), |s| s)
+                .subcommand(blocklist(), |s| s)
+                .subcommand(provider(), |s| s)
+        })
         .run()
         .await
     {